@@ -0,0 +1,27 @@
+use wgpu_renderer::app::{HeadlessApp, HeadlessOptions};
+
+// Renders a single frame without opening a window and writes it to disk
+// usage: cargo run --example headless -- [output.png] [--fallback]
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let force_fallback_adapter = args.iter().any(|arg| arg == "--fallback");
+    let output = args.iter()
+        .find(|arg| !arg.starts_with("--"))
+        .cloned()
+        .unwrap_or_else(|| String::from("headless.png"));
+
+    pollster::block_on(async {
+        let mut app = HeadlessApp::new(HeadlessOptions {
+            force_fallback_adapter,
+            ..Default::default()
+        }).await?;
+
+        app.render_to_file(&output).await
+    })?;
+
+    println!("Saved frame to {}", output);
+
+    Ok(())
+}
@@ -1,85 +1,35 @@
 use winit::{
     event::*,
     window::Window,
-    dpi::PhysicalPosition,
 };
 
-use cgmath::prelude::*;
-
-use wgpu::util::DeviceExt;
-
-use crate::render::create_render_pipeline;
-use crate::texture::Texture;
-use crate::camera::{
-    Camera,
-    CameraUniform,
-    CameraBuffer,
-    CameraController,
-    Projection,
-};
-use crate::camera::{
-    OrthoCamera,
-    OrthoCameraUniform,
-    OrthoCameraBuffer,
-    OrthoProjection,
+use crate::camera::{CameraController, CameraMode, OrbitController};
+use crate::app::renderer::{Renderer, RendererOptions};
+use crate::app::screenshot::{screenshot_file_name, PendingScreenshot};
+use crate::model::RayHit;
+use crate::render::{
+    choose_sample_count,
+    supported_sample_counts,
+    AutoExposure,
+    Bloom,
+    ChromaticAberration,
+    ColorGrading,
+    Fxaa,
+    IdBufferHit,
+    IdReadback,
+    PostProcessOptions,
+    Vignette,
 };
+use crate::scene::Scene;
+use crate::text::Text;
+use crate::texture::TextureCapture;
 
-use crate::primitives::{
-    Vertex,
-    triangle::{TriangleVertex, Triangle},
-    quad::{QuadVertex, Quad, QuadOptions},
-};
-use crate::instance::{Instance, InstanceRaw, InstanceBuffer};
-use crate::model::{ModelVertex, Model};
-use crate::light::Light;
-use crate::resources;
-
-const INDICES: &[u16] = &[
-    0, 1, 4,
-    1, 2, 4,
-    2, 3, 4,
-];
-
-const SPACE_BETWEEN: f32 = 3.0;
-
-pub struct Camera2D {
-    camera: OrthoCamera,
-    uniform: OrthoCameraUniform,
-    buffer: OrthoCameraBuffer,
-    projection: OrthoProjection,
-}
-
-pub fn create_instances(amount: u32) -> Vec<Instance> {
-    // let displacement: cgmath::Vector3<f32> = cgmath::Vector3::new(amount as f32 * 0.5, 0.0, amount as f32 * 0.5);
-
-    let instances = (0..amount).flat_map(|z| {
-        (0..amount).map(move |x| {
-            let x = SPACE_BETWEEN * (x as f32 - amount as f32 / 2.0);
-            let z = SPACE_BETWEEN * (z as f32 - amount as f32 / 2.0);
-
-            let position = cgmath::Vector3 {
-                x: x as f32,
-                y: 0.0,
-                z: z as f32,
-            };
-
-            let rotation = if position.is_zero() {
-                // this is needed so an object at (0, 0, 0) won't get scaled to zero
-                // as Quaternions can effect scale if they're not created correctly
-                cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
-            } else {
-                cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-            };
-
-            Instance {
-                position,
-                rotation,
-            }
-        })
-    }).collect::<Vec<_>>();
-
-    instances
-}
+// loaded on start up, relative to the assets folder
+pub const DEFAULT_SCENE: &str = "scenes/default.json";
+// MSAA on start up, lowered to what the adapter supports. F4 switches it while running
+pub const SAMPLE_COUNT: u32 = 4;
+// the LUT color grading uses once it is turned on
+pub const DEFAULT_LUT: &str = "luts/warm.cube";
 
 pub struct App {
     pub surface: wgpu::Surface,
@@ -92,33 +42,30 @@ pub struct App {
     // unsafe references to the window's resources.
     pub window: Rc<Window>,
 
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub render_pipeline_2d: wgpu::RenderPipeline,
-    pub light_render_pipeline: wgpu::RenderPipeline,
-    // pub vertex_buffer: wgpu::Buffer,
-    // pub index_buffer: wgpu::Buffer,
-    // pub diffuse_bind_group: wgpu::BindGroup,
-    // pub diffuse_texture: Texture,
-    pub camera: Camera,
-    pub camera_uniform: CameraUniform,
-    pub camera_buffer: CameraBuffer,
+    pub renderer: Renderer,
+    // the MSAA sample counts the adapter supports, see `set_sample_count`
+    pub sample_counts: Vec<u32>,
     pub camera_controller: CameraController,
-    pub projection: Projection,
-
-    pub ortho_camera: Camera2D,
-
-    pub instances: Vec<Instance>,
-    pub instance_buffer: InstanceBuffer,
-    pub depth_texture: Texture,
-    pub obj_model: Model,
-
-    pub light: Light,
-    pub light_model: Model,
-
-    pub quad_model: Quad,
-    // pub quad_model_too: Quad,
+    pub orbit_controller: OrbitController,
+    pub camera_mode: CameraMode,
 
     pub mouse_pressed: bool,
+    // in physical pixels, None until the cursor has been over the window
+    pub cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+
+    // what was under the cursor the last time the left button was pressed
+    pub picked: Option<RayHit>,
+    // the same from the id buffer, when the renderer has one
+    pub picked_id: Option<IdBufferHit>,
+    pub pending_pick: Option<IdReadback>,
+
+    // file name the next rendered frame is saved to, see `request_screenshot`
+    pub screenshot_request: Option<String>,
+    pub pending_screenshot: Option<PendingScreenshot>,
+
+    // frames per second in the top left corner, `fps_text` is its index in the renderer's text
+    pub show_fps: bool,
+    fps_text: usize,
 }
 
 use std::rc::Rc;
@@ -157,9 +104,12 @@ impl App {
             },
         ).await.unwrap();
 
+        // lets MSAA use every sample count the adapter has for a format, not just the
+        // guaranteed ones
+        let features = adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                features,
                 // WebGL does not support all wgpu features
                 // disable them when building for the web
                 limits: if cfg!(target_arch = "wasm32") {
@@ -176,15 +126,21 @@ impl App {
         ).await.unwrap();
 
         let surface_caps = surface.get_capabilities(&adapter);
-        // This only supports Shader code that assumes an sRGB surface texture. Using a different
-        // one will result all the colors coming out darker. If you want to support non
-        // sRGB surfaces, you'll need to account for that when drawing to the frame.
+        // The blit at the end of the frame encodes to sRGB itself for surfaces that can't,
+        // but a surface that does it in hardware is preferred
         let surface_format = surface_caps.formats.iter()
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
+        // screenshots are copied straight out of the surface when it allows it,
+        // otherwise the frame is rendered a second time into a texture that does
+        let usage = if surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -195,320 +151,31 @@ impl App {
 
         surface.configure(&device, &config);
 
-        // let diffuse_bytes = include_bytes!("../assets/mario-sprite.png");
-        // let diffuse_texture = Texture::from_bytes(&device, &queue, diffuse_bytes, "mario-sprite.png").unwrap();
-
-        let texture_bind_group_layout = device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float {
-                                filterable: true,
-                            },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // this should match the filterable field of the corresponding Texture
-                        // Entry above
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    // normal map
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            }
-        );
-
-        // let diffuse_bind_group = device.create_bind_group(
-        //     &wgpu::BindGroupDescriptor {
-        //         layout: &texture_bind_group_layout,
-        //         entries: &[
-        //             wgpu::BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-        //             },
-        //             wgpu::BindGroupEntry {
-        //                 binding: 1,
-        //                 resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-        //             }
-        //         ],
-        //         label: Some("diffuse_bind_group"),
-        //     }
-        // );
-
-        // let camera = Camera {
-        //     // posiition the camera one unit up and 2 units back
-        //     // +z is out of the screen
-        //     eye: (0.0, 1.0, 2.0).into(),
-        //     // we have it look at the origin
-        //     target: (0.0, 0.0, 0.0).into(),
-        //     // which way is up?
-        //     up: cgmath::Vector3::unit_y(),
-        //     aspect: config.width as f32 / config.height as f32,
-        //     fovy: 45.0,
-        //     znear: 0.1,
-        //     zfar: 100.0,
-        // };
-        let camera = Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
-        let projection = Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
-
-        let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_projection(&camera, &projection);
-        let camera_buffer = CameraBuffer::new(&device, &camera, &mut camera_uniform, &projection);
-        // let camera_bind_group_layout = camera_uniform.create_bind_group_layout(&device);
+        let sample_counts = supported_sample_counts(&adapter, features, &Renderer::multisampled_formats(config.format));
+        let options = RendererOptions::default()
+            .with_sample_count(choose_sample_count(&sample_counts, SAMPLE_COUNT));
+        let mut renderer = Renderer::new(&device, &queue, &config, options).await.unwrap();
+        renderer.set_scale_factor(window_ref.scale_factor() as f32);
+        // falls back to the initial scene when the file is missing or broken
+        let loaded = async {
+            let scene = Scene::load(DEFAULT_SCENE).await?;
+            renderer.load_scene(&device, &queue, &scene).await
+        }.await;
+        if let Err(e) = loaded {
+            log::error!("Unable to load {}: {:?}", DEFAULT_SCENE, e);
+            renderer.load_scene(&device, &queue, &Scene::initial()).await.unwrap();
+        }
+        // the identity LUT stays in place when this fails, so grading changes nothing
+        if let Err(e) = renderer.load_lut(&device, &queue, DEFAULT_LUT).await {
+            log::error!("Unable to load {}: {:?}", DEFAULT_LUT, e);
+        }
+        renderer.post_processing.options = PostProcessOptions::default()
+            .with_bloom(Bloom::default())
+            .with_fxaa(Fxaa::default());
+        let fps_text = renderer.text.push(Text::new("", [8.0, 8.0], 16.0));
         let camera_controller = CameraController::new(4.0, 0.4);
+        let orbit_controller = OrbitController::new(1.5, 0.005);
 
-        let ortho_cam = OrthoCamera::new((0.0, 0.0, 0.0), [config.width as f32, config.height as f32]);
-        let mut ortho_uniform = OrthoCameraUniform::new();
-        let ortho_projection = OrthoProjection::new(config.width, config.height, -1.0, 1.0);
-        let ortho_buffer =  OrthoCameraBuffer::new(&device, &ortho_cam, &mut ortho_uniform, &ortho_projection);
-        let ortho_camera = Camera2D {
-            camera: ortho_cam,
-            uniform: ortho_uniform,
-            buffer: ortho_buffer,
-            projection: ortho_projection,
-        };
-
-        let instances = create_instances(1); // (10);
-        let instance_buffer = InstanceBuffer::new(&device, &instances);
-
-        let depth_texture = Texture::create_depth_texture(&device, &config, "depth_texture");
-
-        let light = Light::new(&device, [2.0, 2.0, 2.0], [1.0, 1.0, 1.0]);
-
-        // let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        //     label: Some("Shader"),
-        //     source: wgpu::ShaderSource::Wgsl(
-        //         include_str!("../shaders/shader.wgsl").into()
-        //     )
-        // });
-
-        let render_pipline_layout = device.create_pipeline_layout(
-            &wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    &camera_buffer.bind_group_layout,
-                    &light.bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            }
-        );
-
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    include_str!("../shaders/shader.wgsl").into()
-                ),
-            };
-
-            create_render_pipeline(
-                &device,
-                &render_pipline_layout,
-                config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[ModelVertex::layout(), InstanceRaw::layout()],
-                shader,
-                None,
-            )
-        };
-
-        let light_render_pipeline = {
-            let light_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Light Pipeline Layout"),
-                bind_group_layouts: &[&camera_buffer.bind_group_layout, &light.bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Light Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    include_str!("../shaders/light.wgsl").into()
-                ),
-            };
-
-            create_render_pipeline(
-                &device,
-                &light_pipeline_layout,
-                config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[ModelVertex::layout()],
-                shader,
-                None,
-            )
-        };
-
-        // TODO: figure out translating between screen (top/left) and (width/height of objects)
-        // dividing quad width/height by window width.height is closer? but now only drawing a
-        // triangle instead of a quad
-        let quad_model = Quad::new(&device, &config, QuadOptions {
-            position: [0.0, 0.0],
-            color: (10, 207, 131, 0.5),
-            dimensions: (400.0, 200.0),
-        });
-        let render_pipeline_2d = {
-            let render_pipeline_2d_layout = device.create_pipeline_layout(
-                &wgpu::PipelineLayoutDescriptor {
-                    label: Some("2d Render Pipeline Lauout"),
-                    bind_group_layouts: &[
-                        // need camera_2d_buffer.bind_group_layout,
-                        &ortho_camera.buffer.bind_group_layout,
-                        // need triangle.bing_group_layout (or rectangle? whatever we need here)
-                        &quad_model.uniform_buffer.bind_group_layout,
-                    ],
-                    push_constant_ranges: &[],
-                }
-            );
-
-            let shader_2d = wgpu::ShaderModuleDescriptor {
-                label: Some("2D Shader"),
-                source: wgpu::ShaderSource::Wgsl(
-                    include_str!("../shaders/quad.wgsl").into()
-                ),
-            };
-
-            create_render_pipeline(
-                &device,
-                &render_pipeline_2d_layout,
-                config.format,
-                Some(Texture::DEPTH_FORMAT),
-                &[QuadVertex::layout()],
-                shader_2d,
-                // Some(wgpu::BlendState {
-                //     color: wgpu::BlendComponent {
-                //         src_factor: wgpu::BlendFactor::SrcAlpha,
-                //         dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                //         operation: wgpu::BlendOperation::Add,
-                //     },
-                //     alpha: wgpu::BlendComponent::OVER,
-                // }),
-                Some(wgpu::BlendState::ALPHA_BLENDING),
-            )
-        };
-
-        // let render_pipeline = device.create_render_pipeline(
-        //     &wgpu::RenderPipelineDescriptor {
-        //         label: Some("Render Pipeline"),
-        //         layout: Some(&render_pipline_layout),
-        //         vertex: wgpu::VertexState {
-        //             module: &shader,
-        //             entry_point: "vs_main", // 1
-        //             buffers: &[ModelVertex::layout(), InstanceRaw::layout()], // 2
-        //         },
-        //         fragment: Some(wgpu::FragmentState { // 3
-        //             module: &shader,
-        //             entry_point: "fs_main",
-        //             targets: &[Some(wgpu::ColorTargetState { // 4
-        //                 format: config.format,
-        //                 blend: Some(wgpu::BlendState::REPLACE),
-        //                 write_mask: wgpu::ColorWrites::ALL,
-        //             })],
-        //         }),
-        //         primitive: wgpu::PrimitiveState {
-        //             topology: wgpu::PrimitiveTopology::TriangleList,
-        //             strip_index_format: None,
-        //             front_face: wgpu::FrontFace::Ccw,
-        //             cull_mode: Some(wgpu::Face::Back),
-        //             polygon_mode: wgpu::PolygonMode::Fill,
-        //             unclipped_depth: false,
-        //             conservative: false,
-        //         },
-        //         depth_stencil: Some(
-        //             wgpu::DepthStencilState {
-        //                 format: Texture::DEPTH_FORMAT,
-        //                 depth_write_enabled: true,
-        //                 depth_compare: wgpu::CompareFunction::Less,
-        //                 stencil: wgpu::StencilState::default(),
-        //                 bias: wgpu::DepthBiasState::default(),
-        //             }
-        //         ),
-        //         multisample: wgpu::MultisampleState {
-        //             count: 1,
-        //             mask: !0,
-        //             alpha_to_coverage_enabled: false,
-        //         },
-        //         multiview: None,
-        //     }
-        // );
-
-        // let obj_model = resources::load_model(
-        //     // "meshes/cube/cube.obj",
-        //     // "meshes/monkey/lp-monkey.obj",
-        //     // "meshes/monkey/monkey-rev-c.obj",
-        //     "meshes/greg/greg-applied.obj",
-        //     // "meshes/greg/greg-applied-uv.obj",
-        //     &device,
-        //     &queue,
-        //     &texture_bind_group_layout
-        // )
-        // .await
-        // .unwrap();
-
-        let obj_model = resources::load_model_gltf(
-            "meshes/greg/greg_basic_export_applied_uv.gltf",
-            &device,
-            &queue,
-            &texture_bind_group_layout,
-        )
-        .await
-        .unwrap();
-
-        let light_model = resources::load_model(
-            "meshes/light/light-object.obj",
-            &device,
-            &queue,
-            &texture_bind_group_layout,
-        )
-        .await
-        .unwrap();
-
-        // let triangle_model = Triangle::new([
-        //     TriangleVertex { position: [0.0, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
-        //     TriangleVertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
-        //     TriangleVertex { position: [0.5, -0.5, 0.0], color: [0.0, 0.0, 1.0] },
-        // ], &device);
-
-        // let vertex_buffer = device.create_buffer_init(
-        //     &wgpu::util::BufferInitDescriptor {
-        //         label: Some("Vertex Buffer"),
-        //         contents: bytemuck::cast_slice(VERTICES),
-        //         usage: wgpu::BufferUsages::VERTEX,
-        //     }
-        // );
-        //
-        // let index_buffer = device.create_buffer_init(
-        //     &wgpu::util::BufferInitDescriptor {
-        //         label: Some("Index Buffer"),
-        //         contents: bytemuck::cast_slice(INDICES),
-        //         usage: wgpu::BufferUsages::INDEX,
-        //     }
-        // );
-        //
         Self {
             window: window_ref,
             surface,
@@ -517,34 +184,24 @@ impl App {
             config,
             // size should not be 0 as that can lead to app crashes
             size,
-            render_pipeline,
-            light_render_pipeline,
-            render_pipeline_2d,
-            // vertex_buffer,
-            // index_buffer,
-            // diffuse_bind_group,
-            // diffuse_texture,
-            camera,
-            camera_uniform,
-            camera_buffer,
+            renderer,
+            sample_counts,
             camera_controller,
-            projection,
-
-            ortho_camera,
+            orbit_controller,
+            camera_mode: CameraMode::default(),
 
-            instances,
-            instance_buffer,
-
-            depth_texture,
-            obj_model,
+            mouse_pressed: false,
+            cursor_position: None,
 
-            light,
-            light_model,
+            picked: None,
+            picked_id: None,
+            pending_pick: None,
 
-            // triangle_model,
-            quad_model,
+            screenshot_request: None,
+            pending_screenshot: None,
 
-            mouse_pressed: false,
+            show_fps: false,
+            fps_text,
         }
     }
 
@@ -559,16 +216,20 @@ impl App {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-            self.projection.resize(new_size.width, new_size.height);
-            self.ortho_camera.projection.resize(new_size.width, new_size.height);
-            self.ortho_camera.camera.resize(new_size.width, new_size.height);
-            // update depth_texture after config
-            // otherwise, the app will crash because depth_texture will be a different size from
-            // the surface
-            self.depth_texture = Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.renderer.resize(&self.device, &self.config);
         }
     }
 
+    // errors when the adapter doesn't support `sample_count`, see `sample_counts`
+    pub fn set_sample_count(&mut self, sample_count: u32) -> anyhow::Result<()> {
+        self.renderer.set_sample_count(&self.device, &self.sample_counts, sample_count)
+    }
+
+    // the window moved to a screen with a different dpi, a resize to the new size follows
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.renderer.set_scale_factor(scale_factor as f32);
+    }
+
     pub fn input(&mut self, event: &WindowEvent) -> bool {
         // TODO: just returning false for now since there are no events we want to capture
         // false
@@ -582,74 +243,271 @@ impl App {
                 },
                 ..
             } => {
-                let [lx, ly, lz] = self.light.uniform.position;
-                let _light_key = match key {
-                    VirtualKeyCode::B => {
-                        self.light.update_position([lx + -1.0, ly, lz]);
-                        true
-                    },
-                    VirtualKeyCode::M => {
-                        self.light.update_position([lx + 1.0, ly, lz]);
-                        true
-                    },
-                    VirtualKeyCode::H => {
-                        self.light.update_position([lx, ly + 1.0, lz]);
-                        true
-                    },
-                    VirtualKeyCode::N => {
-                        self.light.update_position([lx, ly - 1.0, lz]);
-                        true
-                    },
-                    VirtualKeyCode::J => {
-                        self.light.update_position([lx, ly, lz - 1.0]);
-                        true
-                    },
-                    VirtualKeyCode::K => {
-                        self.light.update_position([lx, ly, lz + 1.0]);
-                        true
-                    },
-                    _ => false,
-                };
-
-                self.camera_controller.process_keyboard(*key, *state)
+                // the movement keys control the first light
+                if let Some(light) = self.renderer.lights.get_mut(0) {
+                    let [lx, ly, lz] = light.position;
+                    match key {
+                        VirtualKeyCode::B => light.update_position([lx + -1.0, ly, lz]),
+                        VirtualKeyCode::M => light.update_position([lx + 1.0, ly, lz]),
+                        VirtualKeyCode::H => light.update_position([lx, ly + 1.0, lz]),
+                        VirtualKeyCode::N => light.update_position([lx, ly - 1.0, lz]),
+                        VirtualKeyCode::J => light.update_position([lx, ly, lz - 1.0]),
+                        VirtualKeyCode::K => light.update_position([lx, ly, lz + 1.0]),
+                        _ => {},
+                    }
+                }
+
+                // save the next frame as a png
+                if *key == VirtualKeyCode::P && *state == ElementState::Pressed {
+                    self.request_screenshot();
+                    return true;
+                }
+
+                // switch between flying and orbiting
+                if *key == VirtualKeyCode::O && *state == ElementState::Pressed {
+                    self.toggle_camera_mode();
+                    return true;
+                }
+
+                // orbit around the whole scene
+                if *key == VirtualKeyCode::F && *state == ElementState::Pressed {
+                    self.frame_scene();
+                    return true;
+                }
+
+                // toggle the frames per second readout
+                if *key == VirtualKeyCode::F3 && *state == ElementState::Pressed {
+                    self.show_fps = !self.show_fps;
+                    return true;
+                }
+
+                // cycle through the MSAA sample counts the adapter supports, 1 is always one
+                if *key == VirtualKeyCode::F4 && *state == ElementState::Pressed {
+                    let current = self.sample_counts.iter()
+                        .position(|count| *count == self.renderer.sample_count())
+                        .unwrap_or(0);
+                    let next = self.sample_counts[(current + 1) % self.sample_counts.len()];
+                    match self.set_sample_count(next) {
+                        Ok(()) => log::info!("MSAA set to {}x", next),
+                        Err(e) => log::error!("Unable to switch MSAA to {}x: {:?}", next, e),
+                    }
+                    return true;
+                }
+
+                // toggle the shadow map debug view
+                if *key == VirtualKeyCode::V && *state == ElementState::Pressed {
+                    self.renderer.show_shadow_debug = !self.renderer.show_shadow_debug;
+                    return true;
+                }
+
+                if *state == ElementState::Pressed {
+                    let tonemapping = &mut self.renderer.tonemapping;
+                    let tonemap_options = &mut tonemapping.options;
+                    match key {
+                        // cycle through the tonemapping curves
+                        VirtualKeyCode::T => tonemap_options.tonemapper = tonemap_options.tonemapper.next(),
+                        // the histogram is only built where compute shaders are supported
+                        VirtualKeyCode::Y if tonemapping.histogram.is_none() => {
+                            log::warn!("Auto exposure needs compute shaders, which this adapter doesn't support");
+                        },
+                        // toggle auto exposure
+                        VirtualKeyCode::Y => toggle(&mut tonemap_options.auto_exposure, AutoExposure::default()),
+                        // half a stop darker or brighter
+                        VirtualKeyCode::LBracket => tonemap_options.exposure -= 0.5,
+                        VirtualKeyCode::RBracket => tonemap_options.exposure += 0.5,
+                        _ => {},
+                    }
+
+                    if matches!(key, VirtualKeyCode::T | VirtualKeyCode::Y | VirtualKeyCode::LBracket | VirtualKeyCode::RBracket) {
+                        return true;
+                    }
+
+                    // 1-5 toggle the screen space effects, back to their default settings
+                    let post_options = &mut self.renderer.post_processing.options;
+                    match key {
+                        VirtualKeyCode::Key1 => toggle(&mut post_options.fxaa, Fxaa::default()),
+                        VirtualKeyCode::Key2 => toggle(&mut post_options.bloom, Bloom::default()),
+                        VirtualKeyCode::Key3 => toggle(&mut post_options.vignette, Vignette::default()),
+                        VirtualKeyCode::Key4 => toggle(&mut post_options.chromatic_aberration, ChromaticAberration::default()),
+                        VirtualKeyCode::Key5 => toggle(&mut post_options.color_grading, ColorGrading::default()),
+                        _ => {},
+                    }
+
+                    if matches!(key, VirtualKeyCode::Key1 | VirtualKeyCode::Key2 | VirtualKeyCode::Key3 | VirtualKeyCode::Key4 | VirtualKeyCode::Key5) {
+                        return true;
+                    }
+                }
+
+                match self.camera_mode {
+                    CameraMode::Fly => self.camera_controller.process_keyboard(*key, *state),
+                    CameraMode::Orbit => self.orbit_controller.process_keyboard(*key, *state),
+                }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+                true
             },
             WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.process_scroll(delta);
+                match self.camera_mode {
+                    CameraMode::Fly => self.camera_controller.process_scroll(delta),
+                    CameraMode::Orbit => self.orbit_controller.process_scroll(delta),
+                }
                 true
             },
             WindowEvent::MouseInput {
-                button: MouseButton::Left,
+                button,
                 state,
                 ..
             } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
-                true
+                // the orbit controller keeps track of every button, it pans with the others
+                let orbit_handled = self.orbit_controller.process_mouse_button(*button, *state);
+                if *button == MouseButton::Left {
+                    self.mouse_pressed = *state == ElementState::Pressed;
+                    if self.mouse_pressed {
+                        self.pick_at_cursor();
+                    }
+                    return true;
+                }
+
+                orbit_handled && self.camera_mode == CameraMode::Orbit
             },
             _ => false,
         }
     }
 
+    // mouse movement from the device, which keeps coming when the cursor leaves the window
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        match self.camera_mode {
+            CameraMode::Fly => if self.mouse_pressed {
+                self.camera_controller.process_mouse(mouse_dx, mouse_dy)
+            },
+            CameraMode::Orbit => self.orbit_controller.process_mouse(mouse_dx, mouse_dy),
+        }
+    }
+
+    // Picks whatever is under the cursor, straight away on the cpu and through the id buffer
+    // (if enabled) once it has been read back
+    pub fn pick_at_cursor(&mut self) {
+        let Some(position) = self.cursor_position else {
+            return;
+        };
+
+        self.picked = self.renderer.pick((position.x as f32, position.y as f32));
+        if let Some(hit) = &self.picked {
+            log::info!("Picked instance {} mesh {} at {:?}", hit.instance, hit.mesh, hit.position);
+        }
+
+        if self.pending_pick.is_none() {
+            self.pending_pick = self.renderer.read_id(&self.device, &self.queue, position.x as u32, position.y as u32);
+        }
+    }
+
+    pub fn set_camera_mode(&mut self, camera_mode: CameraMode) {
+        if camera_mode == CameraMode::Orbit && self.camera_mode != CameraMode::Orbit {
+            // orbit whatever is in front of the camera instead of jumping to the old target
+            self.orbit_controller.attach(&self.renderer.camera);
+        }
+
+        self.camera_mode = camera_mode;
+    }
+
+    pub fn toggle_camera_mode(&mut self) {
+        self.set_camera_mode(match self.camera_mode {
+            CameraMode::Fly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Fly,
+        });
+    }
+
+    // switch to orbiting and back off until everything in the scene is in view
+    pub fn frame_scene(&mut self) {
+        let bounds = self.renderer.scene_bounds();
+
+        self.set_camera_mode(CameraMode::Orbit);
+        self.orbit_controller.frame(&mut self.renderer.camera, &self.renderer.projection, &bounds);
+    }
+
     pub fn update(&mut self, dt: instant::Duration) {
         // camera
-        self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera_uniform.update_view_projection(&self.camera, &self.projection);
-        self.queue.write_buffer(&self.camera_buffer.buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        match self.camera_mode {
+            CameraMode::Fly => self.camera_controller.update_camera(&mut self.renderer.camera, dt),
+            CameraMode::Orbit => self.orbit_controller.update_camera(&mut self.renderer.camera, dt),
+        }
+        // `get_mut` lays the text out again, so only when the readout changes. Hidden, it is
+        // emptied once and left alone
+        let fps = if self.show_fps {
+            format!("{:.0} fps", 1.0 / dt.as_secs_f32().max(f32::EPSILON))
+        } else {
+            String::new()
+        };
+        if self.renderer.text.get(self.fps_text).is_some_and(|text| text.content != fps) {
+            if let Some(text) = self.renderer.text.get_mut(self.fps_text) {
+                text.content = fps;
+            }
+        }
+        self.renderer.update(&self.device, &self.queue);
 
-        self.ortho_camera.uniform.update_view_projection(&self.ortho_camera.camera, &self.ortho_camera.projection);
-        self.queue.write_buffer(&self.ortho_camera.buffer.buffer, 0, bytemuck::cast_slice(&[self.ortho_camera.uniform]));
+        if let Some(pending) = &mut self.pending_pick {
+            self.device.poll(wgpu::Maintain::Poll);
 
-        self.quad_model.uniform.update_model_from_position(self.quad_model.options.position);
-        self.queue.write_buffer(&self.quad_model.uniform_buffer.buffer, 0, bytemuck::cast_slice(&[self.quad_model.uniform]));
-        // light
-        // let prev_position: cgmath::Vector3<_> = self.light.uniform.position.into();
+            if let Some(result) = pending.try_finish() {
+                match result {
+                    Ok(hit) => self.picked_id = hit,
+                    Err(e) => log::error!("Unable to read the id buffer: {:?}", e),
+                }
+                self.pending_pick = None;
+            }
+        }
 
-        // Animated light effect
-        // self.light.uniform.position = (
-        //     cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(60.0 * dt.as_secs_f32()))
-        //     * prev_position
-        // ).into();
+        if let Some(pending) = &mut self.pending_screenshot {
+            // lets the map callback of the capture run on native
+            self.device.poll(wgpu::Maintain::Poll);
 
-        self.queue.write_buffer(&self.light.buffer, 0, bytemuck::cast_slice(&[self.light.uniform]));
+            if let Some(result) = pending.try_save() {
+                if let Err(e) = result {
+                    log::error!("Unable to save screenshot {}: {:?}", pending.file_name, e);
+                }
+                self.pending_screenshot = None;
+            }
+        }
+    }
+
+    // Saves the next rendered frame as `screenshot-<timestamp>.png`, in the working directory on
+    // native and as a download in the browser
+    pub fn request_screenshot(&mut self) {
+        self.request_screenshot_to(screenshot_file_name());
+    }
+
+    pub fn request_screenshot_to(&mut self, file_name: impl Into<String>) {
+        self.screenshot_request = Some(file_name.into());
+    }
+
+    // The surface format is kept as is, capturing takes care of BGRA surfaces
+    fn capture_screenshot(&self, surface_texture: &wgpu::Texture) -> anyhow::Result<TextureCapture> {
+        if self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            return TextureCapture::new(&self.device, &self.queue, surface_texture);
+        }
+
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Screenshot Target"),
+            size: surface_texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Screenshot Encoder"),
+            }
+        );
+        self.renderer.render(&mut encoder, &view);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        TextureCapture::new(&self.device, &self.queue, &target)
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -665,110 +523,33 @@ impl App {
             }
         );
 
-        // extra block here is because bgin_render_pass needs a mut ref of encoder
-        // but `encoder.finish()` can not be called until we release the mut borrow.
-        // an alternative approach would be to use `drop(render_pass)` before calling
-        // `encoder.finish()`
-        {
-            let mut render_pass = encoder.begin_render_pass(
-                &wgpu::RenderPassDescriptor {
-                    label: Some("Render Pass"),
-                    color_attachments: &[
-                        // this is what @location(0) in fragment shader targets
-                        Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0.1,
-                                    g: 0.2,
-                                    b: 0.3,
-                                    a: 1.0,
-                                }),
-                                store: true,
-                            },
-                        })
-                    ],
-                    depth_stencil_attachment: Some(
-                        wgpu::RenderPassDepthStencilAttachment {
-                            view: &self.depth_texture.view,
-                            depth_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(1.0),
-                                store: true,
-                            }),
-                            stencil_ops: None,
-                        }
-                    ),
-                }
-            );
-
-
-            // render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            // render_pass.set_bind_group(1, &self.camera_buffer.bind_group, &[]);
-
-            // render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            // Make sure if you add new instances to the Vec,
-            // that you recreate the instance_buffer and as well as camera_bind_group, otherwise your new instances won't show up correctly.
-            render_pass.set_vertex_buffer(1, self.instance_buffer.buffer.slice(..));
-
-            // render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            // render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
-            // render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..self.instances.len() as _);
-            // let mesh = &self.obj_model.meshes[0];
-            // let material = &self.obj_model.materials[mesh.material];
-
-            use crate::light::DrawLight;
-            render_pass.set_pipeline(&self.light_render_pipeline);
-            render_pass.draw_light_model(
-                // &self.obj_model,
-                &self.light_model,
-                &self.camera_buffer.bind_group,
-                &self.light.bind_group,
-            );
-
-            use crate::model::DrawModel;
-            // render_pass.draw_mesh_instanced(
-            //     mesh,
-            //     material,
-            //     0..self.instances.len() as u32,
-            //     &self.camera_buffer.bind_group
-            // );
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.draw_model_instanced(
-                &self.obj_model,
-                0..self.instances.len() as u32,
-                &self.camera_buffer.bind_group,
-                &self.light.bind_group,
-            );
-
-            // use crate::primitives::triangle::DrawTriangle;
-            //
-            // render_pass.set_pipeline(&self.render_pipeline_2d);
-            // render_pass.draw_triangle(
-            //     &self.triangle_model,
-            //     &self.camera_buffer.bind_group,
-            // );
-            use crate::primitives::quad::DrawQuad;
-
-            render_pass.set_pipeline(&self.render_pipeline_2d);
-            render_pass.draw_quad(
-                &self.quad_model,
-                &self.ortho_camera.buffer.bind_group,
-            );
-
-            // println!("OrthoView {:?}", self.ortho_camera.uniform.view_position);
-            // println!("OrthoProjection {:?}", self.ortho_camera.uniform.view_projection);
-            // println!("QuadModel {:?}", self.quad_model.model());
-        }
+        self.renderer.render(&mut encoder, &view);
 
         // submit will accept anything that implements IntoIter
         // these lines tell wgpu to finish the command buffer and submit it to the gpu's render
         // queue
         self.queue.submit(std::iter::once(encoder.finish()));
+
+        // a second request while one is still being copied back waits for it to finish
+        if self.pending_screenshot.is_none() {
+            if let Some(file_name) = self.screenshot_request.take() {
+                match self.capture_screenshot(&output.texture) {
+                    Ok(capture) => self.pending_screenshot = Some(PendingScreenshot { capture, file_name }),
+                    Err(e) => log::error!("Unable to capture screenshot: {:?}", e),
+                }
+            }
+        }
+
         output.present();
 
         Ok(())
     }
 }
 
+// `None` to `Some(value)` and back
+fn toggle<T>(option: &mut Option<T>, value: T) {
+    *option = match option {
+        Some(_) => None,
+        None => Some(value),
+    };
+}
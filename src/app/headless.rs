@@ -0,0 +1,155 @@
+use anyhow::*;
+
+use crate::app::renderer::{Renderer, RendererOptions};
+use crate::render::{choose_sample_count, supported_sample_counts};
+use crate::scene::Scene;
+use crate::texture::capture_texture;
+
+pub struct HeadlessOptions {
+    pub width: u32,
+    pub height: u32,
+    // use a software adapter (ie llvmpipe/WARP), useful for CI where there is no gpu
+    pub force_fallback_adapter: bool,
+    // MSAA, lowered to what the adapter supports
+    pub sample_count: u32,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            force_fallback_adapter: false,
+            sample_count: 1,
+        }
+    }
+}
+
+// Renders the same scene as `App` but without a window or surface. Frames are drawn into an
+// offscreen texture and copied back into an `image::RgbaImage`
+pub struct HeadlessApp {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    // there is no surface, but the renderer sizes its targets from a SurfaceConfiguration
+    pub config: wgpu::SurfaceConfiguration,
+    pub target: wgpu::Texture,
+    pub target_view: wgpu::TextureView,
+    // the MSAA sample counts this adapter supports, see `set_sample_count`
+    pub sample_counts: Vec<u32>,
+
+    pub renderer: Renderer,
+}
+
+impl HeadlessApp {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub async fn new(options: HeadlessOptions) -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: options.force_fallback_adapter,
+            },
+        ).await.ok_or_else(|| anyhow!("Unable to find a suitable adapter"))?;
+
+        let features = adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                features,
+                // software adapters often can't meet the default limits, so ask
+                // for whatever this adapter supports
+                limits: adapter.limits(),
+                label: Some("Headless Device"),
+            },
+            None,
+        ).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: Self::FORMAT,
+            width: options.width.max(1),
+            height: options.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+
+        let (target, target_view) = Self::create_target(&device, &config);
+        let sample_counts = supported_sample_counts(&adapter, features, &Renderer::multisampled_formats(config.format));
+        let renderer_options = RendererOptions::default()
+            .with_sample_count(choose_sample_count(&sample_counts, options.sample_count));
+        let mut renderer = Renderer::new(&device, &queue, &config, renderer_options).await?;
+        renderer.load_scene(&device, &queue, &Scene::initial()).await?;
+
+        Ok(Self {
+            device,
+            queue,
+            config,
+            target,
+            target_view,
+            sample_counts,
+            renderer,
+        })
+    }
+
+    fn create_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (wgpu::Texture, wgpu::TextureView) {
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (target, target_view)
+    }
+
+    // errors when the adapter doesn't support `sample_count`, see `sample_counts`
+    pub fn set_sample_count(&mut self, sample_count: u32) -> Result<()> {
+        self.renderer.set_sample_count(&self.device, &self.sample_counts, sample_count)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.config.width = width;
+            self.config.height = height;
+            (self.target, self.target_view) = Self::create_target(&self.device, &self.config);
+            self.renderer.resize(&self.device, &self.config);
+        }
+    }
+
+    pub async fn render(&mut self) -> Result<image::RgbaImage> {
+        self.renderer.update(&self.device, &self.queue);
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Headless Render Encoder"),
+            }
+        );
+
+        self.renderer.render(&mut encoder, &self.target_view);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        capture_texture(&self.device, &self.queue, &self.target).await
+    }
+
+    pub async fn render_to_file<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
+        let image = self.render().await?;
+        image.save(path)?;
+
+        Ok(())
+    }
+}
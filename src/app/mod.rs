@@ -1,4 +1,10 @@
 pub mod app;
+pub mod headless;
+pub mod passes;
+pub mod renderer;
+pub mod screenshot;
 pub mod window;
 
 pub use app::App;
+pub use headless::{HeadlessApp, HeadlessOptions};
+pub use renderer::{create_instance_grid, Renderer, RendererOptions};
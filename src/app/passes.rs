@@ -0,0 +1,1003 @@
+use crate::render::fullscreen::{
+    create_fullscreen_pipeline,
+    create_input_bind_group,
+    create_input_bind_group_layout,
+    create_input_sampler,
+};
+use crate::render::graph::{
+    AttachmentDescriptor,
+    AttachmentId,
+    AttachmentLoad,
+    AttachmentSize,
+    GraphResources,
+    NodeBuilder,
+    NodeContext,
+    RenderGraph,
+    RenderNode,
+};
+use crate::render::environment::Environment;
+use crate::render::postprocess::{PostProcessOptions, PostProcessing, BLOOM_LEVELS};
+use crate::render::tonemap::{LuminanceHistogram, TonemapUniform, Tonemapping, HDR_FORMAT, LDR_FORMAT};
+use crate::texture::Texture;
+
+use super::Renderer;
+
+// The passes the renderer draws a frame with: shadow maps and the id buffer first, then the
+// scene into "hdr" with the skybox behind it. Its highlights are blurred down the bloom chain, which tonemapping adds
+// back while mapping "hdr" into "ldr". The screen space effects go from there into "graded"
+// and FXAA into "antialiased", each skipped while it is off, and the last one drawn is blitted
+// to the target. The 2d overlay and the shadow map debug view go on top. More passes can be
+// added to `Renderer::graph`, the attachments can be looked up by those names, "depth" and
+// "shadow maps".
+// With MSAA the scene is drawn into "hdr msaa" and resolved into "hdr", and the blit, overlay
+// and debug view into "target msaa", resolved into the target by the last of them
+pub fn create_graph(
+    device: &wgpu::Device,
+    (width, height): (u32, u32),
+    target_format: wgpu::TextureFormat,
+    environment: &Environment,
+    tonemapping: &Tonemapping,
+    post_processing: &PostProcessing,
+    sample_count: u32,
+) -> RenderGraph<Renderer> {
+    let mut graph = RenderGraph::new(width, height, target_format);
+    let depth = graph.add_attachment(
+        AttachmentDescriptor::new("depth", Texture::DEPTH_FORMAT).with_sample_count(sample_count)
+    );
+    let hdr = graph.add_attachment(AttachmentDescriptor::new("hdr", HDR_FORMAT));
+    let ldr = graph.add_attachment(AttachmentDescriptor::new("ldr", LDR_FORMAT));
+    let graded = graph.add_attachment(AttachmentDescriptor::new("graded", LDR_FORMAT));
+    let antialiased = graph.add_attachment(AttachmentDescriptor::new("antialiased", LDR_FORMAT));
+    let shadow_maps = graph.add_external("shadow maps");
+    let id_buffer = graph.add_external("id buffer");
+    let exposure = graph.add_external("exposure");
+
+    // added without MSAA as well so the attachments have the same ids whatever the sample
+    // count, nothing uses them then
+    let hdr_msaa = graph.add_attachment(
+        AttachmentDescriptor::new("hdr msaa", HDR_FORMAT)
+            .with_sample_count(sample_count)
+            .with_resolve_target(hdr)
+    );
+    let target_msaa = graph.add_attachment(
+        AttachmentDescriptor::new("target msaa", target_format)
+            .with_sample_count(sample_count)
+            .with_resolve_target(AttachmentId::TARGET)
+    );
+    let (scene_color, target_color) = if sample_count > 1 {
+        (hdr_msaa, target_msaa)
+    } else {
+        (hdr, AttachmentId::TARGET)
+    };
+
+    graph.add_node(Box::new(ShadowPass { shadow_maps }));
+    graph.add_node(Box::new(IdBufferPass { id_buffer }));
+    graph.add_node(Box::new(OpaquePass { color: scene_color, depth, shadow_maps }));
+    graph.add_node(Box::new(SkyboxPass::new(device, scene_color, depth, environment, sample_count)));
+    if let Some(histogram) = &tonemapping.histogram {
+        graph.add_node(Box::new(AutoExposurePass::new(device, hdr, exposure, histogram)));
+    }
+    let bloom = add_bloom_passes(&mut graph, device, hdr, post_processing);
+    graph.add_node(Box::new(TonemapPass::new(device, hdr, bloom, exposure, ldr, tonemapping, post_processing)));
+    let tonemapped = EffectOutput::tonemapped(ldr);
+    let post_process = EffectPass::post_process(device, vec![tonemapped], graded, post_processing);
+    let fxaa = EffectPass::fxaa(device, vec![tonemapped, post_process.destination], antialiased, post_processing);
+    let blit = BlitPass::new(
+        device,
+        vec![tonemapped, post_process.destination, fxaa.destination],
+        target_color,
+        target_format,
+        sample_count,
+    );
+    graph.add_node(Box::new(post_process));
+    graph.add_node(Box::new(fxaa));
+    graph.add_node(Box::new(blit));
+    graph.add_node(Box::new(OverlayPass { color: target_color, depth }));
+    graph.add_node(Box::new(ShadowDebugPass { color: target_color, depth, shadow_maps }));
+
+    graph
+}
+
+// The bloom chain, "bloom down 0" at half the size of `hdr` to "bloom down 4" at 1/32 and back
+// up through "bloom up 3" to "bloom up 0", which is returned
+fn add_bloom_passes(
+    graph: &mut RenderGraph<Renderer>,
+    device: &wgpu::Device,
+    hdr: AttachmentId,
+    post_processing: &PostProcessing,
+) -> AttachmentId {
+    let mut add_level = |name: &str, level: usize| {
+        let scale = 0.5f32.powi(level as i32 + 1);
+        graph.add_attachment(
+            AttachmentDescriptor::new(&format!("{} {}", name, level), HDR_FORMAT)
+                .with_size(AttachmentSize::Scaled(scale))
+        )
+    };
+    let downsampled = (0..BLOOM_LEVELS).map(|level| add_level("bloom down", level)).collect::<Vec<_>>();
+    let upsampled = (0..BLOOM_LEVELS - 1).map(|level| add_level("bloom up", level)).collect::<Vec<_>>();
+
+    graph.add_node(Box::new(BloomPass::prefilter(device, hdr, downsampled[0], post_processing)));
+    for level in 1..BLOOM_LEVELS {
+        graph.add_node(Box::new(BloomPass::downsample(device, level, downsampled[level - 1], downsampled[level], post_processing)));
+    }
+
+    let mut below = downsampled[BLOOM_LEVELS - 1];
+    for level in (0..BLOOM_LEVELS - 1).rev() {
+        let pass = BloomPass::upsample(device, level, below, downsampled[level], upsampled[level], post_processing);
+        graph.add_node(Box::new(pass));
+        below = upsampled[level];
+    }
+
+    below
+}
+
+pub struct ShadowPass {
+    pub shadow_maps: AttachmentId,
+}
+
+impl RenderNode<Renderer> for ShadowPass {
+    fn name(&self) -> &str {
+        "shadows"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder.write(self.shadow_maps);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        use crate::light::DrawShadow;
+
+        if !renderer.show_scene {
+            return;
+        }
+
+        // the same list `LightManager::update` hands out shadow indices from
+        for layer in 0..renderer.lights.shadow_casters().len() {
+            let mut shadow_pass = context.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &renderer.lights.shadows.layer_views[layer],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            let layer_bind_group = &renderer.lights.shadows.layer_bind_groups[layer];
+
+            shadow_pass.set_pipeline(&renderer.shadow_pipeline);
+            shadow_pass.set_vertex_buffer(1, renderer.instances.buffer.slice(..));
+            shadow_pass.draw_model_shadow_instanced(&renderer.obj_model, renderer.instances.range(), layer_bind_group);
+
+            if let Some(hierarchy) = &renderer.model_hierarchy {
+                shadow_pass.draw_model_hierarchy_shadow(hierarchy, layer_bind_group);
+            }
+        }
+    }
+}
+
+// only draws once `Renderer::enable_id_buffer` is called
+pub struct IdBufferPass {
+    pub id_buffer: AttachmentId,
+}
+
+impl RenderNode<Renderer> for IdBufferPass {
+    fn name(&self) -> &str {
+        "id buffer"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder.write(self.id_buffer);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        let Some(id_buffer) = &renderer.id_buffer else {
+            return;
+        };
+
+        if renderer.show_scene {
+            id_buffer.render(
+                context.encoder,
+                &renderer.obj_model,
+                &renderer.instances.buffer,
+                &renderer.visibility.model_meshes,
+                &renderer.camera_buffer.bind_group,
+            );
+        }
+    }
+}
+
+// clears `color` and draws the light gizmos, the model and the hierarchy
+pub struct OpaquePass {
+    pub color: AttachmentId,
+    pub depth: AttachmentId,
+    pub shadow_maps: AttachmentId,
+}
+
+impl RenderNode<Renderer> for OpaquePass {
+    fn name(&self) -> &str {
+        "opaque"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder
+            .read(self.shadow_maps)
+            .color(self.color, AttachmentLoad::Clear)
+            .depth(self.depth, AttachmentLoad::Clear);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        context.clear_color = renderer.clear_color;
+        // cleared even when the scene is hidden
+        let mut render_pass = context.begin_render_pass("Opaque Pass");
+
+        if !renderer.show_scene {
+            return;
+        }
+
+        // instances added since the last update won't show up until it uploads them
+        render_pass.set_vertex_buffer(1, renderer.instances.buffer.slice(..));
+
+        use crate::light::DrawLight;
+        render_pass.set_pipeline(&renderer.light_render_pipeline);
+        render_pass.draw_lights(
+            &renderer.light_model,
+            &renderer.lights,
+            &renderer.camera_buffer.bind_group,
+        );
+
+        use crate::model::DrawModel;
+        render_pass.set_pipeline(&renderer.render_pipeline);
+        // the same for every mesh, the draw calls only set the first three groups
+        render_pass.set_bind_group(3, &renderer.environment.bind_group, &[]);
+        render_pass.draw_model_visible(
+            &renderer.obj_model,
+            &renderer.visibility.model_meshes,
+            &renderer.camera_buffer.bind_group,
+            &renderer.lights.bind_group,
+        );
+
+        if let Some(hierarchy) = &renderer.model_hierarchy {
+            use crate::model::DrawModelHierarchy;
+            render_pass.draw_model_hierarchy_visible(
+                hierarchy,
+                &renderer.visibility.hierarchy_nodes,
+                &renderer.camera_buffer.bind_group,
+                &renderer.lights.bind_group,
+            );
+        }
+    }
+}
+
+// The environment cubemap on the far plane, after the opaque pass so it is only shaded where
+// nothing else was drawn. Draws nothing without a cubemap, leaving the clear color
+pub struct SkyboxPass {
+    pub color: AttachmentId,
+    pub depth: AttachmentId,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl SkyboxPass {
+    pub fn new(
+        device: &wgpu::Device,
+        color: AttachmentId,
+        depth: AttachmentId,
+        environment: &Environment,
+        sample_count: u32,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&environment.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../shaders/skybox.wgsl").into()
+            ),
+        });
+        // depth tested against what the opaque pass drew, but never written
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self {
+            color,
+            depth,
+            pipeline,
+        }
+    }
+}
+
+impl RenderNode<Renderer> for SkyboxPass {
+    fn name(&self) -> &str {
+        "skybox"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder
+            .color(self.color, AttachmentLoad::Load)
+            .depth(self.depth, AttachmentLoad::Load);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        if !renderer.environment.draws_skybox() {
+            return;
+        }
+
+        let mut render_pass = context.begin_render_pass("Skybox Pass");
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &renderer.environment.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// Averages the luminance of the scene for auto exposure and copies it into the tonemap uniform.
+// Only added where compute shaders are supported
+pub struct AutoExposurePass {
+    pub hdr: AttachmentId,
+    pub exposure: AttachmentId,
+    input_layout: wgpu::BindGroupLayout,
+    input: Option<wgpu::BindGroup>,
+    sampler: wgpu::Sampler,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+}
+
+impl AutoExposurePass {
+    pub fn new(
+        device: &wgpu::Device,
+        hdr: AttachmentId,
+        exposure: AttachmentId,
+        histogram: &LuminanceHistogram,
+    ) -> Self {
+        let input_layout = create_input_bind_group_layout(device, wgpu::ShaderStages::COMPUTE);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Auto Exposure Pipeline Layout"),
+            bind_group_layouts: &[&input_layout, &histogram.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Auto Exposure Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../shaders/exposure.wgsl").into()
+            ),
+        });
+        let create_pipeline = |entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point,
+        });
+
+        Self {
+            hdr,
+            exposure,
+            input: None,
+            sampler: create_input_sampler(device),
+            histogram_pipeline: create_pipeline("build_histogram"),
+            average_pipeline: create_pipeline("average_histogram"),
+            input_layout,
+        }
+    }
+}
+
+impl RenderNode<Renderer> for AutoExposurePass {
+    fn name(&self) -> &str {
+        "auto exposure"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder.read(self.hdr).write(self.exposure);
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, resources: &GraphResources) {
+        self.input = resources.view(self.hdr)
+            .map(|view| create_input_bind_group(device, &self.input_layout, view, &self.sampler));
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        let tonemapping = &renderer.tonemapping;
+        let (Some(input), Some(histogram)) = (&self.input, &tonemapping.histogram) else {
+            return;
+        };
+
+        if tonemapping.auto_exposure().is_none() {
+            return;
+        }
+
+        let (width, height) = renderer.size;
+        {
+            let mut compute_pass = context.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Auto Exposure Pass"),
+            });
+            compute_pass.set_bind_group(0, input, &[]);
+            compute_pass.set_bind_group(1, &histogram.bind_group, &[]);
+
+            compute_pass.set_pipeline(&self.histogram_pipeline);
+            compute_pass.dispatch_workgroups(width.div_ceil(16), height.div_ceil(16), 1);
+
+            compute_pass.set_pipeline(&self.average_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        context.encoder.copy_buffer_to_buffer(
+            &histogram.luminance_buffer,
+            0,
+            &tonemapping.buffer,
+            TonemapUniform::AVERAGE_LUMINANCE_OFFSET,
+            4,
+        );
+    }
+}
+
+// Bloom, exposure and the tonemapping curve, from the hdr attachment into the ldr one
+pub struct TonemapPass {
+    pub hdr: AttachmentId,
+    pub bloom: AttachmentId,
+    pub exposure: AttachmentId,
+    pub ldr: AttachmentId,
+    input_layout: wgpu::BindGroupLayout,
+    input: Option<wgpu::BindGroup>,
+    bloom_input: Option<wgpu::BindGroup>,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl TonemapPass {
+    pub fn new(
+        device: &wgpu::Device,
+        hdr: AttachmentId,
+        bloom: AttachmentId,
+        exposure: AttachmentId,
+        ldr: AttachmentId,
+        tonemapping: &Tonemapping,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let input_layout = create_input_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT);
+        // four bind groups, as many as WebGL2 has
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[
+                &input_layout,
+                &tonemapping.bind_group_layout,
+                &input_layout,
+                &post_processing.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(
+                include_str!("../shaders/fullscreen.wgsl"),
+                include_str!("../shaders/post_process_uniform.wgsl"),
+                include_str!("../shaders/tonemap.wgsl"),
+            ).into()),
+        };
+        let pipeline = create_fullscreen_pipeline(device, &layout, LDR_FORMAT, shader, "fs_main", None, 1);
+
+        Self {
+            hdr,
+            bloom,
+            exposure,
+            ldr,
+            input_layout,
+            input: None,
+            bloom_input: None,
+            sampler: create_input_sampler(device),
+            pipeline,
+        }
+    }
+}
+
+impl RenderNode<Renderer> for TonemapPass {
+    fn name(&self) -> &str {
+        "tonemap"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder
+            .read(self.hdr)
+            .read(self.bloom)
+            .read(self.exposure)
+            .color(self.ldr, AttachmentLoad::Clear);
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, resources: &GraphResources) {
+        self.input = resources.view(self.hdr)
+            .map(|view| create_input_bind_group(device, &self.input_layout, view, &self.sampler));
+        self.bloom_input = resources.view(self.bloom)
+            .map(|view| create_input_bind_group(device, &self.input_layout, view, &self.sampler));
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        let (Some(input), Some(bloom_input)) = (&self.input, &self.bloom_input) else {
+            return;
+        };
+
+        let mut render_pass = context.begin_render_pass("Tonemap Pass");
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, &renderer.tonemapping.bind_group, &[]);
+        render_pass.set_bind_group(2, bloom_input, &[]);
+        render_pass.set_bind_group(3, &renderer.post_processing.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// One step of the bloom chain: the prefilter keeps the highlights of the hdr scene at half its
+// size, downsampling halves a level again and upsampling blurs a level back up to the size of
+// `level`, adding that to it. Nothing is drawn while bloom is off
+pub struct BloomPass {
+    name: String,
+    pub source: AttachmentId,
+    pub level: Option<AttachmentId>,
+    pub destination: AttachmentId,
+    input_layout: wgpu::BindGroupLayout,
+    input: Option<wgpu::BindGroup>,
+    level_input: Option<wgpu::BindGroup>,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl BloomPass {
+    pub fn prefilter(
+        device: &wgpu::Device,
+        hdr: AttachmentId,
+        destination: AttachmentId,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        Self::new(device, "bloom prefilter".to_string(), "fs_prefilter", hdr, None, destination, post_processing)
+    }
+
+    pub fn downsample(
+        device: &wgpu::Device,
+        level: usize,
+        source: AttachmentId,
+        destination: AttachmentId,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let name = format!("bloom downsample {}", level);
+        Self::new(device, name, "fs_downsample", source, None, destination, post_processing)
+    }
+
+    pub fn upsample(
+        device: &wgpu::Device,
+        level: usize,
+        source: AttachmentId,
+        downsampled: AttachmentId,
+        destination: AttachmentId,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let name = format!("bloom upsample {}", level);
+        Self::new(device, name, "fs_upsample", source, Some(downsampled), destination, post_processing)
+    }
+
+    fn new(
+        device: &wgpu::Device,
+        name: String,
+        entry_point: &str,
+        source: AttachmentId,
+        level: Option<AttachmentId>,
+        destination: AttachmentId,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let input_layout = create_input_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT);
+        let mut bind_group_layouts = vec![&input_layout, &post_processing.bind_group_layout];
+        if level.is_some() {
+            bind_group_layouts.push(&input_layout);
+        }
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(
+                include_str!("../shaders/fullscreen.wgsl"),
+                include_str!("../shaders/post_process_uniform.wgsl"),
+                include_str!("../shaders/bloom.wgsl"),
+            ).into()),
+        };
+        let pipeline = create_fullscreen_pipeline(device, &layout, HDR_FORMAT, shader, entry_point, None, 1);
+
+        Self {
+            name,
+            source,
+            level,
+            destination,
+            input_layout,
+            input: None,
+            level_input: None,
+            sampler: create_input_sampler(device),
+            pipeline,
+        }
+    }
+}
+
+impl RenderNode<Renderer> for BloomPass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder.read(self.source);
+        if let Some(level) = self.level {
+            builder.read(level);
+        }
+        builder.color(self.destination, AttachmentLoad::Clear);
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, resources: &GraphResources) {
+        self.input = resources.view(self.source)
+            .map(|view| create_input_bind_group(device, &self.input_layout, view, &self.sampler));
+        self.level_input = self.level
+            .and_then(|level| resources.view(level))
+            .map(|view| create_input_bind_group(device, &self.input_layout, view, &self.sampler));
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        let Some(input) = &self.input else {
+            return;
+        };
+
+        if renderer.post_processing.options.bloom.is_none() {
+            return;
+        }
+
+        let mut render_pass = context.begin_render_pass("Bloom Pass");
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, &renderer.post_processing.bind_group, &[]);
+        if let Some(level_input) = &self.level_input {
+            render_pass.set_bind_group(2, level_input, &[]);
+        }
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// An attachment the screen space effects leave the frame in, only drawn into while `drawn`
+// says its effect is on. The passes after it read the latest one that was
+#[derive(Copy, Clone)]
+pub struct EffectOutput {
+    pub attachment: AttachmentId,
+    drawn: fn(&PostProcessOptions) -> bool,
+}
+
+impl EffectOutput {
+    // the tonemapped frame, the effects start from
+    pub fn tonemapped(attachment: AttachmentId) -> Self {
+        Self { attachment, drawn: |_| true }
+    }
+
+    pub fn is_drawn(&self, options: &PostProcessOptions) -> bool {
+        (self.drawn)(options)
+    }
+}
+
+// one bind group per output the pass might read, in the order of `sources`
+fn create_effect_inputs(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    sources: &[EffectOutput],
+    resources: &GraphResources,
+) -> Vec<Option<wgpu::BindGroup>> {
+    sources.iter()
+        .map(|source| resources.view(source.attachment).map(|view| create_input_bind_group(device, layout, view, sampler)))
+        .collect()
+}
+
+// the input of the latest source drawn into this frame
+fn latest_effect_input<'a>(
+    sources: &[EffectOutput],
+    inputs: &'a [Option<wgpu::BindGroup>],
+    options: &PostProcessOptions,
+) -> Option<&'a wgpu::BindGroup> {
+    let index = sources.iter().rposition(|source| source.is_drawn(options))?;
+    inputs.get(index)?.as_ref()
+}
+
+// A full screen effect over the tonemapped frame, with the `PostProcessing` settings. It is
+// skipped while its effects are off, the passes after it read its source instead
+pub struct EffectPass {
+    name: &'static str,
+    pub sources: Vec<EffectOutput>,
+    pub destination: EffectOutput,
+    input_layout: wgpu::BindGroupLayout,
+    inputs: Vec<Option<wgpu::BindGroup>>,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl EffectPass {
+    // chromatic aberration, the vignette and color grading
+    pub fn post_process(
+        device: &wgpu::Device,
+        sources: Vec<EffectOutput>,
+        destination: AttachmentId,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("Post Process Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(
+                include_str!("../shaders/fullscreen.wgsl"),
+                include_str!("../shaders/post_process_uniform.wgsl"),
+                include_str!("../shaders/post_process.wgsl"),
+            ).into()),
+        };
+        let destination = EffectOutput {
+            attachment: destination,
+            drawn: |options| {
+                options.chromatic_aberration.is_some() || options.vignette.is_some() || options.color_grading.is_some()
+            },
+        };
+
+        Self::new(device, "post process", shader, sources, destination, post_processing)
+    }
+
+    pub fn fxaa(
+        device: &wgpu::Device,
+        sources: Vec<EffectOutput>,
+        destination: AttachmentId,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("FXAA Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(
+                include_str!("../shaders/fullscreen.wgsl"),
+                include_str!("../shaders/post_process_uniform.wgsl"),
+                include_str!("../shaders/fxaa.wgsl"),
+            ).into()),
+        };
+        let destination = EffectOutput {
+            attachment: destination,
+            drawn: |options| options.fxaa.is_some(),
+        };
+
+        Self::new(device, "fxaa", shader, sources, destination, post_processing)
+    }
+
+    fn new(
+        device: &wgpu::Device,
+        name: &'static str,
+        shader: wgpu::ShaderModuleDescriptor,
+        sources: Vec<EffectOutput>,
+        destination: EffectOutput,
+        post_processing: &PostProcessing,
+    ) -> Self {
+        let input_layout = create_input_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Effect Pipeline Layout"),
+            bind_group_layouts: &[&input_layout, &post_processing.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_fullscreen_pipeline(device, &layout, LDR_FORMAT, shader, "fs_main", None, 1);
+
+        Self {
+            name,
+            sources,
+            destination,
+            input_layout,
+            inputs: Vec::new(),
+            sampler: create_input_sampler(device),
+            pipeline,
+        }
+    }
+}
+
+impl RenderNode<Renderer> for EffectPass {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        for source in &self.sources {
+            builder.read(source.attachment);
+        }
+        builder.color(self.destination.attachment, AttachmentLoad::Clear);
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, resources: &GraphResources) {
+        self.inputs = create_effect_inputs(device, &self.input_layout, &self.sampler, &self.sources, resources);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        let options = &renderer.post_processing.options;
+        if !self.destination.is_drawn(options) {
+            return;
+        }
+
+        let Some(input) = latest_effect_input(&self.sources, &self.inputs, options) else {
+            return;
+        };
+
+        let mut render_pass = context.begin_render_pass(self.name);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, &renderer.post_processing.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// copies the latest of `sources` to `target`, encoding it to sRGB when the target format doesn't
+pub struct BlitPass {
+    pub sources: Vec<EffectOutput>,
+    pub target: AttachmentId,
+    input_layout: wgpu::BindGroupLayout,
+    inputs: Vec<Option<wgpu::BindGroup>>,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl BlitPass {
+    pub fn new(
+        device: &wgpu::Device,
+        sources: Vec<EffectOutput>,
+        target: AttachmentId,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let input_layout = create_input_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&input_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(
+                include_str!("../shaders/fullscreen.wgsl"),
+                include_str!("../shaders/blit.wgsl"),
+            ).into()),
+        };
+        let entry_point = if target_format.is_srgb() { "fs_main" } else { "fs_encode_srgb" };
+        let pipeline = create_fullscreen_pipeline(device, &layout, target_format, shader, entry_point, None, sample_count);
+
+        Self {
+            sources,
+            target,
+            input_layout,
+            inputs: Vec::new(),
+            sampler: create_input_sampler(device),
+            pipeline,
+        }
+    }
+}
+
+impl RenderNode<Renderer> for BlitPass {
+    fn name(&self) -> &str {
+        "blit"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        for source in &self.sources {
+            builder.read(source.attachment);
+        }
+        builder.color(self.target, AttachmentLoad::Clear);
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, resources: &GraphResources) {
+        self.inputs = create_effect_inputs(device, &self.input_layout, &self.sampler, &self.sources, resources);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        let Some(input) = latest_effect_input(&self.sources, &self.inputs, &renderer.post_processing.options) else {
+            return;
+        };
+
+        let mut render_pass = context.begin_render_pass("Blit Pass");
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// the quad, sprites and text, with the ortho camera
+pub struct OverlayPass {
+    pub color: AttachmentId,
+    pub depth: AttachmentId,
+}
+
+impl RenderNode<Renderer> for OverlayPass {
+    fn name(&self) -> &str {
+        "overlay"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder
+            .color(self.color, AttachmentLoad::Load)
+            .depth(self.depth, AttachmentLoad::Load);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        if !renderer.show_overlay {
+            return;
+        }
+
+        let mut render_pass = context.begin_render_pass("Overlay Pass");
+
+        if renderer.show_quad {
+            use crate::primitives::quad::DrawQuad;
+            render_pass.set_pipeline(&renderer.render_pipeline_2d);
+            render_pass.draw_quad(
+                &renderer.quad_model,
+                renderer.ortho_bind_group(),
+            );
+        }
+
+        use crate::sprite::DrawSprites;
+        render_pass.draw_sprites(&renderer.sprites, renderer.ortho_bind_group());
+
+        use crate::text::DrawText;
+        render_pass.draw_text(&renderer.text, renderer.ortho_bind_group());
+    }
+}
+
+// the shadow map of `shadow_debug_layer` in the bottom left corner
+pub struct ShadowDebugPass {
+    pub color: AttachmentId,
+    pub depth: AttachmentId,
+    pub shadow_maps: AttachmentId,
+}
+
+impl RenderNode<Renderer> for ShadowDebugPass {
+    fn name(&self) -> &str {
+        "shadow debug"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder
+            .read(self.shadow_maps)
+            .color(self.color, AttachmentLoad::Load)
+            .depth(self.depth, AttachmentLoad::Load);
+    }
+
+    fn run(&self, renderer: &Renderer, context: &mut NodeContext) {
+        if !renderer.show_shadow_debug {
+            return;
+        }
+
+        let (width, height) = renderer.size;
+        let debug_size = (width.min(height) / 3) as f32;
+
+        let mut render_pass = context.begin_render_pass("Shadow Debug Pass");
+        render_pass.set_viewport(0.0, height as f32 - debug_size, debug_size, debug_size, 0.0, 1.0);
+        render_pass.set_pipeline(&renderer.shadow_debug_pipeline);
+        render_pass.set_bind_group(0, &renderer.shadow_debug_bind_group, &[]);
+        render_pass.draw(0..3, renderer.shadow_debug_layer..renderer.shadow_debug_layer + 1);
+    }
+}
@@ -0,0 +1,893 @@
+use cgmath::prelude::*;
+
+use crate::render::{
+    create_render_pipeline,
+    create_shadow_pipeline,
+    CullingStats,
+    Environment,
+    EnvironmentOptions,
+    IdBuffer,
+    IdReadback,
+    PostProcessOptions,
+    PostProcessing,
+    RenderGraph,
+    TonemapOptions,
+    Tonemapping,
+    Visibility,
+    HDR_FORMAT,
+};
+use crate::texture::mipmap::MipmapGenerator;
+use crate::texture::{CubeLut, MipmapGeneration, Texture, TextureFiltering, TextureOptions};
+use crate::camera::{
+    Camera,
+    Frustum,
+    CameraUniform,
+    CameraBuffer,
+    Projection,
+    Ray,
+};
+use crate::camera::{
+    OrthoCamera,
+    OrthoCameraUniform,
+    OrthoCameraBuffer,
+    OrthoProjection,
+};
+
+use crate::primitives::{
+    Vertex,
+    quad::{QuadVertex, Quad},
+};
+use crate::instance::{Instance, InstanceRaw, InstanceManager};
+use crate::model::{Aabb, ModelVertex, Model, ModelHierarchy, Material, RayHit};
+use crate::light::{LightManager, ShadowMap};
+use crate::sprite::SpriteBatch;
+use crate::text::{Font, Text, TextBatch, TextOptions};
+use crate::resources;
+use crate::scene::{CameraDescription, InstanceDescription, LightDescription, QuadDescription, Scene, SkyboxDescription};
+
+use super::passes;
+
+const SPACE_BETWEEN: f32 = 3.0;
+
+pub struct Camera2D {
+    camera: OrthoCamera,
+    uniform: OrthoCameraUniform,
+    buffer: OrthoCameraBuffer,
+    projection: OrthoProjection,
+}
+
+pub fn create_instances(amount: u32) -> Vec<Instance> {
+    create_instance_grid(amount, SPACE_BETWEEN)
+}
+
+// `amount` x `amount` instances on the xz plane, `spacing` apart and tilted away from the center
+pub fn create_instance_grid(amount: u32, spacing: f32) -> Vec<Instance> {
+    (0..amount).flat_map(|z| {
+        (0..amount).map(move |x| {
+            let x = spacing * (x as f32 - amount as f32 / 2.0);
+            let z = spacing * (z as f32 - amount as f32 / 2.0);
+
+            let position = cgmath::Vector3 {
+                x: x as f32,
+                y: 0.0,
+                z: z as f32,
+            };
+
+            let rotation = if position.is_zero() {
+                // this is needed so an object at (0, 0, 0) won't get scaled to zero
+                // as Quaternions can effect scale if they're not created correctly
+                cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
+            } else {
+                cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+            };
+
+            Instance::new(position, rotation)
+        })
+    }).collect()
+}
+
+// picks the loader from the file extension
+async fn load_model_file(
+    file_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<Model> {
+    let is_gltf = file_name.ends_with(".gltf") || file_name.ends_with(".glb");
+
+    if is_gltf {
+        resources::load_model_gltf(file_name, device, queue, mipmaps, layout).await
+    } else {
+        resources::load_model(file_name, device, queue, mipmaps, layout).await
+    }
+}
+
+async fn load_skybox_file(
+    skybox: &SkyboxDescription,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<Texture> {
+    match (&skybox.equirectangular, &skybox.faces) {
+        (Some(file_name), None) => resources::load_cubemap_equirectangular(file_name, skybox.face_size, device, queue).await,
+        (None, Some(file_names)) => resources::load_cubemap_faces(file_names, device, queue).await,
+        _ => anyhow::bail!("A skybox needs either an equirectangular panorama or six faces"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RendererOptions {
+    // MSAA samples for the scene and the overlay, one of `SAMPLE_COUNTS` the adapter supports
+    // for every format in `Renderer::multisampled_formats`
+    pub sample_count: u32,
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        Self {
+            sample_count: 1,
+        }
+    }
+}
+
+impl RendererOptions {
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+fn clear_color(scene: &Scene) -> wgpu::Color {
+    let [r, g, b, a] = scene.clear_color;
+
+    wgpu::Color { r, g, b, a }
+}
+
+// the bind group layouts of the pipelines in `MultisampledPipelines`
+struct PipelineLayouts<'a> {
+    texture: &'a wgpu::BindGroupLayout,
+    camera: &'a wgpu::BindGroupLayout,
+    lights: &'a wgpu::BindGroupLayout,
+    environment: &'a wgpu::BindGroupLayout,
+    shadow_debug: &'a wgpu::BindGroupLayout,
+    ortho_camera: &'a wgpu::BindGroupLayout,
+    quad: &'a wgpu::BindGroupLayout,
+}
+
+// the renderer's own pipelines drawing into the graph's multisampled attachments, built again
+// by `Renderer::set_sample_count`
+struct MultisampledPipelines {
+    render: wgpu::RenderPipeline,
+    light: wgpu::RenderPipeline,
+    shadow_debug: wgpu::RenderPipeline,
+    overlay: wgpu::RenderPipeline,
+}
+
+impl MultisampledPipelines {
+    fn new(
+        device: &wgpu::Device,
+        layouts: &PipelineLayouts,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let render_pipline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    layouts.texture,
+                    layouts.camera,
+                    layouts.lights,
+                    layouts.environment,
+                ],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let render_pipeline = {
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Normal Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/shader.wgsl").into()
+                ),
+            };
+
+            create_render_pipeline(
+                device,
+                &render_pipline_layout,
+                HDR_FORMAT,
+                Some(Texture::DEPTH_FORMAT),
+                &[ModelVertex::layout(), InstanceRaw::layout()],
+                shader,
+                None,
+                sample_count,
+            )
+        };
+
+        let light_render_pipeline = {
+            let light_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Light Pipeline Layout"),
+                bind_group_layouts: &[layouts.camera, layouts.lights],
+                push_constant_ranges: &[],
+            });
+
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Light Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/light.wgsl").into()
+                ),
+            };
+
+            create_render_pipeline(
+                device,
+                &light_pipeline_layout,
+                HDR_FORMAT,
+                Some(Texture::DEPTH_FORMAT),
+                &[ModelVertex::layout()],
+                shader,
+                None,
+                sample_count,
+            )
+        };
+
+        let shadow_debug_pipeline = {
+            let shadow_debug_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Debug Pipeline Layout"),
+                bind_group_layouts: &[layouts.shadow_debug],
+                push_constant_ranges: &[],
+            });
+
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Debug Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/shadow_debug.wgsl").into()
+                ),
+            };
+
+            create_render_pipeline(
+                device,
+                &shadow_debug_pipeline_layout,
+                target_format,
+                Some(Texture::DEPTH_FORMAT),
+                &[],
+                shader,
+                None,
+                sample_count,
+            )
+        };
+
+        let render_pipeline_2d = {
+            let render_pipeline_2d_layout = device.create_pipeline_layout(
+                &wgpu::PipelineLayoutDescriptor {
+                    label: Some("2d Render Pipeline Lauout"),
+                    bind_group_layouts: &[
+                        // need camera_2d_buffer.bind_group_layout,
+                        layouts.ortho_camera,
+                        // need triangle.bing_group_layout (or rectangle? whatever we need here)
+                        layouts.quad,
+                    ],
+                    push_constant_ranges: &[],
+                }
+            );
+
+            let shader_2d = wgpu::ShaderModuleDescriptor {
+                label: Some("2D Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/quad.wgsl").into()
+                ),
+            };
+
+            create_render_pipeline(
+                device,
+                &render_pipeline_2d_layout,
+                target_format,
+                Some(Texture::DEPTH_FORMAT),
+                &[QuadVertex::layout()],
+                shader_2d,
+                Some(wgpu::BlendState::ALPHA_BLENDING),
+                sample_count,
+            )
+        };
+
+        Self {
+            render: render_pipeline,
+            light: light_render_pipeline,
+            shadow_debug: shadow_debug_pipeline,
+            overlay: render_pipeline_2d,
+        }
+    }
+}
+
+// Everything needed to draw the scene that doesn't depend on where the frame ends up.
+// `App` renders this into the window surface, `HeadlessApp` into an offscreen texture
+pub struct Renderer {
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub render_pipeline_2d: wgpu::RenderPipeline,
+    pub light_render_pipeline: wgpu::RenderPipeline,
+    pub shadow_pipeline: wgpu::RenderPipeline,
+    pub shadow_debug_pipeline: wgpu::RenderPipeline,
+    pub shadow_debug_bind_group: wgpu::BindGroup,
+    shadow_debug_bind_group_layout: wgpu::BindGroupLayout,
+    pub texture_bind_group_layout: wgpu::BindGroupLayout,
+    // builds the mip chains of the textures models and sprites load
+    pub mipmaps: MipmapGenerator,
+
+    pub camera: Camera,
+    pub camera_uniform: CameraUniform,
+    pub camera_buffer: CameraBuffer,
+    pub projection: Projection,
+
+    pub ortho_camera: Camera2D,
+
+    // copies of `obj_model`, changes are uploaded on the next update
+    pub instances: InstanceManager,
+    pub obj_model: Model,
+    // optional glTF node tree drawn with its own per-node transforms
+    pub model_hierarchy: Option<ModelHierarchy>,
+    // skip the meshes outside the camera's view, `visibility` is recomputed every update
+    pub frustum_culling: bool,
+    pub visibility: Visibility,
+    // gpu picking, only rendered once `enable_id_buffer` is called
+    pub id_buffer: Option<IdBuffer>,
+
+    pub lights: LightManager,
+    pub light_model: Model,
+
+    pub quad_model: Quad,
+    pub show_quad: bool,
+    // drawn over the scene with the ortho camera, atlas 0 is the mario sheet
+    pub sprites: SpriteBatch,
+    // drawn over the sprites
+    pub text: TextBatch,
+    // the indices in `text` the scene's text was put at, the rest belongs to whoever pushed it
+    scene_texts: Vec<usize>,
+
+    // linear and in the hdr range, it is tonemapped along with the scene. Only shows where
+    // nothing is drawn and there is no skybox
+    pub clear_color: wgpu::Color,
+    // the cubemap drawn behind the scene and reflected by the models
+    pub environment: Environment,
+    pub tonemapping: Tonemapping,
+    // bloom, FXAA and the other screen space effects, all off until enabled in its options
+    pub post_processing: PostProcessing,
+    // toggle the 3d scene (light gizmo + models) and the 2d overlay independently
+    pub show_scene: bool,
+    pub show_overlay: bool,
+    // draws the shadow map of `shadow_debug_layer` in the bottom left corner
+    pub show_shadow_debug: bool,
+    pub shadow_debug_layer: u32,
+    // size of the target being rendered to, used to place the debug view
+    pub size: (u32, u32),
+    // the scene last loaded, `current_scene` adds the changes made since
+    pub scene: Scene,
+    // the passes `render` runs, see `passes::create_graph`
+    pub graph: RenderGraph<Renderer>,
+    // every pipeline drawing into the graph's color and depth attachments is built with it
+    sample_count: u32,
+}
+
+impl Renderer {
+    pub async fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        options: RendererOptions,
+    ) -> anyhow::Result<Self> {
+        let sample_count = options.sample_count;
+        let texture_bind_group_layout = Material::create_bind_group_layout(device);
+        let mipmaps = MipmapGenerator::new(device);
+
+        // nothing is loaded until `load_scene`, so the apps only parse their first model once
+        let scene = Scene::default();
+        let camera = scene.camera.to_camera();
+        let projection = scene.camera.to_projection(config.width, config.height);
+
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_projection(&camera, &projection);
+        let camera_buffer = CameraBuffer::new(device, &camera, &mut camera_uniform, &projection);
+
+        let ortho_cam = OrthoCamera::new((0.0, 0.0, 0.0), [config.width as f32, config.height as f32]);
+        let mut ortho_uniform = OrthoCameraUniform::new();
+        // z = 0 lands on the near plane, so the overlay is drawn in front of the scene
+        let ortho_projection = OrthoProjection::new(config.width, config.height, 0.0, 1.0);
+        let ortho_buffer =  OrthoCameraBuffer::new(device, &ortho_cam, &mut ortho_uniform, &ortho_projection);
+        let ortho_camera = Camera2D {
+            camera: ortho_cam,
+            uniform: ortho_uniform,
+            buffer: ortho_buffer,
+            projection: ortho_projection,
+        };
+
+        let instances = InstanceManager::new(device, Vec::new());
+        let lights = LightManager::new(device);
+
+        let environment = Environment::new(device, queue, EnvironmentOptions::default());
+
+        let shadow_pipeline = {
+            let shadow_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[&lights.shadows.layer_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/shadow.wgsl").into()
+                ),
+            };
+
+            create_shadow_pipeline(
+                device,
+                &shadow_pipeline_layout,
+                Texture::DEPTH_FORMAT,
+                &[ModelVertex::layout(), InstanceRaw::layout()],
+                shader,
+            )
+        };
+
+        let shadow_debug_bind_group_layout = ShadowMap::create_debug_bind_group_layout(device);
+        let shadow_debug_bind_group = lights.shadows.create_debug_bind_group(device, &shadow_debug_bind_group_layout);
+        let quad_model = Quad::new(device, scene.overlay.quad.map(|quad| quad.to_options()).unwrap_or_default());
+        let pipelines = MultisampledPipelines::new(
+            device,
+            &PipelineLayouts {
+                texture: &texture_bind_group_layout,
+                camera: &camera_buffer.bind_group_layout,
+                lights: &lights.bind_group_layout,
+                environment: &environment.bind_group_layout,
+                shadow_debug: &shadow_debug_bind_group_layout,
+                ortho_camera: &ortho_camera.buffer.bind_group_layout,
+                quad: &quad_model.uniform_buffer.bind_group_layout,
+            },
+            config.format,
+            sample_count,
+        );
+
+        let mut sprites = SpriteBatch::new(device, config.format, &ortho_camera.buffer.bind_group_layout, sample_count);
+        sprites.load_atlas(
+            device,
+            queue,
+            &mipmaps,
+            include_bytes!("../assets/mario-sprite.png"),
+            "mario-sprite.png",
+            &TextureOptions::default()
+                .with_filtering(TextureFiltering::Nearest)
+                .with_mipmaps(MipmapGeneration::None),
+        )?;
+
+        let font = Font::load("fonts/DejaVuSans.ttf").await?;
+        let text = TextBatch::new(
+            device,
+            config.format,
+            &ortho_camera.buffer.bind_group_layout,
+            font,
+            TextOptions::default(),
+            sample_count,
+        );
+
+        let obj_model = Model { meshes: Vec::new(), materials: Vec::new() };
+
+        let light_model = resources::load_model(
+            "meshes/light/light-object.obj",
+            device,
+            queue,
+            &mipmaps,
+            &texture_bind_group_layout,
+        )
+        .await?;
+
+        let tonemapping = Tonemapping::new(device, TonemapOptions::default());
+        let post_processing = PostProcessing::new(device, queue, PostProcessOptions::default());
+        let mut graph = passes::create_graph(
+            device,
+            (config.width, config.height),
+            config.format,
+            &environment,
+            &tonemapping,
+            &post_processing,
+            sample_count,
+        );
+        graph.compile(device)?;
+
+        Ok(Self {
+            render_pipeline: pipelines.render,
+            light_render_pipeline: pipelines.light,
+            render_pipeline_2d: pipelines.overlay,
+            shadow_pipeline,
+            shadow_debug_pipeline: pipelines.shadow_debug,
+            shadow_debug_bind_group,
+            shadow_debug_bind_group_layout,
+            texture_bind_group_layout,
+            mipmaps,
+
+            camera,
+            camera_uniform,
+            camera_buffer,
+            projection,
+
+            ortho_camera,
+
+            instances,
+
+            obj_model,
+            model_hierarchy: None,
+            frustum_culling: true,
+            visibility: Visibility::default(),
+            id_buffer: None,
+
+            lights,
+            light_model,
+
+            quad_model,
+            sprites,
+            text,
+            scene_texts: Vec::new(),
+
+            clear_color: clear_color(&scene),
+            environment,
+            tonemapping,
+            post_processing,
+            show_quad: scene.overlay.quad.is_some(),
+            show_scene: true,
+            show_overlay: true,
+            show_shadow_debug: false,
+            shadow_debug_layer: 0,
+            size: (config.width, config.height),
+            scene,
+            graph,
+            sample_count,
+        })
+    }
+
+    // the formats that are multisampled when rendering into a `target_format` target
+    pub fn multisampled_formats(target_format: wgpu::TextureFormat) -> [wgpu::TextureFormat; 3] {
+        [target_format, HDR_FORMAT, Texture::DEPTH_FORMAT]
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // Switches MSAA while running, to one of the `supported` counts (see
+    // `supported_sample_counts`). Every pipeline drawing into the multisampled attachments is
+    // built again, and so is `graph` with its depth and MSAA targets. Passes and attachments
+    // added to it since `new` are moved over to the new graph
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        supported: &[u32],
+        sample_count: u32,
+    ) -> anyhow::Result<()> {
+        if !supported.contains(&sample_count) {
+            anyhow::bail!("{}x MSAA isn't supported, only {:?}", sample_count, supported);
+        }
+
+        if sample_count == self.sample_count {
+            return Ok(());
+        }
+
+        let target_format = self.graph.target_format();
+        let mut graph = passes::create_graph(
+            device,
+            self.graph.target_size(),
+            target_format,
+            &self.environment,
+            &self.tonemapping,
+            &self.post_processing,
+            sample_count,
+        );
+        graph.take_added(&mut self.graph)?;
+        if let Err(error) = graph.compile(device) {
+            // an added pass may not cope with the new sample count, keep drawing with the old one
+            self.graph.take_added(&mut graph)?;
+            self.graph.compile(device)?;
+            return Err(error);
+        }
+
+        let pipelines = MultisampledPipelines::new(
+            device,
+            &PipelineLayouts {
+                texture: &self.texture_bind_group_layout,
+                camera: &self.camera_buffer.bind_group_layout,
+                lights: &self.lights.bind_group_layout,
+                environment: &self.environment.bind_group_layout,
+                shadow_debug: &self.shadow_debug_bind_group_layout,
+                ortho_camera: &self.ortho_camera.buffer.bind_group_layout,
+                quad: &self.quad_model.uniform_buffer.bind_group_layout,
+            },
+            target_format,
+            sample_count,
+        );
+        self.render_pipeline = pipelines.render;
+        self.light_render_pipeline = pipelines.light;
+        self.shadow_debug_pipeline = pipelines.shadow_debug;
+        self.render_pipeline_2d = pipelines.overlay;
+        self.sprites.set_sample_count(device, sample_count);
+        self.text.set_sample_count(device, sample_count);
+        self.graph = graph;
+        self.sample_count = sample_count;
+
+        Ok(())
+    }
+
+    // replace the model being drawn, picking the loader from the file extension
+    pub async fn load_model(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        file_name: &str,
+    ) -> anyhow::Result<()> {
+        self.obj_model = load_model_file(file_name, device, queue, &self.mipmaps, &self.texture_bind_group_layout).await?;
+
+        Ok(())
+    }
+
+    // the .cube LUT color grading uses, `post_processing.options.color_grading` turns it on
+    pub async fn load_lut(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        file_name: &str,
+    ) -> anyhow::Result<()> {
+        let lut = CubeLut::load(file_name).await?;
+        self.post_processing.set_lut(device, queue, &lut);
+
+        Ok(())
+    }
+
+    // the cubemap behind the scene and in its reflections, from a panorama or six faces
+    pub async fn load_skybox(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        skybox: &SkyboxDescription,
+    ) -> anyhow::Result<()> {
+        let cubemap = load_skybox_file(skybox, device, queue).await?;
+        self.set_skybox(device, cubemap, skybox);
+
+        Ok(())
+    }
+
+    fn set_skybox(&mut self, device: &wgpu::Device, cubemap: Texture, skybox: &SkyboxDescription) {
+        self.environment.set_cubemap(device, cubemap);
+        self.environment.options.intensity = skybox.intensity;
+        self.environment.options.reflection_intensity = skybox.reflection_intensity;
+    }
+
+    pub async fn load_model_hierarchy(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        file_name: &str,
+    ) -> anyhow::Result<()> {
+        let hierarchy = resources::load_model_hierarchy_gltf(
+            file_name,
+            device,
+            queue,
+            &self.mipmaps,
+            &self.texture_bind_group_layout,
+        ).await?;
+
+        self.model_hierarchy = Some(hierarchy);
+
+        Ok(())
+    }
+
+    // Replaces the models, lights, camera and overlay with the ones in `scene`. Nothing changes
+    // when a model fails to load
+    pub async fn load_scene(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+    ) -> anyhow::Result<()> {
+        scene.validate()?;
+
+        let model = match &scene.model {
+            Some(model) => Some(load_model_file(&model.path, device, queue, &self.mipmaps, &self.texture_bind_group_layout).await?),
+            None => None,
+        };
+        let hierarchy = match &scene.hierarchy {
+            Some(path) => Some(resources::load_model_hierarchy_gltf(
+                path,
+                device,
+                queue,
+                &self.mipmaps,
+                &self.texture_bind_group_layout,
+            ).await?),
+            None => None,
+        };
+        let cubemap = match &scene.skybox {
+            Some(skybox) => Some(load_skybox_file(skybox, device, queue).await?),
+            None => None,
+        };
+
+        // a scene without an instanced model keeps the old one around but draws no copies of it
+        if let Some(model) = model {
+            self.obj_model = model;
+        }
+        self.instances.set_all(scene.model.as_ref().map(|model| model.to_instances()).unwrap_or_default());
+        self.model_hierarchy = hierarchy;
+
+        // `validate` checked there is room for all of them
+        self.lights.lights.clear();
+        for light in &scene.lights {
+            self.lights.add(light.to_light())?;
+        }
+
+        self.camera = scene.camera.to_camera();
+        self.projection = scene.camera.to_projection(self.size.0, self.size.1);
+        self.clear_color = clear_color(scene);
+        match (cubemap, &scene.skybox) {
+            (Some(cubemap), Some(skybox)) => self.set_skybox(device, cubemap, skybox),
+            _ => self.environment.clear(device, queue),
+        }
+
+        if let Some(quad) = &scene.overlay.quad {
+            self.quad_model = Quad::new(device, quad.to_options());
+        }
+        self.show_quad = scene.overlay.quad.is_some();
+        self.sprites.clear();
+        self.sprites.extend(scene.overlay.sprites.iter().copied());
+        // reuses the slots of the last scene's text, the indices of text pushed by others (like
+        // the fps counter) stay where they are
+        for (slot, text) in scene.overlay.text.iter().enumerate() {
+            match self.scene_texts.get(slot) {
+                Some(index) => self.text.set(*index, text.clone()),
+                None => self.scene_texts.push(self.text.push(text.clone())),
+            }
+        }
+        for index in &self.scene_texts[scene.overlay.text.len()..] {
+            self.text.set(*index, Text::default());
+        }
+
+        self.scene = scene.clone();
+
+        Ok(())
+    }
+
+    // The last loaded scene with the camera, lights, instances and overlay as they are now, for
+    // saving back out
+    pub fn current_scene(&self) -> Scene {
+        let mut scene = self.scene.clone();
+
+        if let Some(model) = &mut scene.model {
+            model.instances = self.instances.iter().map(InstanceDescription::from_instance).collect();
+            model.grid = None;
+        }
+        scene.lights = self.lights.lights.iter().map(LightDescription::from_light).collect();
+        scene.camera = CameraDescription::from_camera(&self.camera, &self.projection);
+        let wgpu::Color { r, g, b, a } = self.clear_color;
+        scene.clear_color = [r, g, b, a];
+        if let Some(skybox) = &mut scene.skybox {
+            skybox.intensity = self.environment.options.intensity;
+            skybox.reflection_intensity = self.environment.options.reflection_intensity;
+        }
+        scene.overlay.quad = self.show_quad.then(|| QuadDescription::from_options(&self.quad_model.options));
+        scene.overlay.sprites = self.sprites.sprites().to_vec();
+        scene.overlay.text = self.scene_texts.iter()
+            .take(self.scene.overlay.text.len())
+            .filter_map(|index| self.text.get(*index).cloned())
+            .collect();
+
+        scene
+    }
+
+    // world space bounds of everything the scene draws, the model under each of its instances
+    // plus the hierarchy
+    pub fn scene_bounds(&self) -> Aabb {
+        let model_bounds = self.obj_model.bounds();
+        let bounds = self.instances.transforms().iter()
+            .map(|transform| model_bounds.transform(transform))
+            .fold(Aabb::empty(), |bounds, instance_bounds| bounds.union(&instance_bounds));
+
+        match &self.model_hierarchy {
+            Some(hierarchy) => bounds.union(&hierarchy.bounds()),
+            None => bounds,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.projection.resize(config.width, config.height);
+        self.ortho_camera.projection.resize(config.width, config.height);
+        self.ortho_camera.camera.resize(config.width, config.height);
+        // the graph's attachments (depth included) have to match the size of the surface
+        if let Err(error) = self.graph.resize(device, config.width, config.height) {
+            log::error!("couldn't resize the render graph: {:?}", error);
+        }
+        self.size = (config.width, config.height);
+
+        if let Some(id_buffer) = &mut self.id_buffer {
+            id_buffer.resize(device, config);
+        }
+    }
+
+    // Logical pixels per physical pixel of the target. The 2d overlay is laid out in logical
+    // pixels so it keeps its size on HiDPI screens
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.ortho_camera.projection.set_scale_factor(scale_factor);
+    }
+
+    pub fn ortho_projection(&self) -> &OrthoProjection {
+        &self.ortho_camera.projection
+    }
+
+    // for building more 2d batches, sprites or text with other options
+    pub fn ortho_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.ortho_camera.buffer.bind_group_layout
+    }
+
+    pub fn ortho_bind_group(&self) -> &wgpu::BindGroup {
+        &self.ortho_camera.buffer.bind_group
+    }
+
+    // The ray through a pixel of the target, `position` is in physical pixels from the top left
+    pub fn screen_ray(&self, position: (f32, f32)) -> Ray {
+        Ray::from_screen(&self.camera, &self.projection, position, self.size)
+    }
+
+    // The closest instance of the model under a pixel, tested against its triangles on the cpu
+    pub fn pick(&self, position: (f32, f32)) -> Option<RayHit> {
+        self.obj_model.raycast(&self.screen_ray(position), &self.instances.transforms())
+    }
+
+    // starts rendering the id buffer with every frame so `read_id` can be used
+    pub fn enable_id_buffer(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        if self.id_buffer.is_none() {
+            self.id_buffer = Some(IdBuffer::new(device, config, &self.camera_buffer.bind_group_layout));
+        }
+    }
+
+    // Reads back the id buffer as of the last rendered frame, None without an id buffer or for a
+    // pixel outside the target
+    pub fn read_id(&self, device: &wgpu::Device, queue: &wgpu::Queue, x: u32, y: u32) -> Option<IdReadback> {
+        self.id_buffer.as_ref()?.read(device, queue, x, y)
+    }
+
+    // write all the uniforms and instances that may have changed since the last frame
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.camera_uniform.update_view_projection(&self.camera, &self.projection);
+        queue.write_buffer(&self.camera_buffer.buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+
+        self.ortho_camera.uniform.update_view_projection(&self.ortho_camera.camera, &self.ortho_camera.projection);
+        queue.write_buffer(&self.ortho_camera.buffer.buffer, 0, bytemuck::cast_slice(&[self.ortho_camera.uniform]));
+
+        self.quad_model.uniform.update_model_from_position(self.quad_model.options.position);
+        queue.write_buffer(&self.quad_model.uniform_buffer.buffer, 0, bytemuck::cast_slice(&[self.quad_model.uniform]));
+
+        self.lights.update(queue);
+        self.instances.upload(device, queue);
+        self.sprites.prepare(device, queue);
+        self.text.prepare(device, queue);
+        self.tonemapping.update(queue, self.size);
+        self.post_processing.update(queue);
+        self.environment.update(queue, &self.camera, &self.projection);
+
+        self.update_visibility();
+    }
+
+    // Works out which meshes of which instances/nodes can be seen by the camera. The shadow
+    // passes don't use it, things outside the view can still cast shadows into it
+    pub fn update_visibility(&mut self) {
+        let hierarchy = self.model_hierarchy.as_ref();
+
+        self.visibility = if self.frustum_culling {
+            let frustum = Frustum::from_uniform(&self.camera_uniform);
+
+            Visibility::compute(&frustum, &self.obj_model, &self.instances.transforms(), hierarchy)
+        } else {
+            Visibility::all(&self.obj_model, self.instances.len() as u32, hierarchy)
+        };
+    }
+
+    // how many mesh draws were skipped by the last update
+    pub fn culling_stats(&self) -> CullingStats {
+        self.visibility.stats
+    }
+
+    // record the scene into `view` by running every node of `graph`. The caller owns the encoder
+    // so it can decide what happens to the frame afterwards (present it, copy it back to the cpu, etc)
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.graph.execute(self, encoder, view);
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::*;
+
+use crate::texture::TextureCapture;
+
+// A frame that has been copied out of the surface and is waiting for the gpu to hand it back.
+// Checked every frame by `App::update` and saved once it is ready
+pub struct PendingScreenshot {
+    pub capture: TextureCapture,
+    pub file_name: String,
+}
+
+impl PendingScreenshot {
+    // Some once the capture finished, with the result of saving it
+    pub fn try_save(&mut self) -> Option<Result<()>> {
+        let image = self.capture.try_finish()?;
+
+        Some(image.and_then(|image| save_screenshot(&image, &self.file_name)))
+    }
+}
+
+// screenshot-<milliseconds since the unix epoch>.png
+pub fn screenshot_file_name() -> String {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            let millis = js_sys::Date::now() as u128;
+        } else {
+            let millis = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_millis())
+                .unwrap_or_default();
+        }
+    }
+
+    format!("screenshot-{}.png", millis)
+}
+
+// Writes the png to `file_name` on native, relative to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_screenshot(image: &image::RgbaImage, file_name: &str) -> Result<()> {
+    image.save(file_name)?;
+    log::info!("Saved screenshot to {}", file_name);
+
+    Ok(())
+}
+
+// The browser has no file system to write to, so the png is handed to it as a download
+// through a temporary link to a blob
+#[cfg(target_arch = "wasm32")]
+pub fn save_screenshot(image: &image::RgbaImage, file_name: &str) -> Result<()> {
+    use wasm_bindgen::JsCast;
+
+    let mut bytes = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+
+    let js_error = |error: wasm_bindgen::JsValue| anyhow!("Unable to download screenshot: {:?}", error);
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes.as_slice()));
+    let mut blob_options = web_sys::BlobPropertyBag::new();
+    blob_options.type_("image/png");
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &blob_options)
+        .map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| anyhow!("Unable to download screenshot: no document"))?;
+    let link = document.create_element("a")
+        .map_err(js_error)?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|element| js_error(element.into()))?;
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+
+    web_sys::Url::revoke_object_url(&url).map_err(js_error)?;
+
+    Ok(())
+}
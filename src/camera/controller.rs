@@ -8,6 +8,16 @@ use crate::camera::Camera;
 use std::f32::consts::FRAC_PI_2;
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
+// which controller drives the camera, `App` switches between them at runtime
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    // free flying with CameraController
+    #[default]
+    Fly,
+    // orbiting a target with OrbitController
+    Orbit,
+}
+
 pub struct CameraController {
     pub speed: f32,
     pub scroll: f32,
@@ -0,0 +1,89 @@
+use cgmath::*;
+
+use crate::camera::CameraUniform;
+use crate::model::{Aabb, BoundingSphere};
+
+// a plane where dot(normal, point) + distance is positive on the inside
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.truncate();
+        let length = normal.magnitude();
+
+        Self {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    pub fn signed_distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.distance
+    }
+}
+
+// The six planes of a camera's view volume in world space, all facing inward. Used to skip
+// drawing anything that can't end up on screen
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // Extracts the planes from a view projection matrix (Gribb/Hartmann). The matrix has to map
+    // depth to wgpu's 0..1 range, which is what the camera uniform holds
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let m = view_projection.transpose();
+        let (row0, row1, row2, row3) = (m.x, m.y, m.z, m.w);
+
+        Self {
+            planes: [
+                row3 + row0,
+                row3 - row0,
+                row3 + row1,
+                row3 - row1,
+                // z >= 0 instead of z >= -w, the near plane is the third row on its own
+                row2,
+                row3 - row2,
+            ].map(Plane::from_coefficients),
+        }
+    }
+
+    pub fn from_uniform(uniform: &CameraUniform) -> Self {
+        Self::from_matrix(uniform.view_projection.into())
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+    }
+
+    // Conservative, a box near a corner of the frustum can pass without being visible. That
+    // only costs a draw, it never culls something that is on screen
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane's normal
+            let corner = Point3::new(
+                if plane.normal.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.normal.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.normal.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+
+            plane.signed_distance(corner) >= 0.0
+        })
+    }
+
+    // the sphere test is cheap and rejects most things, the box is only transformed and
+    // tested for what the sphere lets through
+    pub fn intersects_bounds(&self, aabb: &Aabb, sphere: &BoundingSphere, transform: &Matrix4<f32>) -> bool {
+        self.intersects_sphere(&sphere.transform(transform)) && self.intersects_aabb(&aabb.transform(transform))
+    }
+}
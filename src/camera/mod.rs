@@ -1,9 +1,15 @@
 pub mod camera;
 pub mod controller;
+pub mod frustum;
+pub mod orbit;
 pub mod ortho_camera;
+pub mod ray;
 
 pub use camera::{Camera, CameraUniform, CameraBuffer, Projection};
-pub use controller::CameraController;
+pub use controller::{CameraController, CameraMode};
+pub use frustum::{Frustum, Plane};
+pub use orbit::OrbitController;
+pub use ray::Ray;
 
 pub use ortho_camera::{OrthoCamera, OrthoCameraUniform, OrthoCameraBuffer, OrthoProjection};
 
@@ -0,0 +1,186 @@
+use winit::event::*;
+use winit::dpi::PhysicalPosition;
+use cgmath::*;
+use instant::Duration;
+
+use crate::camera::{Camera, Projection};
+use crate::model::Aabb;
+
+use std::f32::consts::FRAC_PI_2;
+const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+
+// Orbits the camera around `target` for inspecting models. Drives the same `Camera` as
+// `CameraController`: the camera keeps its yaw/pitch and sits `distance` behind the target, so
+// switching between the two controllers doesn't make the view jump.
+//   - left drag or WASD/arrows rotate around the target
+//   - right or middle drag pans the target in screen space
+//   - the scroll wheel dollies toward or away from the target
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    // radians per second for the keys
+    pub speed: f32,
+    // radians per pixel dragged
+    pub sensitivity: f32,
+    // fraction of the distance moved per pixel dragged, so panning feels the same at any zoom
+    pub pan_sensitivity: f32,
+    // fraction of the distance moved per pixel scrolled
+    pub zoom_sensitivity: f32,
+    pub rotating: bool,
+    pub panning: bool,
+    pub amount_left: f32,
+    pub amount_right: f32,
+    pub amount_up: f32,
+    pub amount_down: f32,
+    pub rotate_horizontal: f32,
+    pub rotate_vertical: f32,
+    pub pan_horizontal: f32,
+    pub pan_vertical: f32,
+    pub scroll: f32,
+}
+
+impl OrbitController {
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        Self {
+            target: Point3::origin(),
+            distance: 5.0,
+            min_distance: 0.1,
+            max_distance: 100.0,
+            speed,
+            sensitivity,
+            pan_sensitivity: 0.002,
+            zoom_sensitivity: 0.001,
+            rotating: false,
+            panning: false,
+            amount_left: 0.0,
+            amount_right: 0.0,
+            amount_up: 0.0,
+            amount_down: 0.0,
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            pan_horizontal: 0.0,
+            pan_vertical: 0.0,
+            scroll: 0.0,
+        }
+    }
+
+    // Starts orbiting from wherever the camera is, around the point `distance` in front of it
+    pub fn attach(&mut self, camera: &Camera) {
+        self.target = camera.position + camera.forward() * self.distance;
+    }
+
+    // Moves the target to the center of `bounds` and backs off until the whole box fits in view
+    pub fn frame(&mut self, camera: &mut Camera, projection: &Projection, bounds: &Aabb) {
+        if bounds.is_empty() {
+            return;
+        }
+
+        // the narrower of the vertical and horizontal field of view
+        let half_fovy = projection.fovy().0 * 0.5;
+        let half_fovx = (half_fovy.tan() * projection.aspect()).atan();
+        let half_fov = half_fovy.min(half_fovx);
+
+        // a little margin so the model doesn't touch the edges of the window
+        let radius = bounds.radius().max(0.001) * 1.1;
+        self.target = bounds.center();
+        self.distance = (radius / half_fov.sin()).clamp(self.min_distance, self.max_distance);
+
+        self.apply(camera);
+    }
+
+    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        let amount = if state == ElementState::Pressed { 1.0 } else { 0.0 };
+
+        match key {
+            VirtualKeyCode::W | VirtualKeyCode::Up => {
+                self.amount_up = amount;
+                true
+            },
+            VirtualKeyCode::S | VirtualKeyCode::Down => {
+                self.amount_down = amount;
+                true
+            },
+            VirtualKeyCode::A | VirtualKeyCode::Left => {
+                self.amount_left = amount;
+                true
+            },
+            VirtualKeyCode::D | VirtualKeyCode::Right => {
+                self.amount_right = amount;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+
+        match button {
+            MouseButton::Left => {
+                self.rotating = pressed;
+                true
+            },
+            MouseButton::Right | MouseButton::Middle => {
+                self.panning = pressed;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        if self.rotating {
+            self.rotate_horizontal += mouse_dx as f32;
+            self.rotate_vertical += mouse_dy as f32;
+        } else if self.panning {
+            self.pan_horizontal += mouse_dx as f32;
+            self.pan_vertical += mouse_dy as f32;
+        }
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll += match delta {
+            // assuming a line is about 100 pixels
+            MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
+            MouseScrollDelta::PixelDelta(PhysicalPosition {
+                y: scroll,
+                ..
+            }) => *scroll as f32,
+        };
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        // Rotate, dragging right swings the camera left so the model appears to turn right
+        camera.yaw += Rad(self.rotate_horizontal * self.sensitivity + (self.amount_right - self.amount_left) * self.speed * dt);
+        camera.pitch += Rad(-self.rotate_vertical * self.sensitivity + (self.amount_up - self.amount_down) * self.speed * dt);
+        camera.pitch = Rad(camera.pitch.0.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+
+        // Pan along the camera's right and up vectors, the scene follows the mouse
+        let forward = camera.forward();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let pan_scale = self.distance * self.pan_sensitivity;
+        self.target += (right * -self.pan_horizontal + up * self.pan_vertical) * pan_scale;
+
+        // Dolly, scaled by the distance so it slows down close to the target and never passes it
+        self.distance = (self.distance * (-self.scroll * self.zoom_sensitivity).exp())
+            .clamp(self.min_distance, self.max_distance);
+
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.pan_horizontal = 0.0;
+        self.pan_vertical = 0.0;
+        self.scroll = 0.0;
+
+        self.apply(camera);
+    }
+
+    // place the camera `distance` behind the target along its view direction
+    fn apply(&self, camera: &mut Camera) {
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
@@ -1,19 +1,6 @@
 use cgmath::*;
 
-// The coordinate system in Wgpu is based on DirectX, and Metal's coordinate systems.
-// That means that in normalized device coordinates (opens new window)
-// the x axis and y axis are in the range of -1.0 to +1.0,
-// and the z axis is 0.0 to +1.0.
-// The cgmath crate (as well as most game math crates) is built for OpenGL's coordinate system.
-// This matrix will scale and translate our scene from OpenGL's coordinate system to WGPU's.
-// We'll define it as follows.
-#[rustfmt::skip]
-pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
-);
+use crate::camera::camera::OPENGL_TO_WGPU_MATRIX;
 
 // We need this for Rust to store our data correctly for the shaders
 #[repr(C)]
@@ -132,9 +119,13 @@ impl OrthoCameraBuffer {
     }
 }
 
+// Maps logical pixels to clip space with (0, 0) in the top left corner and y pointing down.
+// The size is given in physical pixels like everything else that comes from the surface, the
+// scale factor turns that into logical pixels so things keep their size on HiDPI screens
 pub struct OrthoProjection {
     width: f32,
     height: f32,
+    scale_factor: f32,
     znear: f32,
     zfar: f32,
 }
@@ -144,6 +135,7 @@ impl Default for OrthoProjection {
         Self {
             width: 0.0,
             height: 0.0,
+            scale_factor: 1.0,
             znear: -1.0,
             zfar: 1.0,
         }
@@ -160,28 +152,40 @@ impl OrthoProjection {
         Self {
             width: width as f32,
             height: height as f32,
+            scale_factor: 1.0,
             znear,
             zfar,
         }
     }
 
+    pub fn with_scale_factor(mut self, scale_factor: f32) -> Self {
+        self.set_scale_factor(scale_factor);
+        self
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.width = width as f32;
         self.height = height as f32;
     }
 
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor.max(f32::EPSILON);
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    // the size of the target in the units things are placed in
+    pub fn logical_size(&self) -> [f32; 2] {
+        [self.width / self.scale_factor, self.height / self.scale_factor]
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        // OPENGL_TO_WGPU_MATRIX * ortho(0.0, self.width, self.height, 0.0, self.znear, self.zfar)
-        // let projection: Matrix4<f32> = [
-        //     [2.0 / self.width, 0.0, 0.0, 0.0],
-        //     [0.0, -2.0 / self.height, 0.0, 0.0],
-        //     [0.0, 0.0, 1.0, 0.0],
-        //     [-1.0, 1.0, 0.0, 1.0],
-        // ].into();
-        let projection = cgmath::ortho(0.0, self.width, self.height, 0.0, -10.0, 100.0);
+        let [width, height] = self.logical_size();
+        let projection = cgmath::ortho(0.0, width, height, 0.0, self.znear, self.zfar);
 
         OPENGL_TO_WGPU_MATRIX * projection
-        // projection
     }
 }
 
@@ -0,0 +1,121 @@
+use cgmath::*;
+
+use crate::camera::{Camera, Projection};
+use crate::model::Aabb;
+
+// below this a ray is treated as parallel to a triangle
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+// A half line starting at `origin`. Distances along it are in multiples of `direction`, which
+// is normalized for rays built from the camera so they come out in world units
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new<P: Into<Point3<f32>>, V: Into<Vector3<f32>>>(origin: P, direction: V) -> Self {
+        Self {
+            origin: origin.into(),
+            direction: direction.into(),
+        }
+    }
+
+    // The ray under a cursor at `position`, in pixels from the top left of a viewport that is
+    // `size` pixels big. winit reports the cursor in physical pixels, so pass the physical
+    // size of the window as well. Starts on the near plane
+    pub fn from_screen(
+        camera: &Camera,
+        projection: &Projection,
+        position: (f32, f32),
+        size: (u32, u32),
+    ) -> Self {
+        let view_projection = projection.calc_matrix() * camera.calc_matrix();
+        let inverse = view_projection.invert().unwrap_or_else(Matrix4::identity);
+
+        let (width, height) = (size.0.max(1) as f32, size.1.max(1) as f32);
+        let x = position.0 / width * 2.0 - 1.0;
+        let y = 1.0 - position.1 / height * 2.0;
+
+        // wgpu's depth goes from 0 at the near plane to 1 at the far plane
+        let unproject = |z: f32| {
+            let point = inverse * Vector4::new(x, y, z, 1.0);
+            Point3::from_homogeneous(point)
+        };
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+
+        Self::new(near, (far - near).normalize())
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // The same ray in the space `matrix` maps to. The direction is not renormalized, so a
+    // distance found against the transformed ray is still a distance along this one
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        Self {
+            origin: matrix.transform_point(self.origin),
+            direction: matrix.transform_vector(self.direction),
+        }
+    }
+
+    // Distance to where the ray enters the box, 0 when it starts inside it (slab test)
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        if aabb.is_empty() {
+            return None;
+        }
+
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            // a zero direction gives infinities here, or nan for an origin exactly on a slab
+            // which max/min then ignore
+            let inverse = 1.0 / self.direction[axis];
+            let mut t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let mut t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            near = near.max(t0);
+            far = far.min(t1);
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    // Distance to the triangle `a b c`, hit from either side (Möller–Trumbore)
+    pub fn intersect_triangle(&self, a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge2.dot(q) * inverse_determinant;
+        (distance >= 0.0).then_some(distance)
+    }
+}
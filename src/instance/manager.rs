@@ -0,0 +1,174 @@
+use std::ops::Range;
+
+use crate::instance::{Instance, InstanceRaw};
+
+// Instances that can be added, removed and changed while the app runs. Changes are kept on the
+// cpu until `upload`, which only writes the part of the buffer that changed. The buffer
+// doubles in size when it runs out of room instead of being recreated for every new instance
+pub struct InstanceManager {
+    instances: Vec<Instance>,
+    pub buffer: wgpu::Buffer,
+    // how many instances fit in `buffer`
+    capacity: usize,
+    // instances that changed since the last upload
+    dirty: Option<Range<usize>>,
+}
+
+impl InstanceManager {
+    // room for this many instances before the first time the buffer grows
+    pub const INITIAL_CAPACITY: usize = 16;
+
+    pub fn new(device: &wgpu::Device, instances: Vec<Instance>) -> Self {
+        let capacity = instances.len().max(Self::INITIAL_CAPACITY);
+        let buffer = Self::create_buffer(device, capacity);
+
+        Self {
+            dirty: (!instances.is_empty()).then_some(0..instances.len()),
+            instances,
+            buffer,
+            capacity,
+        }
+    }
+
+    pub fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        self.dirty = match self.dirty.take() {
+            Some(dirty) => Some(dirty.start.min(range.start)..dirty.end.max(range.end)),
+            None => Some(range),
+        };
+    }
+
+    // returns the index of the new instance
+    pub fn add(&mut self, instance: Instance) -> usize {
+        let index = self.instances.len();
+        self.instances.push(instance);
+        self.mark_dirty(index..index + 1);
+
+        index
+    }
+
+    pub fn extend(&mut self, instances: impl IntoIterator<Item = Instance>) {
+        let start = self.instances.len();
+        self.instances.extend(instances);
+        let end = self.instances.len();
+
+        if end > start {
+            self.mark_dirty(start..end);
+        }
+    }
+
+    // Keeps the order, every instance after `index` moves down one and has to be uploaded again
+    pub fn remove(&mut self, index: usize) -> Instance {
+        let instance = self.instances.remove(index);
+        if index < self.instances.len() {
+            self.mark_dirty(index..self.instances.len());
+        }
+
+        instance
+    }
+
+    // Moves the last instance into `index`, so only that one has to be uploaded again
+    pub fn swap_remove(&mut self, index: usize) -> Instance {
+        let instance = self.instances.swap_remove(index);
+        if index < self.instances.len() {
+            self.mark_dirty(index..index + 1);
+        }
+
+        instance
+    }
+
+    pub fn set(&mut self, index: usize, instance: Instance) {
+        self.instances[index] = instance;
+        self.mark_dirty(index..index + 1);
+    }
+
+    // replaces every instance
+    pub fn set_all(&mut self, instances: Vec<Instance>) {
+        self.instances = instances;
+        self.dirty = (!self.instances.is_empty()).then_some(0..self.instances.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.dirty = None;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Instance> {
+        self.instances.get(index)
+    }
+
+    // the instance is assumed to change, it is uploaded again either way
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Instance> {
+        if index < self.instances.len() {
+            self.mark_dirty(index..index + 1);
+        }
+
+        self.instances.get_mut(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Instance> {
+        self.instances.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // the instances waiting to be uploaded
+    pub fn dirty_range(&self) -> Option<Range<usize>> {
+        self.dirty.clone()
+    }
+
+    // every instance, for `draw_model_instanced` and friends
+    pub fn range(&self) -> Range<u32> {
+        0..self.instances.len() as u32
+    }
+
+    pub fn transforms(&self) -> Vec<cgmath::Matrix4<f32>> {
+        self.instances.iter()
+            .map(Instance::model_matrix)
+            .collect()
+    }
+
+    // Writes whatever changed since the last upload. When the instances no longer fit, the
+    // buffer is recreated at double the size (or more) and filled from scratch
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().max(self.capacity * 2);
+            self.buffer = Self::create_buffer(device, self.capacity);
+            self.dirty = Some(0..self.instances.len());
+        }
+
+        let Some(dirty) = self.dirty.take() else {
+            return;
+        };
+        // anything past the end was removed, it doesn't need writing
+        let dirty = dirty.start..dirty.end.min(self.instances.len());
+        if dirty.is_empty() {
+            return;
+        }
+
+        let data = self.instances[dirty.clone()].iter()
+            .map(Instance::to_raw)
+            .collect::<Vec<_>>();
+        let offset = (dirty.start * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress;
+
+        queue.write_buffer(&self.buffer, offset, bytemuck::cast_slice(&data));
+    }
+}
@@ -1,6 +1,31 @@
+pub mod manager;
+
+pub use manager::InstanceManager;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    // per axis, applied before the rotation
+    pub scale: cgmath::Vector3<f32>,
+    // multiplies the material's base color, white leaves it as is
+    pub tint: [f32; 4],
+    // passed through to the shaders untouched, for per-instance effects
+    pub custom: [f32; 4],
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        use cgmath::{One, Zero};
+
+        Self {
+            position: cgmath::Vector3::zero(),
+            rotation: cgmath::Quaternion::one(),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            tint: [1.0; 4],
+            custom: [0.0; 4],
+        }
+    }
 }
 
 #[repr(C)]
@@ -8,19 +33,77 @@ pub struct Instance {
 pub struct InstanceRaw {
     pub model: [[f32; 4]; 4],
     pub normal: [[f32; 3]; 3],
+    pub tint: [f32; 4],
+    pub custom: [f32; 4],
 }
 
 impl Instance {
+    pub fn new(position: cgmath::Vector3<f32>, rotation: cgmath::Quaternion<f32>) -> Self {
+        Self {
+            position,
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_scale(mut self, scale: cgmath::Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_uniform_scale(self, scale: f32) -> Self {
+        self.with_scale(cgmath::Vector3::new(scale, scale, scale))
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_custom(mut self, custom: [f32; 4]) -> Self {
+        self.custom = custom;
+        self
+    }
+
+    // translation * rotation * scale
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
-        let model = cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation);
         InstanceRaw {
-            model: model.into(),
-            normal: cgmath::Matrix3::from(self.rotation).into(),
+            tint: self.tint,
+            custom: self.custom,
+            ..InstanceRaw::from_matrix(self.model_matrix())
         }
     }
 }
 
 impl InstanceRaw {
+    // build the raw instance data from an arbitrary model matrix. The normal matrix is the
+    // inverse-transpose of the upper 3x3 so normals stay correct under non-uniform scale
+    pub fn from_matrix(model: cgmath::Matrix4<f32>) -> Self {
+        use cgmath::{Matrix, SquareMatrix};
+
+        let upper = cgmath::Matrix3::from_cols(
+            model.x.truncate(),
+            model.y.truncate(),
+            model.z.truncate(),
+        );
+        let normal = upper.invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or_else(cgmath::Matrix3::identity);
+
+        InstanceRaw {
+            model: model.into(),
+            normal: normal.into(),
+            tint: [1.0; 4],
+            custom: [0.0; 4],
+        }
+    }
+
     pub fn layout() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
 
@@ -65,36 +148,19 @@ impl InstanceRaw {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // tint
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // custom
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 29]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
-
-pub struct InstanceBuffer {
-    pub buffer: wgpu::Buffer,
-}
-
-impl InstanceBuffer {
-    pub fn new(device: &wgpu::Device, instances: &Vec<Instance>) -> Self {
-        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-        let buffer = InstanceBuffer::create_buffer(device, &instance_data);
-
-        Self {
-            buffer,
-        }
-    }
-
-    pub fn create_buffer(device: &wgpu::Device, instances: &Vec<InstanceRaw>) -> wgpu::Buffer {
-        use wgpu::util::DeviceExt;
-
-        let instance_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Instance Buffer"),
-                contents: bytemuck::cast_slice(instances),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
-
-        instance_buffer
-    }
-}
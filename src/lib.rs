@@ -11,13 +11,16 @@ use winit::{
 
 pub mod app;
 pub mod render;
-mod resources;
+pub mod resources;
 pub mod texture;
 pub mod camera;
 pub mod instance;
 pub mod model;
 pub mod light;
 pub mod primitives;
+pub mod sprite;
+pub mod text;
+pub mod scene;
 
 use crate::app::App;
 
@@ -104,9 +107,7 @@ pub async fn run() {
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta, },
                 .. // we're not using device_id currently
-            } => if app.mouse_pressed {
-                app.camera_controller.process_mouse(delta.0, delta.1)
-            },
+            } => app.process_mouse(delta.0, delta.1),
             Event::WindowEvent {
                 ref event,
                 window_id,
@@ -124,18 +125,10 @@ pub async fn run() {
                     WindowEvent::Resized(physical_size) => {
                         app.resize(*physical_size);
                     },
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                        app.set_scale_factor(*scale_factor);
                         app.resize(**new_inner_size);
                     },
-                    // WindowEvent::CursorMoved {
-                    //     device_id,
-                    //     position,
-                    //     ..
-                    // } => {
-                    //     let scale_factor = app.window().scale_factor();
-                    //     let logical_position = position.to_logical::<i32>(scale_factor);
-                    //     println!("Scale Factor: {} Physical {:?} Logical {:?}", scale_factor, position, logical_position);
-                    // },
                     _ => {}
                 }
             },
@@ -1,69 +1,303 @@
 use std::ops::Range;
 use crate::model::{Model, Mesh};
 
+pub mod shadow;
+
+pub use shadow::{ShadowMap, ShadowOptions, ShadowUniform, DrawShadow, MAX_SHADOWS};
+
+// has to match MAX_LIGHTS in shader.wgsl and light.wgsl. A fixed size uniform array is used
+// instead of a storage buffer because WebGL2 doesn't support storage buffers
+pub const MAX_LIGHTS: usize = 16;
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LightKind {
+    Point = 0,
+    // lights everything from `direction`, the position is only used to place its gizmo
+    Directional = 1,
+    Spot = 2,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    // distance at which point and spot lights fade out completely, 0.0 means unlimited
+    pub range: f32,
+    // constant, linear and quadratic falloff over distance
+    pub attenuation: [f32; 3],
+    // spot lights are at full strength inside the inner cone and fade out towards the outer cone
+    pub inner_cone_angle: cgmath::Rad<f32>,
+    pub outer_cone_angle: cgmath::Rad<f32>,
+    // only directional and spot lights can cast shadows
+    pub cast_shadows: bool,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            position: [0.0; 3],
+            direction: [0.0, -1.0, 0.0],
+            color: [1.0; 3],
+            intensity: 1.0,
+            range: 0.0,
+            attenuation: [1.0, 0.0, 0.0],
+            inner_cone_angle: cgmath::Deg(20.0).into(),
+            outer_cone_angle: cgmath::Deg(30.0).into(),
+            cast_shadows: false,
+        }
+    }
+}
+
+impl Light {
+    pub fn point(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position,
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn directional(direction: [f32; 3], color: [f32; 3]) -> Self {
+        use cgmath::InnerSpace;
+
+        // far enough away from the origin that the gizmo doesn't sit inside the scene
+        let position = cgmath::Vector3::from(direction).normalize() * -3.0;
+
+        Self {
+            kind: LightKind::Directional,
+            position: position.into(),
+            direction,
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn spot<A: Into<cgmath::Rad<f32>>>(
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        inner_cone_angle: A,
+        outer_cone_angle: A,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot,
+            position,
+            direction,
+            color,
+            inner_cone_angle: inner_cone_angle.into(),
+            outer_cone_angle: outer_cone_angle.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.attenuation = [constant, linear, quadratic];
+        self
+    }
+
+    pub fn with_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+
+    pub fn update_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    pub fn update_color(&mut self, color: [f32; 3]) {
+        self.color = color;
+    }
+
+    pub fn to_uniform(&self) -> LightUniform {
+        use cgmath::{InnerSpace, SquareMatrix};
+
+        let direction = cgmath::Vector3::from(self.direction);
+        let direction = if direction.magnitude2() > 0.0 {
+            direction.normalize()
+        } else {
+            cgmath::Vector3::new(0.0, -1.0, 0.0)
+        };
+
+        LightUniform {
+            position: self.position,
+            kind: self.kind as u32,
+            direction: direction.into(),
+            range: self.range,
+            color: self.color,
+            intensity: self.intensity,
+            attenuation: self.attenuation,
+            // the shader compares against the cosine so it doesn't need to call acos per pixel
+            inner_cone_cos: self.inner_cone_angle.0.cos(),
+            outer_cone_cos: self.outer_cone_angle.0.cos(),
+            // filled in by the LightManager once it knows which shadow layer the light got
+            shadow_index: -1,
+            _padding: [0; 2],
+            shadow_view_projection: cgmath::Matrix4::identity().into(),
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniform {
     pub position: [f32; 3],
-    // Due to uniforms requiring 16 bytes (4 floats), we need to add padding here
-    pub _padding: u32,
+    pub kind: u32,
+    pub direction: [f32; 3],
+    pub range: f32,
     pub color: [f32; 3],
-    pub _padding2: u32,
+    pub intensity: f32,
+    pub attenuation: [f32; 3],
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    // layer in the shadow map, -1 when the light doesn't cast shadows
+    pub shadow_index: i32,
+    // Due to uniforms requiring 16 bytes (4 floats), we need to add padding here
+    pub _padding: [u32; 2],
+    pub shadow_view_projection: [[f32; 4]; 4],
 }
 
-pub struct Light {
-    pub uniform: LightUniform,
+// written in front of the light array in the buffer
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsHeader {
+    count: u32,
+    _padding: [u32; 3],
+}
+
+// Owns every light in the scene and the uniform buffer they are uploaded to
+pub struct LightManager {
+    pub lights: Vec<Light>,
+    pub shadows: ShadowMap,
     pub buffer: wgpu::Buffer,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
 }
 
-impl Light {
-    pub fn new(device: &wgpu::Device, position: [f32; 3], color: [f32; 3]) -> Self {
-        let uniform = LightUniform {
-            position,
-            _padding: 0,
-            color,
-            _padding2: 0,
-        };
+impl LightManager {
+    pub fn new(device: &wgpu::Device) -> Self {
+        LightManager::with_shadow_options(device, ShadowOptions::default())
+    }
 
-        let buffer = Light::create_buffer(device, &uniform);
+    pub fn with_shadow_options(device: &wgpu::Device, shadow_options: ShadowOptions) -> Self {
+        let buffer = LightManager::create_buffer(device);
+        let shadows = ShadowMap::new(device, shadow_options);
 
-        let bind_group_layout = Light::create_bind_group_layout(device);
-        let bind_group = Light::create_bind_group(device, &bind_group_layout, &buffer);
+        let bind_group_layout = LightManager::create_bind_group_layout(device);
+        let bind_group = LightManager::create_bind_group(device, &bind_group_layout, &buffer, &shadows);
 
         Self {
-            uniform,
+            lights: Vec::new(),
+            shadows,
             buffer,
             bind_group_layout,
             bind_group,
         }
     }
 
-    pub fn update_position(&mut self, position: [f32; 3]) {
-        self.uniform.position = position;
+    // returns the index of the new light, or an error when all MAX_LIGHTS slots are taken
+    pub fn add(&mut self, light: Light) -> anyhow::Result<usize> {
+        if self.lights.len() >= MAX_LIGHTS {
+            anyhow::bail!("Unable to add light, only {} lights are supported", MAX_LIGHTS);
+        }
+
+        self.lights.push(light);
+
+        Ok(self.lights.len() - 1)
     }
 
-    pub fn update_color(&mut self, color: [f32; 3]) {
-        self.uniform.color = color;
+    pub fn remove(&mut self, index: usize) -> Light {
+        self.lights.remove(index)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Light> {
+        self.lights.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Light> {
+        self.lights.get_mut(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    // indices of the lights that get a shadow map and the view projection it is rendered with,
+    // in shadow layer order. Lights without a view projection (like a zero direction) or past
+    // MAX_SHADOWS are lit without shadows
+    pub fn shadow_casters(&self) -> Vec<(usize, cgmath::Matrix4<f32>)> {
+        self.lights.iter()
+            .enumerate()
+            .filter(|(_, light)| light.cast_shadows)
+            .filter_map(|(index, light)| {
+                shadow::light_view_projection(light, &self.shadows.options).map(|view_projection| (index, view_projection))
+            })
+            .take(MAX_SHADOWS)
+            .collect()
+    }
+
+    // upload every light, the slots past `len()` are left as they are since the shaders stop at count
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let header = LightsHeader {
+            count: self.lights.len() as u32,
+            _padding: [0; 3],
+        };
+        let mut uniforms = self.lights.iter()
+            .map(Light::to_uniform)
+            .collect::<Vec<_>>();
+
+        let mut view_projections = Vec::new();
+        for (index, view_projection) in self.shadow_casters() {
+            uniforms[index].shadow_index = view_projections.len() as i32;
+            uniforms[index].shadow_view_projection = view_projection.into();
+            view_projections.push(view_projection);
+        }
+        self.shadows.update(queue, &view_projections);
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[header]));
+        if !uniforms.is_empty() {
+            queue.write_buffer(
+                &self.buffer,
+                std::mem::size_of::<LightsHeader>() as wgpu::BufferAddress,
+                bytemuck::cast_slice(&uniforms),
+            );
+        }
     }
 
-    pub fn create_buffer(device: &wgpu::Device, uniform: &LightUniform) -> wgpu::Buffer {
-        use wgpu::util::DeviceExt;
+    pub fn create_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+        let size = std::mem::size_of::<LightsHeader>() + std::mem::size_of::<LightUniform>() * MAX_LIGHTS;
 
-        let light_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Light VB"),
-                contents: bytemuck::cast_slice(&[* uniform]),
+        device.create_buffer(
+            &wgpu::BufferDescriptor {
+                label: Some("Light Buffer"),
+                size: size as wgpu::BufferAddress,
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
             }
-        );
-
-        light_buffer
+        )
     }
 
     pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
-        let bind_group_layout = device.create_bind_group_layout(
+        device.create_bind_group_layout(
             &wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
@@ -75,30 +309,70 @@ impl Light {
                             min_binding_size: None,
                         },
                         count: None,
-                    }
+                    },
+                    // shadow maps
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
-                label: None,
+                label: Some("light_bind_group_layout"),
             }
-        );
-
-        bind_group_layout
+        )
     }
 
-    pub fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
-        let bind_group = device.create_bind_group(
+    pub fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        shadows: &ShadowMap,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(
             &wgpu::BindGroupDescriptor {
-                layout: &layout,
+                layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
                         resource: buffer.as_entire_binding(),
-                    }
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&shadows.texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&shadows.texture.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: shadows.uniform_buffer.as_entire_binding(),
+                    },
                 ],
-                label: None,
+                label: Some("light_bind_group"),
             }
-        );
-
-        bind_group
+        )
     }
 }
 
@@ -132,6 +406,14 @@ pub trait DrawLight<'a> {
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+
+    // one gizmo per light, the instance index picks the light in light.wgsl
+    fn draw_lights(
+        &mut self,
+        model: &'a Model,
+        lights: &'a LightManager,
+        camera_bind_group: &'a wgpu::BindGroup,
+    );
 }
 
 impl<'a, 'b> DrawLight<'b> for wgpu::RenderPass<'a>
@@ -186,5 +468,22 @@ where
             );
         }
     }
-}
 
+    fn draw_lights(
+        &mut self,
+        model: &'b Model,
+        lights: &'b LightManager,
+        camera_bind_group: &'b wgpu::BindGroup,
+    ) {
+        if lights.is_empty() {
+            return;
+        }
+
+        self.draw_light_model_instanced(
+            model,
+            0..lights.len() as u32,
+            camera_bind_group,
+            &lights.bind_group,
+        );
+    }
+}
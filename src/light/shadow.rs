@@ -0,0 +1,294 @@
+use std::ops::Range;
+
+use cgmath::*;
+
+use crate::camera::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::light::{Light, LightKind};
+use crate::model::{Mesh, Model, ModelHierarchy};
+use crate::texture::Texture;
+
+// has to match MAX_SHADOWS in shader.wgsl, every shadow casting light takes one layer of the
+// shadow map array and the shader treats indices past it as unshadowed
+pub const MAX_SHADOWS: usize = 4;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowOptions {
+    // width and height of every shadow map, fixed once the ShadowMap is created
+    pub map_size: u32,
+    // subtracted from the depth before comparing, removes shadow acne on lit surfaces
+    pub depth_bias: f32,
+    // moves the sampled position along the surface normal, in world units
+    pub normal_bias: f32,
+    // width of the square PCF kernel in texels, 1 disables filtering
+    pub pcf_kernel_size: u32,
+    // half size of the box directional lights render their shadows from, centered on the origin
+    pub directional_extent: f32,
+}
+
+impl Default for ShadowOptions {
+    fn default() -> Self {
+        Self {
+            map_size: 1024,
+            depth_bias: 0.002,
+            normal_bias: 0.02,
+            pcf_kernel_size: 3,
+            directional_extent: 10.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    pub depth_bias: f32,
+    pub normal_bias: f32,
+    pub pcf_radius: u32,
+    pub texel_size: f32,
+}
+
+impl ShadowUniform {
+    pub fn new(options: &ShadowOptions) -> Self {
+        Self {
+            depth_bias: options.depth_bias,
+            normal_bias: options.normal_bias,
+            // an even kernel size is rounded down to the next odd one
+            pcf_radius: options.pcf_kernel_size.max(1) / 2,
+            texel_size: 1.0 / options.map_size as f32,
+        }
+    }
+}
+
+// view projection the shadow map of `light` is rendered with, point lights don't cast shadows
+pub fn light_view_projection(light: &Light, options: &ShadowOptions) -> Option<Matrix4<f32>> {
+    let direction = Vector3::from(light.direction);
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    // look_to_rh breaks down when looking straight up or down the y axis
+    let up = if direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+
+    match light.kind {
+        LightKind::Point => None,
+        LightKind::Directional => {
+            let extent = options.directional_extent;
+            let eye = Point3::from_vec(-direction * extent * 2.0);
+            let view = Matrix4::look_to_rh(eye, direction, up);
+            let projection = ortho(-extent, extent, -extent, extent, 0.1, extent * 4.0);
+
+            Some(OPENGL_TO_WGPU_MATRIX * projection * view)
+        },
+        LightKind::Spot => {
+            let eye = Point3::from(light.position);
+            let view = Matrix4::look_to_rh(eye, direction, up);
+            let far = if light.range > 0.0 { light.range } else { 100.0 };
+            let fovy = Rad((light.outer_cone_angle.0 * 2.0).min(Rad::from(Deg(170.0)).0));
+            let projection = perspective(fovy, 1.0, 0.1, far);
+
+            Some(OPENGL_TO_WGPU_MATRIX * projection * view)
+        },
+    }
+}
+
+// Depth texture array the shadow casting lights render into, plus the per layer camera
+// uniforms used by the shadow pass
+pub struct ShadowMap {
+    pub options: ShadowOptions,
+    pub texture: Texture,
+    pub layer_views: Vec<wgpu::TextureView>,
+    pub uniform_buffer: wgpu::Buffer,
+    pub layer_buffers: Vec<wgpu::Buffer>,
+    pub layer_bind_group_layout: wgpu::BindGroupLayout,
+    pub layer_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl ShadowMap {
+    pub fn new(device: &wgpu::Device, options: ShadowOptions) -> Self {
+        use wgpu::util::DeviceExt;
+
+        let texture = Texture::create_shadow_texture(device, options.map_size, MAX_SHADOWS as u32, "shadow_texture");
+        let layer_views = (0..MAX_SHADOWS as u32)
+            .map(|layer| texture.create_layer_view(layer))
+            .collect::<Vec<_>>();
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[ShadowUniform::new(&options)]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let layer_bind_group_layout = ShadowMap::create_layer_bind_group_layout(device);
+        let identity: [[f32; 4]; 4] = Matrix4::identity().into();
+        let layer_buffers = (0..MAX_SHADOWS)
+            .map(|_| device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Shadow Layer Buffer"),
+                    contents: bytemuck::cast_slice(&[identity]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                }
+            ))
+            .collect::<Vec<_>>();
+        let layer_bind_groups = layer_buffers.iter()
+            .map(|buffer| device.create_bind_group(
+                &wgpu::BindGroupDescriptor {
+                    layout: &layer_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        }
+                    ],
+                    label: Some("shadow_layer_bind_group"),
+                }
+            ))
+            .collect::<Vec<_>>();
+
+        Self {
+            options,
+            texture,
+            layer_views,
+            uniform_buffer,
+            layer_buffers,
+            layer_bind_group_layout,
+            layer_bind_groups,
+        }
+    }
+
+    // upload the options and the view projection of every shadow casting light, in layer order
+    pub fn update(&self, queue: &wgpu::Queue, view_projections: &[Matrix4<f32>]) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[ShadowUniform::new(&self.options)]));
+
+        for (buffer, view_projection) in self.layer_buffers.iter().zip(view_projections) {
+            let matrix: [[f32; 4]; 4] = (*view_projection).into();
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[matrix]));
+        }
+    }
+
+    pub fn create_layer_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }
+                ],
+                label: Some("shadow_layer_bind_group_layout"),
+            }
+        )
+    }
+
+    // the shadow map as a plain float texture for the debug view, which reads it with textureLoad
+    pub fn create_debug_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    }
+                ],
+                label: Some("shadow_debug_bind_group_layout"),
+            }
+        )
+    }
+
+    pub fn create_debug_bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+        device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.texture.view),
+                    }
+                ],
+                label: Some("shadow_debug_bind_group"),
+            }
+        )
+    }
+}
+
+// Draws depth only from a light's point of view, the instance buffer is expected at slot 1
+pub trait DrawShadow<'a> {
+    fn draw_mesh_shadow_instanced(
+        &mut self,
+        mesh: &'a Mesh,
+        instances: Range<u32>,
+        layer_bind_group: &'a wgpu::BindGroup,
+    );
+
+    fn draw_model_shadow_instanced(
+        &mut self,
+        model: &'a Model,
+        instances: Range<u32>,
+        layer_bind_group: &'a wgpu::BindGroup,
+    );
+
+    fn draw_model_hierarchy_shadow(
+        &mut self,
+        hierarchy: &'a ModelHierarchy,
+        layer_bind_group: &'a wgpu::BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawShadow<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_mesh_shadow_instanced(
+        &mut self,
+        mesh: &'b Mesh,
+        instances: Range<u32>,
+        layer_bind_group: &'b wgpu::BindGroup,
+    ) {
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_bind_group(0, layer_bind_group, &[]);
+        self.draw_indexed(0..mesh.num_elements, 0, instances);
+    }
+
+    fn draw_model_shadow_instanced(
+        &mut self,
+        model: &'b Model,
+        instances: Range<u32>,
+        layer_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for mesh in &model.meshes {
+            self.draw_mesh_shadow_instanced(mesh, instances.clone(), layer_bind_group);
+        }
+    }
+
+    fn draw_model_hierarchy_shadow(
+        &mut self,
+        hierarchy: &'b ModelHierarchy,
+        layer_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for (index, node) in hierarchy.nodes.iter().enumerate() {
+            if node.meshes.is_empty() {
+                continue;
+            }
+
+            self.set_vertex_buffer(1, hierarchy.buffer.slice(ModelHierarchy::node_range(index)));
+
+            for &mesh_index in &node.meshes {
+                self.draw_mesh_shadow_instanced(&hierarchy.model.meshes[mesh_index], 0..1, layer_bind_group);
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use cgmath::*;
+
+// Axis aligned bounding box. `Aabb::empty()` contains nothing and grows to whatever it is
+// unioned with, so bounds can be folded together without special casing the first one
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new<P: Into<Point3<f32>>>(min: P, max: P) -> Self {
+        Self {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<P: Into<Point3<f32>>>(points: impl IntoIterator<Item = P>) -> Self {
+        points.into_iter().fold(Self::empty(), |bounds, point| bounds.including(point.into()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn including(&self, point: Point3<f32>) -> Self {
+        Self {
+            min: Point3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            max: Point3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        if other.is_empty() {
+            return *self;
+        }
+
+        self.including(other.min).including(other.max)
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    // radius of the sphere around `center` that contains the whole box
+    pub fn radius(&self) -> f32 {
+        self.size().magnitude() * 0.5
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    // the box around this one after `matrix` is applied to it
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        Self::from_points(self.corners().map(|corner| matrix.transform_point(corner)))
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new<P: Into<Point3<f32>>>(center: P, radius: f32) -> Self {
+        Self {
+            center: center.into(),
+            radius,
+        }
+    }
+
+    // Centered on the points' bounding box, which is tighter than the box's own sphere for
+    // anything that doesn't fill its corners
+    pub fn from_points<P: Into<Point3<f32>> + Copy>(points: &[P]) -> Self {
+        if points.is_empty() {
+            return Self::new(Point3::origin(), 0.0);
+        }
+
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points.iter()
+            .map(|point| center.distance((*point).into()))
+            .fold(0.0, f32::max);
+
+        Self::new(center, radius)
+    }
+
+    // a sphere that contains this one after `matrix` is applied, non-uniform scale grows the
+    // radius by the largest axis
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let scale = matrix.x.truncate().magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+
+        Self::new(matrix.transform_point(self.center), self.radius * scale)
+    }
+}
@@ -0,0 +1,224 @@
+use std::ops::Range;
+
+use cgmath::*;
+
+use crate::instance::InstanceRaw;
+use crate::model::{Aabb, Model, DrawModel};
+
+// Local translation/rotation/scale of a node relative to its parent
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl From<gltf::scene::Transform> for Transform {
+    fn from(transform: gltf::scene::Transform) -> Self {
+        let (translation, rotation, scale) = transform.decomposed();
+        // glTF stores quaternions as [x, y, z, w], cgmath takes w first
+        let [x, y, z, w] = rotation;
+
+        Self {
+            translation: translation.into(),
+            rotation: Quaternion::new(w, x, y, z),
+            scale: scale.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    // indices into `Model::meshes`, a glTF mesh with several primitives maps to several meshes
+    pub meshes: Vec<usize>,
+    // indices into `ModelHierarchy::nodes`
+    pub children: Vec<usize>,
+    pub parent: Option<usize>,
+}
+
+// Walks the tree from `roots` and returns the world transform of every node, in the same order
+// as `nodes`. Nodes that can't be reached from a root keep their local transform
+pub fn compute_world_transforms(nodes: &[Node], roots: &[usize]) -> Vec<Matrix4<f32>> {
+    let mut world_transforms: Vec<Matrix4<f32>> = nodes.iter()
+        .map(|node| node.transform.matrix())
+        .collect();
+    let mut stack: Vec<(usize, Matrix4<f32>)> = roots.iter()
+        .map(|&root| (root, Matrix4::identity()))
+        .collect();
+
+    while let Some((index, parent_transform)) = stack.pop() {
+        let world = parent_transform * nodes[index].transform.matrix();
+        world_transforms[index] = world;
+
+        for &child in &nodes[index].children {
+            stack.push((child, world));
+        }
+    }
+
+    world_transforms
+}
+
+// A model that keeps the node tree it was loaded with. The meshes are stored untransformed and
+// each node's world transform is uploaded as an instance so nodes can be moved at runtime
+pub struct ModelHierarchy {
+    pub model: Model,
+    pub nodes: Vec<Node>,
+    pub roots: Vec<usize>,
+    pub world_transforms: Vec<Matrix4<f32>>,
+    pub buffer: wgpu::Buffer,
+}
+
+impl ModelHierarchy {
+    pub fn new(device: &wgpu::Device, model: Model, nodes: Vec<Node>, roots: Vec<usize>) -> Self {
+        use wgpu::util::DeviceExt;
+
+        let world_transforms = compute_world_transforms(&nodes, &roots);
+        let mut instance_data = world_transforms.iter()
+            .map(|world| InstanceRaw::from_matrix(*world))
+            .collect::<Vec<_>>();
+
+        // a zero sized buffer is not allowed, keep room for at least one node
+        if instance_data.is_empty() {
+            instance_data.push(InstanceRaw::from_matrix(Matrix4::identity()));
+        }
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Model Hierarchy Buffer"),
+                contents: bytemuck::cast_slice(&instance_data),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        Self {
+            model,
+            nodes,
+            roots,
+            world_transforms,
+            buffer,
+        }
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    pub fn set_transform(&mut self, index: usize, transform: Transform) {
+        self.nodes[index].transform = transform;
+    }
+
+    // recompute the world transforms after nodes have been changed and upload them
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        self.world_transforms = compute_world_transforms(&self.nodes, &self.roots);
+
+        let instance_data = self.world_transforms.iter()
+            .map(|world| InstanceRaw::from_matrix(*world))
+            .collect::<Vec<_>>();
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&instance_data));
+    }
+
+    // bounds of every node's meshes after their world transforms
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.iter()
+            .zip(&self.world_transforms)
+            .flat_map(|(node, world)| node.meshes.iter().map(move |&mesh| (mesh, world)))
+            .fold(Aabb::empty(), |bounds, (mesh, world)| {
+                bounds.union(&self.model.meshes[mesh].bounds.transform(world))
+            })
+    }
+
+    pub(crate) fn node_range(index: usize) -> Range<wgpu::BufferAddress> {
+        let stride = std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress;
+        let start = index as wgpu::BufferAddress * stride;
+
+        start..start + stride
+    }
+}
+
+pub trait DrawModelHierarchy<'a> {
+    fn draw_model_hierarchy(
+        &mut self,
+        hierarchy: &'a ModelHierarchy,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+    // skips the node meshes that were culled, see `render::Visibility`
+    fn draw_model_hierarchy_visible(
+        &mut self,
+        hierarchy: &'a ModelHierarchy,
+        visible_nodes: &[Vec<bool>],
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawModelHierarchy<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    // each node is drawn as a single instance using its slot in the hierarchy buffer,
+    // this replaces whatever instance buffer was bound to slot 1
+    fn draw_model_hierarchy(
+        &mut self,
+        hierarchy: &'b ModelHierarchy,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for (index, node) in hierarchy.nodes.iter().enumerate() {
+            if node.meshes.is_empty() {
+                continue;
+            }
+
+            self.set_vertex_buffer(1, hierarchy.buffer.slice(ModelHierarchy::node_range(index)));
+
+            for &mesh_index in &node.meshes {
+                let mesh = &hierarchy.model.meshes[mesh_index];
+                let material = &hierarchy.model.materials[mesh.material];
+                self.draw_mesh(mesh, material, camera_bind_group, light_bind_group);
+            }
+        }
+    }
+
+    fn draw_model_hierarchy_visible(
+        &mut self,
+        hierarchy: &'b ModelHierarchy,
+        visible_nodes: &[Vec<bool>],
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for (index, (node, visible)) in hierarchy.nodes.iter().zip(visible_nodes).enumerate() {
+            if !visible.contains(&true) {
+                continue;
+            }
+
+            self.set_vertex_buffer(1, hierarchy.buffer.slice(ModelHierarchy::node_range(index)));
+
+            for (&mesh_index, _) in node.meshes.iter().zip(visible).filter(|(_, visible)| **visible) {
+                let mesh = &hierarchy.model.meshes[mesh_index];
+                let material = &hierarchy.model.materials[mesh.material];
+                self.draw_mesh(mesh, material, camera_bind_group, light_bind_group);
+            }
+        }
+    }
+}
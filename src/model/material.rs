@@ -0,0 +1,227 @@
+use crate::texture::{Texture, TextureOptions};
+
+// Scalar factors of a glTF metallic-roughness material. Each one is multiplied with the
+// matching texture in the shader, so a material without textures is described by these alone
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub occlusion_strength: f32,
+    pub normal_scale: f32,
+    // Due to uniforms requiring 16 bytes (4 floats), we need to add padding here
+    pub _padding: u32,
+}
+
+impl Default for MaterialUniform {
+    // the defaults from the glTF spec
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            emissive_factor: [0.0; 3],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            occlusion_strength: 1.0,
+            normal_scale: 1.0,
+            _padding: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MaterialTextures {
+    // sRGB color, alpha is used as coverage
+    pub base_color: Texture,
+    // tangent space normal map
+    pub normal: Texture,
+    // glTF packs roughness in the green channel and metallic in the blue channel
+    pub metallic_roughness: Texture,
+    // ambient occlusion in the red channel
+    pub occlusion: Texture,
+    // sRGB color
+    pub emissive: Texture,
+}
+
+impl MaterialTextures {
+    // 1x1 textures that leave the factors unchanged, used for every map a material doesn't have
+    pub fn new_default(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            base_color: Texture::from_color(device, queue, [255, 255, 255, 255], "Default Base Color", &TextureOptions::default()),
+            // flat normal pointing straight out of the surface
+            normal: Texture::from_color(device, queue, [128, 128, 255, 255], "Default Normal", &TextureOptions::normal_map()),
+            metallic_roughness: Texture::from_color(device, queue, [255, 255, 255, 255], "Default Metallic Roughness", &TextureOptions::linear()),
+            occlusion: Texture::from_color(device, queue, [255, 255, 255, 255], "Default Occlusion", &TextureOptions::linear()),
+            emissive: Texture::from_color(device, queue, [255, 255, 255, 255], "Default Emissive", &TextureOptions::default()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Material {
+    pub name: String,
+    pub textures: MaterialTextures,
+    pub uniform: MaterialUniform,
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        textures: MaterialTextures,
+        uniform: MaterialUniform,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let buffer = Material::create_buffer(device, name, &uniform);
+        let bind_group = Material::create_bind_group(device, name, layout, &textures, &buffer);
+
+        Self {
+            name: String::from(name),
+            textures,
+            uniform,
+            buffer,
+            bind_group,
+        }
+    }
+
+    // upload the factors after changing `uniform`
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    pub fn create_buffer(device: &wgpu::Device, name: &str, uniform: &MaterialUniform) -> wgpu::Buffer {
+        use wgpu::util::DeviceExt;
+
+        let material_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Material Buffer", name)),
+                contents: bytemuck::cast_slice(&[* uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        material_buffer
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float {
+                    filterable: true,
+                },
+            },
+            count: None,
+        };
+        // this should match the filterable field of the corresponding Texture
+        // Entry above
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    // base color
+                    texture_entry(0),
+                    sampler_entry(1),
+                    // normal map
+                    texture_entry(2),
+                    sampler_entry(3),
+                    // metallic roughness
+                    texture_entry(4),
+                    sampler_entry(5),
+                    // occlusion
+                    texture_entry(6),
+                    sampler_entry(7),
+                    // emissive
+                    texture_entry(8),
+                    sampler_entry(9),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 10,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("material_bind_group_layout"),
+            }
+        )
+    }
+
+    pub fn create_bind_group(
+        device: &wgpu::Device,
+        name: &str,
+        layout: &wgpu::BindGroupLayout,
+        textures: &MaterialTextures,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&textures.base_color.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&textures.base_color.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&textures.normal.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&textures.normal.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(&textures.metallic_roughness.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::Sampler(&textures.metallic_roughness.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: wgpu::BindingResource::TextureView(&textures.occlusion.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: wgpu::BindingResource::Sampler(&textures.occlusion.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: wgpu::BindingResource::TextureView(&textures.emissive.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 9,
+                        resource: wgpu::BindingResource::Sampler(&textures.emissive.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 10,
+                        resource: buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some(name),
+            }
+        );
+
+        bind_group
+    }
+}
@@ -1,6 +1,16 @@
 use std::ops::Range;
 
-use crate::texture::Texture;
+pub mod bounds;
+pub mod hierarchy;
+pub mod material;
+pub mod raycast;
+pub mod tangent;
+
+pub use bounds::{Aabb, BoundingSphere};
+pub use hierarchy::{Transform, Node, ModelHierarchy, DrawModelHierarchy};
+pub use material::{Material, MaterialTextures, MaterialUniform};
+pub use raycast::RayHit;
+pub use tangent::generate_tangents;
 
 // pub trait Vertex {
 //     fn layout() -> wgpu::VertexBufferLayout<'static>;
@@ -13,8 +23,8 @@ pub struct ModelVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
-    pub tangent: [f32; 3],
-    pub bitangent: [f32; 3],
+    // xyz points along u, w is the handedness: bitangent = cross(normal, tangent.xyz) * w
+    pub tangent: [f32; 4],
 }
 
 impl Vertex for ModelVertex {
@@ -40,78 +50,29 @@ impl Vertex for ModelVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
-                // tangent and bitangent
+                // tangent and handedness
                 wgpu::VertexAttribute {
                     offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
                     shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x4,
                 },
             ],
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Material {
-    pub name: String,
-    pub diffuse_texture: Texture,
-    pub normal_texture: Texture,
-    pub bind_group: wgpu::BindGroup,
-}
-
-impl Material {
-    pub fn new(
-        device: &wgpu::Device,
-        name: &str,
-        diffuse_texture: Texture,
-        normal_texture: Texture,
-        layout: &wgpu::BindGroupLayout,
-    ) -> Self {
-        let bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
-                    },
-                ],
-                label: Some(name),
-            }
-        );
-
-        Self {
-            name: String::from(name),
-            diffuse_texture,
-            normal_texture,
-            bind_group,
-        }
-    }
-}
-
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    // in the mesh's own space, before any instance transform
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    // cpu copy of the geometry for ray casting, see `Mesh::raycast`
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
 }
 
 pub struct Model {
@@ -119,6 +80,12 @@ pub struct Model {
     pub materials: Vec<Material>,
 }
 
+impl Model {
+    pub fn bounds(&self) -> Aabb {
+        self.meshes.iter().fold(Aabb::empty(), |bounds, mesh| bounds.union(&mesh.bounds))
+    }
+}
+
 pub trait DrawModel<'a> {
     fn draw_mesh(
         &mut self,
@@ -148,6 +115,14 @@ pub trait DrawModel<'a> {
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    // only the instance ranges of each mesh that survived culling, see `render::Visibility`
+    fn draw_model_visible(
+        &mut self,
+        model: &'a Model,
+        visible_instances: &[Vec<Range<u32>>],
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
 }
 
 impl <'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
@@ -202,4 +177,20 @@ where
             self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
         }
     }
+
+    fn draw_model_visible(
+        &mut self,
+        model: &'b Model,
+        visible_instances: &[Vec<Range<u32>>],
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for (mesh, ranges) in model.meshes.iter().zip(visible_instances) {
+            let material = &model.materials[mesh.material];
+
+            for instances in ranges {
+                self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group);
+            }
+        }
+    }
 }
@@ -0,0 +1,64 @@
+use cgmath::*;
+
+use crate::camera::Ray;
+use crate::model::{Mesh, Model};
+
+// the closest thing a ray ran into
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    // index into the instances the model was cast against
+    pub instance: usize,
+    // index into the model's meshes
+    pub mesh: usize,
+    // along the ray, in world units for a normalized ray
+    pub distance: f32,
+    pub position: Point3<f32>,
+}
+
+impl Mesh {
+    // Distance to the closest triangle, `ray` has to be in the mesh's own space. The bounds are
+    // checked first so most misses never look at the triangles
+    pub fn raycast(&self, ray: &Ray) -> Option<f32> {
+        ray.intersect_aabb(&self.bounds)?;
+
+        let position = |index: u32| Point3::from(self.positions[index as usize]);
+
+        self.indices.chunks_exact(3)
+            .filter_map(|triangle| ray.intersect_triangle(position(triangle[0]), position(triangle[1]), position(triangle[2])))
+            .reduce(f32::min)
+    }
+}
+
+impl Model {
+    // Casts a world space `ray` against every mesh of the model placed by each of
+    // `instance_transforms`, returning the closest hit
+    pub fn raycast(&self, ray: &Ray, instance_transforms: &[Matrix4<f32>]) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+
+        for (instance, transform) in instance_transforms.iter().enumerate() {
+            // a collapsed instance can't be hit
+            let Some(inverse) = transform.invert() else {
+                continue;
+            };
+            // the direction isn't renormalized, so local distances are world distances
+            let local_ray = ray.transform(&inverse);
+
+            for (mesh_index, mesh) in self.meshes.iter().enumerate() {
+                let Some(distance) = mesh.raycast(&local_ray) else {
+                    continue;
+                };
+
+                if closest.map_or(true, |hit| distance < hit.distance) {
+                    closest = Some(RayHit {
+                        instance,
+                        mesh: mesh_index,
+                        distance,
+                        position: ray.at(distance),
+                    });
+                }
+            }
+        }
+
+        closest
+    }
+}
@@ -0,0 +1,154 @@
+// Per vertex tangents following MikkTSpace, the convention normal maps are baked with (Blender,
+// Substance, xNormal) and the one glTF's TANGENT attribute uses:
+//   - every triangle's tangent (d position / d u) is projected onto the plane of the vertex
+//     normal, normalized and weighted by the angle of the corner
+//   - vertices with the same position, normal and uv share their tangent, even when they were
+//     split into separate vertices
+//   - triangles with mirrored uvs don't get averaged with the rest, a vertex used by both gets
+//     duplicated so each side keeps its own tangent
+//   - the handedness is stored in `tangent[3]`, bitangent = cross(normal, tangent.xyz) * w
+// Texture coordinates are expected with v pointing up (the OBJ/OpenGL convention), which is how
+// the loaders store them
+use std::collections::HashMap;
+
+use cgmath::*;
+
+use crate::model::ModelVertex;
+
+// uv areas below this can't be solved for a tangent
+const DEGENERATE_UV_AREA: f32 = 1e-12;
+
+// vertices with the same data get the same group, like MikkTSpace does before generating
+fn weld_groups(vertices: &[ModelVertex]) -> Vec<usize> {
+    let mut groups = HashMap::new();
+
+    vertices.iter()
+        .enumerate()
+        .map(|(index, vertex)| {
+            let key: Vec<u32> = vertex.position.iter()
+                .chain(vertex.normal.iter())
+                .chain(vertex.tex_coords.iter())
+                .map(|value| value.to_bits())
+                .collect();
+
+            *groups.entry(key).or_insert(index)
+        })
+        .collect()
+}
+
+fn project_onto_plane(vector: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    vector - normal * normal.dot(vector)
+}
+
+// any unit vector perpendicular to `normal`, for vertices no triangle gives a tangent to
+fn perpendicular(normal: Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let tangent = project_onto_plane(axis, normal);
+
+    if tangent.magnitude2() > 0.0 {
+        tangent.normalize()
+    } else {
+        Vector3::unit_x()
+    }
+}
+
+fn safe_normalize(vector: Vector3<f32>) -> Option<Vector3<f32>> {
+    let length = vector.magnitude();
+    if length > 1e-20 && length.is_finite() {
+        Some(vector / length)
+    } else {
+        None
+    }
+}
+
+// Fills in `tangent` for every vertex. Vertices can be appended (and `indices` updated to use
+// them) where mirrored uvs meet, see above
+pub fn generate_tangents(vertices: &mut Vec<ModelVertex>, indices: &mut [u32]) {
+    let groups = weld_groups(vertices);
+    // indexed by the group's vertex and whether the uvs are mirrored
+    let mut accumulated: HashMap<(usize, bool), Vector3<f32>> = HashMap::new();
+    // the orientation of the first triangle that used a vertex, later triangles with the other
+    // orientation get a copy of the vertex
+    let mut orientations: Vec<Option<bool>> = vec![None; vertices.len()];
+    // the vertex every vertex was copied from, itself for the original ones
+    let mut originals: Vec<usize> = (0..vertices.len()).collect();
+    let mut copies: HashMap<u32, u32> = HashMap::new();
+
+    for triangle in indices.chunks_exact_mut(3) {
+        let corners = [triangle[0], triangle[1], triangle[2]].map(|index| vertices[index as usize]);
+        let [p0, p1, p2] = corners.map(|vertex| Vector3::from(vertex.position));
+        let [uv0, uv1, uv2] = corners.map(|vertex| Vector2::from(vertex.tex_coords));
+
+        let delta_pos1 = p1 - p0;
+        let delta_pos2 = p2 - p0;
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        // Solving the following system of equations gives the tangent and bitangent
+        //     delta_pos1 = delta_uv1.x * T + delta_uv1.y * B
+        //     delta_pos2 = delta_uv2.x * T + delta_uv2.y * B
+        // only their directions are used so the division by the uv area is left out, apart
+        // from its sign
+        let uv_area = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if uv_area.abs() < DEGENERATE_UV_AREA {
+            // no tangent to contribute, the corners pick up whatever their vertex ends up with
+            continue;
+        }
+        let orientation = uv_area > 0.0;
+        let face_tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * uv_area.signum();
+
+        for corner in 0..3 {
+            let index = triangle[corner];
+
+            // the other side of a uv mirror seam, this corner needs its own vertex
+            match orientations[index as usize] {
+                None => orientations[index as usize] = Some(orientation),
+                Some(existing) if existing != orientation => {
+                    triangle[corner] = *copies.entry(index).or_insert_with(|| {
+                        vertices.push(vertices[index as usize]);
+                        orientations.push(Some(orientation));
+                        originals.push(index as usize);
+                        (vertices.len() - 1) as u32
+                    });
+                },
+                Some(_) => {},
+            }
+
+            let normal = Vector3::from(corners[corner].normal);
+            let normal = safe_normalize(normal).unwrap_or(Vector3::zero());
+
+            let Some(tangent) = safe_normalize(project_onto_plane(face_tangent, normal)) else {
+                continue;
+            };
+
+            // angle between the two edges leaving this corner, in the normal's plane
+            let position = Vector3::from(corners[corner].position);
+            let edge1 = project_onto_plane(Vector3::from(corners[(corner + 1) % 3].position) - position, normal);
+            let edge2 = project_onto_plane(Vector3::from(corners[(corner + 2) % 3].position) - position, normal);
+            let weight = match (safe_normalize(edge1), safe_normalize(edge2)) {
+                (Some(edge1), Some(edge2)) => edge1.dot(edge2).clamp(-1.0, 1.0).acos(),
+                _ => 0.0,
+            };
+
+            *accumulated.entry((groups[index as usize], orientation)).or_insert(Vector3::zero()) += tangent * weight;
+        }
+    }
+
+    for (index, vertex) in vertices.iter_mut().enumerate() {
+        // copies are welded with the vertex they were made from
+        let group = groups[originals[index]];
+        let normal = safe_normalize(Vector3::from(vertex.normal)).unwrap_or(Vector3::unit_z());
+
+        // vertices only used by degenerate triangles borrow the tangent of their group
+        let (tangent, orientation) = match orientations[index] {
+            Some(orientation) => accumulated.get(&(group, orientation)).map(|tangent| (*tangent, orientation)),
+            None => accumulated.get(&(group, true)).map(|tangent| (*tangent, true))
+                .or_else(|| accumulated.get(&(group, false)).map(|tangent| (*tangent, false))),
+        }.unwrap_or((Vector3::zero(), true));
+
+        let tangent = safe_normalize(project_onto_plane(tangent, normal)).unwrap_or_else(|| perpendicular(normal));
+        let handedness = if orientation { 1.0 } else { -1.0 };
+
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    }
+}
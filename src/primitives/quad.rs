@@ -71,8 +71,6 @@ pub struct QuadUniformBuffer {
 
 impl QuadUniformBuffer {
     pub fn new(device: &wgpu::Device, uniform: &mut QuadUniform, position: [f32; 2]) -> Self {
-        use cgmath::SquareMatrix;
-
         uniform.update_model_from_position(position);
 
         let buffer = QuadUniformBuffer::create_buffer(device, uniform, position);
@@ -148,12 +146,22 @@ impl QuadUniformBuffer {
     }
 }
 
+// `position` is the top left corner and `dimensions` the size, both in logical pixels
 pub struct QuadOptions {
     pub position: QuadPosition,
     pub color: ColorRGBA,
     pub dimensions: QuadDimensions,
 }
 
+impl QuadOptions {
+    // 0-255 rgb with a 0-1 alpha to what the shader expects
+    pub fn vertex_color(&self) -> VertexColor {
+        let (r, g, b, a) = self.color;
+
+        [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a]
+    }
+}
+
 impl Default for QuadOptions {
     fn default() -> Self {
         Self {
@@ -199,71 +207,29 @@ pub struct Quad {
     pub transform: QuadTransform,
 }
 
-struct QuadVertexPositions {
-    pub x: f32,
-    pub y: f32,
-    pub x2: f32,
-    pub y2: f32,
-}
-
-fn calculate_vertex_positions(config: &wgpu::SurfaceConfiguration, quad: &QuadOptions) -> QuadVertexPositions {
-    let QuadOptions { position, dimensions, color: _ } = quad;
-    let (pos_x, pos_y) = (position[0], position[1]);
-    let width = dimensions.0 / config.width as f32;
-    let height = dimensions.1 / config.height as f32;
-
-    // let x = pos_x / width * 2.0 - 1.0;
-    // let y = pos_y / height * 2.0 - 1.0;
-    let x_center = width / 2.0;
-    let y_center = height / 2.0;
-    let x_shifted = pos_x - x_center;
-    let y_shifted = pos_y - y_center;
-    // let x = -(pos_x / width);
-    // let y = -(pos_y / height);
-    let x = x_shifted / (width / 2.0);
-    let y = y_shifted / (height / 2.0);
-
-    let x2 = x + width;
-    let y2 = y + height;
-
-    QuadVertexPositions {
-        x: x2,
-        y: y2,
-        x2: x,
-        y2: y,
-    }
+// The corners of a quad in its own space, in pixels from its top left corner. The model
+// uniform moves it to `options.position`
+pub fn quad_vertices(options: &QuadOptions) -> [QuadVertex; 4] {
+    let (width, height) = options.dimensions;
+    let color = options.vertex_color();
+
+    [
+        QuadVertex { position: [0.0, 0.0, 0.0], color },
+        QuadVertex { position: [width, 0.0, 0.0], color },
+        QuadVertex { position: [0.0, height, 0.0], color },
+        QuadVertex { position: [width, height, 0.0], color },
+    ]
 }
 
 impl Quad {
-    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, options: QuadOptions) -> Self {
+    pub fn new(device: &wgpu::Device, options: QuadOptions) -> Self {
         use wgpu::util::DeviceExt;
-        let QuadOptions { position, color, dimensions: _ } = options;
-        let quad_color: VertexColor = [
-            color.0 as f32 / 255.0,
-            color.1 as f32 / 255.0,
-            color.2 as f32 / 255.0,
-            color.3
-        ];
-        let QuadVertexPositions {
-            x, y, x2, y2
-        } = calculate_vertex_positions(&config, &options);
-
-        let vertices = [
-            QuadVertex { position: [x, y, 0.0], color: quad_color },
-            QuadVertex { position: [x2, y, 0.0], color: quad_color },
-            QuadVertex { position: [x, y2, 0.0], color: quad_color },
-            QuadVertex { position: [x2, y2, 0.0], color: quad_color },
-        ];
+        let [x, y] = options.position;
+        let vertices = quad_vertices(&options);
         let transform = QuadTransform {
             translation: cgmath::Matrix4::from_translation([x, y, 0.0].into()),
             ..Default::default()
         };
-        // let vertices = [
-        //     QuadVertex { position: [(x - half_width) / 100.0, (y - half_height) / 100.0, 0.0], color: quad_color },
-        //     QuadVertex { position: [(x + half_width) / 100.0, (y - half_height) / 100.0, 0.0], color: quad_color },
-        //     QuadVertex { position: [(x - half_width) / 100.0, (y + half_height) / 100.0, 0.0], color: quad_color },
-        //     QuadVertex { position: [(x + half_width) / 100.0, (y + half_height) / 100.0, 0.0], color: quad_color },
-        // ];
         let vertex_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Quad Vertex Buffer"),
@@ -271,14 +237,10 @@ impl Quad {
                 usage: wgpu::BufferUsages::VERTEX,
             }
         );
-        // let indices = [
-        //     0, 1, 2,
-        //     1, 2, 3,
-        // ];
-        println!("QUADVERTEX {:?}", vertices);
+        // counter clockwise once y is flipped to point down
         let indices = [
-            0, 1, 2,
-            2, 1, 3,
+            0, 2, 1,
+            1, 2, 3,
         ];
         let index_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -288,9 +250,7 @@ impl Quad {
             }
         );
         let mut uniform = QuadUniform::new();
-        let uniform_buffer = QuadUniformBuffer::new(device, &mut uniform, position);
-
-        uniform.update_model_from_position(position);
+        let uniform_buffer = QuadUniformBuffer::new(device, &mut uniform, options.position);
 
         Self {
             vertices,
@@ -358,6 +318,7 @@ where
         self.set_vertex_buffer(0, quad.vertex_buffer.slice(..));
         self.set_index_buffer(quad.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
         self.set_bind_group(0, camera_bind_group, &[]);
+        self.set_bind_group(1, &quad.uniform_buffer.bind_group, &[]);
         self.draw_indexed(0..num_indices, 0, instances);
     }
 }
@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+use cgmath::Matrix4;
+
+use crate::camera::Frustum;
+use crate::model::{Aabb, BoundingSphere, Model, ModelHierarchy};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CullingStats {
+    // mesh draws (one per mesh per instance/node) that passed the frustum test
+    pub visible: u32,
+    // mesh draws that were skipped
+    pub culled: u32,
+}
+
+// The instances whose copy of a mesh is inside the frustum, as runs of consecutive instances so
+// every run is still a single instanced draw
+pub fn visible_instance_ranges(
+    frustum: &Frustum,
+    bounds: &Aabb,
+    bounding_sphere: &BoundingSphere,
+    instance_transforms: &[Matrix4<f32>],
+) -> Vec<Range<u32>> {
+    let mut ranges: Vec<Range<u32>> = Vec::new();
+
+    for (index, transform) in instance_transforms.iter().enumerate() {
+        if !frustum.intersects_bounds(bounds, bounding_sphere, transform) {
+            continue;
+        }
+
+        let index = index as u32;
+        match ranges.last_mut() {
+            Some(range) if range.end == index => range.end += 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+
+    ranges
+}
+
+// What is left to draw after culling, indexed the same way as the model's meshes and the
+// hierarchy's nodes
+#[derive(Debug, Default, Clone)]
+pub struct Visibility {
+    pub model_meshes: Vec<Vec<Range<u32>>>,
+    // one flag per entry of the node's `meshes`
+    pub hierarchy_nodes: Vec<Vec<bool>>,
+    pub stats: CullingStats,
+}
+
+impl Visibility {
+    // every mesh of every instance and node, for when culling is turned off
+    pub fn all(model: &Model, instance_count: u32, hierarchy: Option<&ModelHierarchy>) -> Self {
+        let model_meshes = vec![vec![0..instance_count]; model.meshes.len()];
+        let hierarchy_nodes = hierarchy
+            .map(|hierarchy| hierarchy.nodes.iter().map(|node| vec![true; node.meshes.len()]).collect())
+            .unwrap_or_default();
+        let node_meshes = hierarchy
+            .map(|hierarchy| hierarchy.nodes.iter().map(|node| node.meshes.len() as u32).sum())
+            .unwrap_or(0);
+
+        Self {
+            model_meshes,
+            hierarchy_nodes,
+            stats: CullingStats {
+                visible: model.meshes.len() as u32 * instance_count + node_meshes,
+                culled: 0,
+            },
+        }
+    }
+
+    pub fn compute(
+        frustum: &Frustum,
+        model: &Model,
+        instance_transforms: &[Matrix4<f32>],
+        hierarchy: Option<&ModelHierarchy>,
+    ) -> Self {
+        let mut stats = CullingStats::default();
+        let instance_count = instance_transforms.len() as u32;
+
+        let model_meshes = model.meshes.iter()
+            .map(|mesh| {
+                let ranges = visible_instance_ranges(frustum, &mesh.bounds, &mesh.bounding_sphere, instance_transforms);
+                let visible = ranges.iter().map(|range| range.len() as u32).sum::<u32>();
+
+                stats.visible += visible;
+                stats.culled += instance_count - visible;
+
+                ranges
+            })
+            .collect();
+
+        let hierarchy_nodes = hierarchy
+            .map(|hierarchy| {
+                hierarchy.nodes.iter()
+                    .zip(&hierarchy.world_transforms)
+                    .map(|(node, world)| {
+                        node.meshes.iter()
+                            .map(|&mesh_index| {
+                                let mesh = &hierarchy.model.meshes[mesh_index];
+                                let visible = frustum.intersects_bounds(&mesh.bounds, &mesh.bounding_sphere, world);
+
+                                if visible {
+                                    stats.visible += 1;
+                                } else {
+                                    stats.culled += 1;
+                                }
+
+                                visible
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            model_meshes,
+            hierarchy_nodes,
+            stats,
+        }
+    }
+}
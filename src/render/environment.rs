@@ -0,0 +1,214 @@
+use cgmath::prelude::*;
+use wgpu::util::DeviceExt;
+
+use crate::camera::{Camera, Projection};
+use crate::texture::{Cubemap, Texture};
+
+// The cubemap around the scene. The skybox pass draws it behind everything and the model
+// shader reflects it. Until one is set it is a black cube that reflects nothing, and the clear
+// color shows behind the scene instead
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EnvironmentOptions {
+    // draw the cubemap behind the scene, reflections don't depend on it
+    pub skybox: bool,
+    // scales the sky, hdr panoramas are often much brighter or darker than the scene
+    pub intensity: f32,
+    // how strongly surfaces reflect the environment, 0 turns reflections off
+    pub reflection_intensity: f32,
+}
+
+impl Default for EnvironmentOptions {
+    fn default() -> Self {
+        Self {
+            skybox: true,
+            intensity: 1.0,
+            reflection_intensity: 1.0,
+        }
+    }
+}
+
+impl EnvironmentOptions {
+    pub fn with_skybox(mut self, skybox: bool) -> Self {
+        self.skybox = skybox;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_reflection_intensity(mut self, reflection_intensity: f32) -> Self {
+        self.reflection_intensity = reflection_intensity;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnvironmentUniform {
+    // clip space back to world space directions, from the camera's view with its translation
+    // stripped so the sky never gets any closer
+    pub inverse_view_projection: [[f32; 4]; 4],
+    pub intensity: f32,
+    // 0 without a cubemap
+    pub reflection_intensity: f32,
+    // the last mip of the cubemap, the roughest surfaces reflect that one
+    pub max_lod: f32,
+    pub _padding: u32,
+}
+
+impl EnvironmentUniform {
+    pub fn new() -> Self {
+        Self {
+            inverse_view_projection: cgmath::Matrix4::identity().into(),
+            intensity: 0.0,
+            reflection_intensity: 0.0,
+            max_lod: 0.0,
+            _padding: 0,
+        }
+    }
+
+    pub fn update_view_projection(&mut self, camera: &Camera, projection: &Projection) {
+        let mut view = camera.calc_matrix();
+        view.w = cgmath::Vector4::unit_w();
+        let view_projection = projection.calc_matrix() * view;
+
+        self.inverse_view_projection = view_projection.invert()
+            .unwrap_or_else(cgmath::Matrix4::identity)
+            .into();
+    }
+}
+
+impl Default for EnvironmentUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Environment {
+    pub options: EnvironmentOptions,
+    pub uniform: EnvironmentUniform,
+    pub buffer: wgpu::Buffer,
+    pub cubemap: Texture,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    // false while `cubemap` is the black stand in
+    loaded: bool,
+}
+
+impl Environment {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, options: EnvironmentOptions) -> Self {
+        let uniform = EnvironmentUniform::new();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Environment Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let cubemap = Texture::from_cubemap(device, queue, &Cubemap::from_color([0.0, 0.0, 0.0, 1.0]), "Empty Environment");
+        let bind_group_layout = Self::create_bind_group_layout(device);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &buffer, &cubemap);
+
+        Self {
+            options,
+            uniform,
+            buffer,
+            cubemap,
+            bind_group_layout,
+            bind_group,
+            loaded: false,
+        }
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("environment_bind_group_layout"),
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        cubemap: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&cubemap.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&cubemap.sampler),
+                },
+            ],
+            label: Some("environment_bind_group"),
+        })
+    }
+
+    // `cubemap` has to be a cube texture, see `Texture::from_cubemap`
+    pub fn set_cubemap(&mut self, device: &wgpu::Device, cubemap: Texture) {
+        self.cubemap = cubemap;
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.buffer, &self.cubemap);
+        self.loaded = true;
+    }
+
+    // back to no environment at all
+    pub fn clear(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let empty = Texture::from_cubemap(device, queue, &Cubemap::from_color([0.0, 0.0, 0.0, 1.0]), "Empty Environment");
+        self.set_cubemap(device, empty);
+        self.loaded = false;
+    }
+
+    pub fn has_cubemap(&self) -> bool {
+        self.loaded
+    }
+
+    // whether the skybox pass has anything to draw
+    pub fn draws_skybox(&self) -> bool {
+        self.loaded && self.options.skybox
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, projection: &Projection) {
+        self.uniform.update_view_projection(camera, projection);
+        self.uniform.intensity = self.options.intensity.max(0.0);
+        self.uniform.reflection_intensity = if self.loaded { self.options.reflection_intensity.max(0.0) } else { 0.0 };
+        self.uniform.max_lod = (self.cubemap.texture.mip_level_count() - 1) as f32;
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
@@ -0,0 +1,834 @@
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::*;
+
+// A render graph: nodes declare the attachments they read and write, the graph works out the
+// order they run in, allocates the textures they render into and resolves multisampled
+// attachments. `W` is whatever the nodes draw from, the `Renderer` for the app's own passes
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AttachmentId(usize);
+
+impl AttachmentId {
+    // the view handed to `execute`, the window surface or the headless target
+    pub const TARGET: AttachmentId = AttachmentId(0);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AttachmentSize {
+    // the size of the target
+    Target,
+    // a fraction (or multiple) of the target, for half resolution effects
+    Scaled(f32),
+    Fixed(u32, u32),
+}
+
+impl AttachmentSize {
+    pub fn resolve(&self, target: (u32, u32)) -> (u32, u32) {
+        match *self {
+            AttachmentSize::Target => target,
+            AttachmentSize::Scaled(scale) => (
+                ((target.0 as f32 * scale).round() as u32).max(1),
+                ((target.1 as f32 * scale).round() as u32).max(1),
+            ),
+            AttachmentSize::Fixed(width, height) => (width.max(1), height.max(1)),
+        }
+    }
+}
+
+// A texture owned by the graph. It only lives for the frame, so textures whose uses don't
+// overlap can end up sharing the same memory
+#[derive(Debug, Clone)]
+pub struct AttachmentDescriptor {
+    pub label: String,
+    pub format: wgpu::TextureFormat,
+    pub size: AttachmentSize,
+    pub sample_count: u32,
+    // where a multisampled attachment is resolved to once the last node has written it. When
+    // `None` and a node reads the attachment, the graph allocates a single sampled copy
+    pub resolve_target: Option<AttachmentId>,
+}
+
+impl AttachmentDescriptor {
+    pub fn new(label: &str, format: wgpu::TextureFormat) -> Self {
+        Self {
+            label: label.to_string(),
+            format,
+            size: AttachmentSize::Target,
+            sample_count: 1,
+            resolve_target: None,
+        }
+    }
+
+    pub fn with_size(mut self, size: AttachmentSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count.max(1);
+        self
+    }
+
+    pub fn with_resolve_target(mut self, resolve_target: AttachmentId) -> Self {
+        self.resolve_target = Some(resolve_target);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Attachment {
+    Target,
+    Transient(AttachmentDescriptor),
+    // something outside the graph (the shadow maps, the id buffer), only used to order nodes
+    External(String),
+}
+
+impl Attachment {
+    fn label(&self) -> &str {
+        match self {
+            Attachment::Target => "target",
+            Attachment::Transient(descriptor) => &descriptor.label,
+            Attachment::External(label) => label,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttachmentLoad {
+    // cleared at the start of the node, to the context's clear color for color attachments
+    // and 1.0 for depth
+    Clear,
+    // keep what earlier nodes drew
+    Load,
+}
+
+// What a node declares in `RenderNode::setup`
+#[derive(Debug, Default, Clone)]
+pub struct NodeBuilder {
+    reads: Vec<AttachmentId>,
+    writes: Vec<AttachmentId>,
+    colors: Vec<(AttachmentId, AttachmentLoad)>,
+    depth: Option<(AttachmentId, AttachmentLoad)>,
+}
+
+impl NodeBuilder {
+    // sampled by the node, or for external attachments, anything that has to happen first
+    pub fn read(&mut self, attachment: AttachmentId) -> &mut Self {
+        self.reads.push(attachment);
+        self
+    }
+
+    // written outside of `NodeContext::begin_render_pass`, by a pass the node records itself
+    pub fn write(&mut self, attachment: AttachmentId) -> &mut Self {
+        self.writes.push(attachment);
+        self
+    }
+
+    // color attachments of the node's render pass, in @location order
+    pub fn color(&mut self, attachment: AttachmentId, load: AttachmentLoad) -> &mut Self {
+        self.colors.push((attachment, load));
+        self
+    }
+
+    pub fn depth(&mut self, attachment: AttachmentId, load: AttachmentLoad) -> &mut Self {
+        self.depth = Some((attachment, load));
+        self
+    }
+
+    fn touches(&self, attachment: AttachmentId) -> bool {
+        self.reads.contains(&attachment)
+            || self.writes.contains(&attachment)
+            || self.colors.iter().any(|(id, _)| *id == attachment)
+            || self.depth.map(|(id, _)| id) == Some(attachment)
+    }
+}
+
+pub trait RenderNode<W> {
+    fn name(&self) -> &str;
+
+    // declare the attachments the node uses, called by `RenderGraph::compile`
+    fn setup(&self, builder: &mut NodeBuilder);
+
+    // called whenever the graph's textures are (re)allocated, to rebuild anything that
+    // points at them, like bind groups sampling an attachment
+    fn prepare(&mut self, _device: &wgpu::Device, _resources: &GraphResources) {}
+
+    fn run(&self, world: &W, context: &mut NodeContext);
+}
+
+// where an attachment lives once the graph is compiled
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttachmentSlot {
+    Target,
+    Texture(usize),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColorTarget {
+    pub attachment: AttachmentId,
+    pub load: AttachmentLoad,
+    pub view: AttachmentSlot,
+    pub resolve_target: Option<AttachmentSlot>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthTarget {
+    pub attachment: AttachmentId,
+    pub load: AttachmentLoad,
+    pub view: AttachmentSlot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledNode {
+    // index in the order nodes were added
+    pub node: usize,
+    pub colors: Vec<ColorTarget>,
+    pub depth: Option<DepthTarget>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureAllocation {
+    pub label: String,
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub sample_count: u32,
+}
+
+// The nodes in the order they run and the textures backing the attachments. Everything
+// `compile` decides before it touches the device
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub nodes: Vec<ScheduledNode>,
+    pub textures: Vec<TextureAllocation>,
+    slots: Vec<Option<AttachmentSlot>>,
+    // what nodes reading an attachment sample, the resolved copy for multisampled ones
+    read_slots: Vec<Option<AttachmentSlot>>,
+}
+
+impl Schedule {
+    pub fn order(&self) -> Vec<usize> {
+        self.nodes.iter().map(|node| node.node).collect()
+    }
+
+    pub fn slot(&self, attachment: AttachmentId) -> Option<AttachmentSlot> {
+        self.slots.get(attachment.0).copied().flatten()
+    }
+
+    pub fn read_slot(&self, attachment: AttachmentId) -> Option<AttachmentSlot> {
+        self.read_slots.get(attachment.0).copied().flatten()
+    }
+}
+
+// The textures of a compiled graph
+pub struct GraphResources {
+    pub textures: Vec<wgpu::Texture>,
+    pub views: Vec<wgpu::TextureView>,
+    slots: Vec<Option<AttachmentSlot>>,
+    read_slots: Vec<Option<AttachmentSlot>>,
+}
+
+impl GraphResources {
+    fn new(device: &wgpu::Device, schedule: &Schedule) -> Self {
+        let textures = schedule.textures.iter().map(|allocation| {
+            // multisampled textures are only ever rendered to and resolved
+            let usage = if allocation.sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            };
+
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&allocation.label),
+                size: wgpu::Extent3d {
+                    width: allocation.width,
+                    height: allocation.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: allocation.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: allocation.format,
+                usage,
+                view_formats: &[],
+            })
+        }).collect::<Vec<_>>();
+        let views = textures.iter()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect();
+
+        Self {
+            textures,
+            views,
+            slots: schedule.slots.clone(),
+            read_slots: schedule.read_slots.clone(),
+        }
+    }
+
+    // the view to sample `attachment` through, `None` for the target and external attachments
+    pub fn view(&self, attachment: AttachmentId) -> Option<&wgpu::TextureView> {
+        match self.read_slots.get(attachment.0).copied().flatten() {
+            Some(AttachmentSlot::Texture(index)) => self.views.get(index),
+            _ => None,
+        }
+    }
+
+    // the view nodes render into, multisampled if the attachment is
+    pub fn attachment_view(&self, attachment: AttachmentId) -> Option<&wgpu::TextureView> {
+        match self.slots.get(attachment.0).copied().flatten() {
+            Some(AttachmentSlot::Texture(index)) => self.views.get(index),
+            _ => None,
+        }
+    }
+}
+
+pub struct NodeContext<'a> {
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub resources: &'a GraphResources,
+    // used by color attachments declared with `AttachmentLoad::Clear`
+    pub clear_color: wgpu::Color,
+    target: &'a wgpu::TextureView,
+    node: &'a ScheduledNode,
+    began_render_pass: bool,
+}
+
+impl<'a> NodeContext<'a> {
+    fn slot_view(&self, slot: AttachmentSlot) -> &'a wgpu::TextureView {
+        match slot {
+            AttachmentSlot::Target => self.target,
+            AttachmentSlot::Texture(index) => &self.resources.views[index],
+        }
+    }
+
+    pub fn view(&self, attachment: AttachmentId) -> Option<&'a wgpu::TextureView> {
+        if attachment == AttachmentId::TARGET {
+            return Some(self.target);
+        }
+
+        self.resources.view(attachment)
+    }
+
+    // a render pass with the color and depth attachments the node declared
+    pub fn begin_render_pass(&mut self, label: &str) -> wgpu::RenderPass<'_> {
+        self.began_render_pass = true;
+        let clear_color = self.clear_color;
+        let color_attachments = self.node.colors.iter().map(|color| {
+            let load = match color.load {
+                AttachmentLoad::Clear => wgpu::LoadOp::Clear(clear_color),
+                AttachmentLoad::Load => wgpu::LoadOp::Load,
+            };
+
+            Some(wgpu::RenderPassColorAttachment {
+                view: self.slot_view(color.view),
+                resolve_target: color.resolve_target.map(|slot| self.slot_view(slot)),
+                ops: wgpu::Operations { load, store: true },
+            })
+        }).collect::<Vec<_>>();
+        let depth_stencil_attachment = self.node.depth.map(|depth| {
+            let load = match depth.load {
+                AttachmentLoad::Clear => wgpu::LoadOp::Clear(1.0),
+                AttachmentLoad::Load => wgpu::LoadOp::Load,
+            };
+
+            wgpu::RenderPassDepthStencilAttachment {
+                view: self.slot_view(depth.view),
+                depth_ops: Some(wgpu::Operations { load, store: true }),
+                stencil_ops: None,
+            }
+        });
+
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
+        })
+    }
+}
+
+pub struct RenderGraph<W> {
+    attachments: Vec<Attachment>,
+    nodes: Vec<Box<dyn RenderNode<W>>>,
+    target_size: (u32, u32),
+    target_format: wgpu::TextureFormat,
+    schedule: Option<Schedule>,
+    resources: Option<GraphResources>,
+}
+
+impl<W> RenderGraph<W> {
+    pub fn new(width: u32, height: u32, target_format: wgpu::TextureFormat) -> Self {
+        Self {
+            attachments: vec![Attachment::Target],
+            nodes: Vec::new(),
+            target_size: (width.max(1), height.max(1)),
+            target_format,
+            schedule: None,
+            resources: None,
+        }
+    }
+
+    pub fn add_attachment(&mut self, descriptor: AttachmentDescriptor) -> AttachmentId {
+        self.attachments.push(Attachment::Transient(descriptor));
+        AttachmentId(self.attachments.len() - 1)
+    }
+
+    pub fn add_external(&mut self, label: &str) -> AttachmentId {
+        self.attachments.push(Attachment::External(label.to_string()));
+        AttachmentId(self.attachments.len() - 1)
+    }
+
+    pub fn attachment(&self, label: &str) -> Option<AttachmentId> {
+        self.attachments.iter()
+            .position(|attachment| attachment.label() == label)
+            .map(AttachmentId)
+    }
+
+    pub fn descriptor(&self, attachment: AttachmentId) -> Option<&AttachmentDescriptor> {
+        match self.attachments.get(attachment.0) {
+            Some(Attachment::Transient(descriptor)) => Some(descriptor),
+            _ => None,
+        }
+    }
+
+    // nodes only run once the graph is compiled again
+    pub fn add_node(&mut self, node: Box<dyn RenderNode<W>>) -> usize {
+        self.nodes.push(node);
+        self.schedule = None;
+        self.resources = None;
+        self.nodes.len() - 1
+    }
+
+    // Moves the nodes and attachments `other` has on top of the ones this graph was built with
+    // over to this graph, for a graph built again from the same function. Their attachments
+    // keep their ids, and both graphs have to be compiled again
+    pub fn take_added(&mut self, other: &mut RenderGraph<W>) -> Result<()> {
+        let attachments = self.attachments.len();
+        let nodes = self.nodes.len();
+        let same_attachments = other.attachments.len() >= attachments
+            && self.attachments.iter().zip(&other.attachments).all(|(a, b)| a.label() == b.label());
+        let same_nodes = other.nodes.len() >= nodes
+            && self.nodes.iter().zip(&other.nodes).all(|(a, b)| a.name() == b.name());
+        if !same_attachments || !same_nodes {
+            bail!("the graphs weren't built from the same attachments and nodes");
+        }
+
+        self.attachments.extend(other.attachments.drain(attachments..));
+        self.nodes.extend(other.nodes.drain(nodes..));
+        for graph in [&mut *self, other] {
+            graph.schedule = None;
+            graph.resources = None;
+        }
+
+        Ok(())
+    }
+
+    pub fn node(&self, index: usize) -> Option<&dyn RenderNode<W>> {
+        self.nodes.get(index).map(|node| node.as_ref())
+    }
+
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name() == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn target_size(&self) -> (u32, u32) {
+        self.target_size
+    }
+
+    pub fn target_format(&self) -> wgpu::TextureFormat {
+        self.target_format
+    }
+
+    pub fn schedule(&self) -> Option<&Schedule> {
+        self.schedule.as_ref()
+    }
+
+    pub fn resources(&self) -> Option<&GraphResources> {
+        self.resources.as_ref()
+    }
+
+    // order the nodes and allocate their textures
+    pub fn compile(&mut self, device: &wgpu::Device) -> Result<()> {
+        let schedule = self.build_schedule()?;
+        self.allocate(device, schedule);
+
+        Ok(())
+    }
+
+    // textures are sized from the target, so they are reallocated along with it
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> Result<()> {
+        self.target_size = (width.max(1), height.max(1));
+        self.compile(device)
+    }
+
+    fn allocate(&mut self, device: &wgpu::Device, schedule: Schedule) {
+        let resources = GraphResources::new(device, &schedule);
+
+        for node in self.nodes.iter_mut() {
+            node.prepare(device, &resources);
+        }
+
+        self.schedule = Some(schedule);
+        self.resources = Some(resources);
+    }
+
+    // Records every node into `encoder`, `target` is what `AttachmentId::TARGET` renders to
+    pub fn execute(&self, world: &W, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let (Some(schedule), Some(resources)) = (&self.schedule, &self.resources) else {
+            log::warn!("render graph wasn't compiled, nothing was drawn");
+            return;
+        };
+
+        for scheduled in &schedule.nodes {
+            let mut context = NodeContext {
+                encoder: &mut *encoder,
+                resources,
+                clear_color: wgpu::Color::BLACK,
+                target,
+                node: scheduled,
+                began_render_pass: false,
+            };
+
+            self.nodes[scheduled.node].run(world, &mut context);
+
+            // multisampled attachments are only resolved by a render pass, so one is still needed
+            // when the node had nothing to draw
+            let resolves = scheduled.colors.iter().any(|color| color.resolve_target.is_some());
+            if resolves && !context.began_render_pass {
+                context.begin_render_pass("Resolve Pass");
+            }
+        }
+    }
+
+    // everything `compile` does that doesn't need a device
+    pub fn build_schedule(&self) -> Result<Schedule> {
+        let builders = self.nodes.iter().map(|node| {
+            let mut builder = NodeBuilder::default();
+            node.setup(&mut builder);
+            builder
+        }).collect::<Vec<_>>();
+
+        self.validate(&builders)?;
+        let order = self.order(&builders)?;
+
+        // position of every node in `order`
+        let mut positions = vec![0; builders.len()];
+        for (position, node) in order.iter().enumerate() {
+            positions[*node] = position;
+        }
+
+        let attachment_count = self.attachments.len();
+        let mut slots = vec![None; attachment_count];
+        let mut read_slots = vec![None; attachment_count];
+        slots[AttachmentId::TARGET.0] = Some(AttachmentSlot::Target);
+
+        // (attachment, needs a resolve copy) -> first and last position using it
+        let mut lifetimes: Vec<(usize, bool, usize, usize)> = Vec::new();
+        let mut resolving_node = vec![None; attachment_count];
+
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            let Attachment::Transient(descriptor) = attachment else {
+                continue;
+            };
+            let id = AttachmentId(index);
+
+            let writers = builders.iter().enumerate()
+                .filter(|(_, builder)| {
+                    builder.writes.contains(&id)
+                        || builder.colors.iter().any(|(color, _)| *color == id)
+                        || builder.depth.map(|(depth, _)| depth) == Some(id)
+                })
+                .map(|(node, _)| positions[node])
+                .collect::<Vec<_>>();
+            let readers = builders.iter().enumerate()
+                .filter(|(_, builder)| builder.reads.contains(&id))
+                .map(|(node, _)| positions[node])
+                .collect::<Vec<_>>();
+            // resolving another attachment into this one counts as writing it
+            let resolves = self.resolved_into(id).into_iter()
+                .flat_map(|source| builders.iter().enumerate().filter(move |(_, builder)| builder.colors.iter().any(|(color, _)| *color == source)))
+                .map(|(node, _)| positions[node])
+                .collect::<Vec<_>>();
+
+            let uses = writers.iter().chain(&readers).chain(&resolves).copied().collect::<Vec<_>>();
+            if uses.is_empty() {
+                continue;
+            }
+
+            let multisampled = descriptor.sample_count > 1;
+            if multisampled {
+                let last_writer = builders.iter().enumerate()
+                    .filter(|(_, builder)| builder.colors.iter().any(|(color, _)| *color == id))
+                    .map(|(node, _)| node)
+                    .max_by_key(|node| positions[*node]);
+                resolving_node[index] = last_writer;
+
+                let writes_end = writers.iter().copied().max().unwrap_or(0);
+                let writes_start = writers.iter().copied().min().unwrap_or(0);
+                lifetimes.push((index, false, writes_start, writes_end));
+
+                if descriptor.resolve_target.is_none() && !readers.is_empty() {
+                    let reads_end = readers.iter().copied().max().unwrap_or(writes_end);
+                    lifetimes.push((index, true, writes_end, reads_end.max(writes_end)));
+                }
+            } else {
+                let start = uses.iter().copied().min().unwrap_or(0);
+                let end = uses.iter().copied().max().unwrap_or(0);
+                lifetimes.push((index, false, start, end));
+            }
+        }
+
+        // textures whose lifetimes don't overlap are shared, the first to start gets the texture
+        lifetimes.sort_by_key(|(_, _, start, _)| *start);
+        let mut textures: Vec<TextureAllocation> = Vec::new();
+        // position of the last use of each texture
+        let mut texture_ends: Vec<usize> = Vec::new();
+        for (index, resolve_copy, start, end) in lifetimes {
+            let Attachment::Transient(descriptor) = &self.attachments[index] else {
+                continue;
+            };
+            let (width, height) = descriptor.size.resolve(self.target_size);
+            let allocation = TextureAllocation {
+                label: if resolve_copy { format!("{} resolve", descriptor.label) } else { descriptor.label.clone() },
+                format: descriptor.format,
+                width,
+                height,
+                sample_count: if resolve_copy { 1 } else { descriptor.sample_count },
+            };
+
+            let reusable = textures.iter().zip(&texture_ends).position(|(texture, texture_end)| {
+                texture.format == allocation.format
+                    && texture.width == allocation.width
+                    && texture.height == allocation.height
+                    && texture.sample_count == allocation.sample_count
+                    && *texture_end < start
+            });
+            let texture = match reusable {
+                Some(texture) => {
+                    texture_ends[texture] = end;
+                    texture
+                },
+                None => {
+                    textures.push(allocation);
+                    texture_ends.push(end);
+                    textures.len() - 1
+                },
+            };
+
+            if resolve_copy {
+                read_slots[index] = Some(AttachmentSlot::Texture(texture));
+            } else {
+                slots[index] = Some(AttachmentSlot::Texture(texture));
+            }
+        }
+
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            let Attachment::Transient(descriptor) = attachment else {
+                continue;
+            };
+
+            if read_slots[index].is_none() {
+                read_slots[index] = match descriptor.resolve_target {
+                    Some(target) if descriptor.sample_count > 1 => slots[target.0],
+                    _ if descriptor.sample_count > 1 => None,
+                    _ => slots[index],
+                };
+            }
+        }
+
+        let nodes = order.iter().map(|node| {
+            let builder = &builders[*node];
+            let colors = builder.colors.iter().map(|(attachment, load)| {
+                let resolve_target = match &self.attachments[attachment.0] {
+                    Attachment::Transient(descriptor) if resolving_node[attachment.0] == Some(*node) => {
+                        match descriptor.resolve_target {
+                            Some(target) => slots[target.0],
+                            None => read_slots[attachment.0],
+                        }
+                    },
+                    _ => None,
+                };
+
+                ColorTarget {
+                    attachment: *attachment,
+                    load: *load,
+                    view: slots[attachment.0].expect("color attachments are allocated"),
+                    resolve_target,
+                }
+            }).collect();
+            let depth = builder.depth.map(|(attachment, load)| DepthTarget {
+                attachment,
+                load,
+                view: slots[attachment.0].expect("depth attachments are allocated"),
+            });
+
+            ScheduledNode { node: *node, colors, depth }
+        }).collect();
+
+        Ok(Schedule { nodes, textures, slots, read_slots })
+    }
+
+    // the attachments resolved into `attachment`
+    fn resolved_into(&self, attachment: AttachmentId) -> Vec<AttachmentId> {
+        self.attachments.iter().enumerate()
+            .filter(|(_, source)| matches!(source, Attachment::Transient(descriptor) if descriptor.sample_count > 1 && descriptor.resolve_target == Some(attachment)))
+            .map(|(index, _)| AttachmentId(index))
+            .collect()
+    }
+
+    fn sample_count(&self, attachment: AttachmentId) -> u32 {
+        match &self.attachments[attachment.0] {
+            Attachment::Transient(descriptor) => descriptor.sample_count,
+            _ => 1,
+        }
+    }
+
+    fn validate(&self, builders: &[NodeBuilder]) -> Result<()> {
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            let Attachment::Transient(descriptor) = attachment else {
+                continue;
+            };
+
+            if let Some(target) = descriptor.resolve_target {
+                let target_format = match self.attachments.get(target.0) {
+                    Some(Attachment::Target) => self.target_format,
+                    Some(Attachment::Transient(target)) if target.sample_count == 1 => target.format,
+                    _ => bail!("`{}` can only be resolved into the target or a single sampled attachment", descriptor.label),
+                };
+
+                if descriptor.sample_count > 1 && target_format != descriptor.format {
+                    bail!("`{}` is {:?} but its resolve target is {:?}", descriptor.label, descriptor.format, target_format);
+                }
+            }
+
+            // a transient texture starts out with whatever the last user of its memory left
+            let id = AttachmentId(index);
+            let creates = builders.iter().any(|builder| {
+                builder.writes.contains(&id)
+                    || builder.colors.contains(&(id, AttachmentLoad::Clear))
+                    || builder.depth == Some((id, AttachmentLoad::Clear))
+            }) || !self.resolved_into(id).is_empty();
+            if !creates {
+                if let Some(node) = builders.iter().position(|builder| builder.touches(id)) {
+                    bail!("`{}` is used by `{}` but no node clears or writes it", descriptor.label, self.nodes[node].name());
+                }
+            }
+        }
+
+        for (node, builder) in builders.iter().enumerate() {
+            let name = self.nodes[node].name();
+
+            for attachment in builder.reads.iter().chain(&builder.writes)
+                .chain(builder.colors.iter().map(|(id, _)| id))
+                .chain(builder.depth.iter().map(|(id, _)| id))
+            {
+                if attachment.0 >= self.attachments.len() {
+                    bail!("`{}` uses an attachment that isn't part of the graph", name);
+                }
+            }
+
+            if builder.reads.contains(&AttachmentId::TARGET) {
+                bail!("`{}` reads the target, which can only be rendered to", name);
+            }
+
+            let rendered = builder.colors.iter().map(|(id, _)| id).chain(builder.depth.iter().map(|(id, _)| id));
+            for attachment in rendered.clone() {
+                if matches!(self.attachments[attachment.0], Attachment::External(_)) {
+                    bail!("`{}` renders to `{}`, which is outside of the graph", name, self.attachments[attachment.0].label());
+                }
+            }
+
+            let mut sample_counts = rendered.map(|id| self.sample_count(*id)).collect::<Vec<_>>();
+            sample_counts.dedup();
+            if sample_counts.len() > 1 {
+                bail!("the attachments of `{}` have different sample counts", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Per attachment: nodes clearing it run first, then the ones drawing on top in the order
+    // they were added, then the ones reading it. Otherwise nodes keep the order they were added in
+    fn order(&self, builders: &[NodeBuilder]) -> Result<Vec<usize>> {
+        let mut edges: HashSet<(usize, usize)> = HashSet::new();
+
+        for (index, _) in self.attachments.iter().enumerate() {
+            let id = AttachmentId(index);
+            let resolved_from = self.resolved_into(id);
+
+            let mut creators = Vec::new();
+            let mut modifiers = Vec::new();
+            let mut readers = Vec::new();
+            for (node, builder) in builders.iter().enumerate() {
+                let load = builder.colors.iter().find(|(color, _)| *color == id).map(|(_, load)| *load)
+                    .or(builder.depth.filter(|(depth, _)| *depth == id).map(|(_, load)| load));
+                let resolves = resolved_from.iter().any(|source| builder.colors.iter().any(|(color, _)| color == source));
+
+                if builder.writes.contains(&id) || load == Some(AttachmentLoad::Clear) || resolves {
+                    creators.push(node);
+                } else if load == Some(AttachmentLoad::Load) {
+                    modifiers.push(node);
+                }
+
+                if builder.reads.contains(&id) {
+                    readers.push(node);
+                }
+            }
+
+            for pair in creators.windows(2).chain(modifiers.windows(2)) {
+                edges.insert((pair[0], pair[1]));
+            }
+            for creator in &creators {
+                for modifier in &modifiers {
+                    edges.insert((*creator, *modifier));
+                }
+            }
+            for writer in creators.iter().chain(&modifiers) {
+                for reader in &readers {
+                    if writer != reader {
+                        edges.insert((*writer, *reader));
+                    }
+                }
+            }
+        }
+
+        let mut incoming = vec![0; builders.len()];
+        for (_, to) in &edges {
+            incoming[*to] += 1;
+        }
+
+        // the ready node added first goes next
+        let mut ready = (0..builders.len()).filter(|node| incoming[*node] == 0).collect::<BTreeSet<_>>();
+        let mut order = Vec::with_capacity(builders.len());
+        while let Some(node) = ready.pop_first() {
+            order.push(node);
+
+            for (from, to) in &edges {
+                if *from == node {
+                    incoming[*to] -= 1;
+                    if incoming[*to] == 0 {
+                        ready.insert(*to);
+                    }
+                }
+            }
+        }
+
+        if order.len() < builders.len() {
+            let stuck = (0..builders.len())
+                .filter(|node| !order.contains(node))
+                .map(|node| self.nodes[node].name().to_string())
+                .collect::<Vec<_>>();
+            bail!("render graph has a cycle between {}", stuck.join(", "));
+        }
+
+        Ok(order)
+    }
+}
@@ -1,3 +1,21 @@
+pub mod culling;
+pub mod environment;
+pub mod fullscreen;
+pub mod graph;
+pub mod multisample;
+pub mod picking;
+pub mod postprocess;
+pub mod tonemap;
+
+pub use culling::{CullingStats, Visibility};
+pub use environment::{Environment, EnvironmentOptions};
+pub use graph::{AttachmentDescriptor, AttachmentId, AttachmentLoad, AttachmentSize, NodeBuilder, NodeContext, RenderGraph, RenderNode};
+pub use multisample::{choose_sample_count, supported_sample_counts};
+pub use picking::{IdBuffer, IdBufferHit, IdReadback};
+pub use postprocess::{Bloom, ChromaticAberration, ColorGrading, Fxaa, PostProcessOptions, PostProcessing, Vignette};
+pub use tonemap::{AutoExposure, TonemapOptions, Tonemapper, Tonemapping, HDR_FORMAT, LDR_FORMAT};
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
@@ -6,6 +24,8 @@ pub fn create_render_pipeline(
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
     blend_state: Option<wgpu::BlendState>,
+    // has to match the attachments of the pass the pipeline is used in
+    sample_count: u32,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
     let default_blend_state = wgpu::BlendState {
@@ -52,6 +72,56 @@ pub fn create_render_pipeline(
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// depth only pipeline for rendering shadow maps, there is no fragment stage or color target
+pub fn create_shadow_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    depth_format: wgpu::TextureFormat,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Shadow Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            // slope scaled bias for surfaces at grazing angles, the constant part
+            // is configurable through ShadowOptions::depth_bias instead
+            bias: wgpu::DepthBiasState {
+                constant: 0,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
         multisample: wgpu::MultisampleState {
             count: 1,
             mask: !0,
@@ -0,0 +1,46 @@
+// the sample counts MSAA can be configured with
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+// The sample counts every one of `formats` can be rendered and resolved with, 1 is always
+// included. Without TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES enabled on the device only the
+// counts guaranteed for every adapter can be used (4x for most formats)
+pub fn supported_sample_counts(
+    adapter: &wgpu::Adapter,
+    device_features: wgpu::Features,
+    formats: &[wgpu::TextureFormat],
+) -> Vec<u32> {
+    let adapter_specific = device_features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+    sample_counts_supported_by(formats, |format| {
+        if adapter_specific {
+            adapter.get_texture_format_features(format)
+        } else {
+            format.guaranteed_format_features(device_features)
+        }
+    })
+}
+
+pub fn sample_counts_supported_by(
+    formats: &[wgpu::TextureFormat],
+    format_features: impl Fn(wgpu::TextureFormat) -> wgpu::TextureFormatFeatures,
+) -> Vec<u32> {
+    SAMPLE_COUNTS.into_iter().filter(|count| {
+        *count == 1 || formats.iter().all(|format| {
+            let flags = format_features(*format).flags;
+            // depth is never resolved, only color
+            let resolvable = format.has_depth_aspect()
+                || flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+
+            flags.sample_count_supported(*count) && resolvable
+        })
+    }).collect()
+}
+
+// the highest supported count that isn't above `requested`
+pub fn choose_sample_count(supported: &[u32], requested: u32) -> u32 {
+    supported.iter()
+        .copied()
+        .filter(|count| *count <= requested)
+        .max()
+        .unwrap_or(1)
+}
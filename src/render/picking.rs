@@ -0,0 +1,368 @@
+use std::ops::Range;
+
+use anyhow::*;
+
+use crate::instance::InstanceRaw;
+use crate::model::{Model, ModelVertex};
+use crate::primitives::Vertex;
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshIdUniform {
+    pub index: u32,
+    // uniforms are bound in blocks of 16 bytes
+    pub _padding: [u32; 3],
+}
+
+// what the id buffer has under a pixel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IdBufferHit {
+    pub instance: usize,
+    pub mesh: usize,
+}
+
+// Picking on the gpu. The scene is rendered a second time into an integer texture holding the
+// instance and mesh index of each pixel, which is then read back for the pixel under the
+// cursor. Unlike `Model::raycast` it is exact for anything the shaders move around, but the
+// answer takes a frame or so to come back
+pub struct IdBuffer {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub depth_texture: Texture,
+    pub pipeline: wgpu::RenderPipeline,
+    // one MeshIdUniform per mesh, selected with a dynamic offset
+    pub mesh_buffer: wgpu::Buffer,
+    pub mesh_bind_group: wgpu::BindGroup,
+    mesh_stride: u32,
+    // set once the meshes past MAX_MESHES have been reported
+    warned_mesh_limit: std::cell::Cell<bool>,
+}
+
+impl IdBuffer {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+    // meshes past this aren't drawn into the id buffer and can't be picked
+    pub const MAX_MESHES: u32 = 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let (texture, view) = Self::create_texture(device, config);
+        let depth_texture = Texture::create_depth_texture(device, config, "Id Buffer Depth Texture");
+
+        let mesh_stride = (std::mem::size_of::<MeshIdUniform>() as u32)
+            .max(device.limits().min_uniform_buffer_offset_alignment);
+        let mesh_buffer = Self::create_mesh_buffer(device, mesh_stride);
+        let mesh_bind_group_layout = Self::create_bind_group_layout(device);
+        let mesh_bind_group = Self::create_bind_group(device, &mesh_bind_group_layout, &mesh_buffer);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Id Buffer Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &mesh_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &layout);
+
+        Self {
+            texture,
+            view,
+            depth_texture,
+            pipeline,
+            mesh_buffer,
+            mesh_bind_group,
+            mesh_stride,
+            warned_mesh_limit: std::cell::Cell::new(false),
+        }
+    }
+
+    fn create_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Id Buffer"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (texture, view)
+    }
+
+    // the ids never change, so every slot is written once up front
+    fn create_mesh_buffer(device: &wgpu::Device, mesh_stride: u32) -> wgpu::Buffer {
+        use wgpu::util::DeviceExt;
+
+        let mut contents = vec![0u8; (mesh_stride * Self::MAX_MESHES) as usize];
+        for index in 0..Self::MAX_MESHES {
+            let offset = (index * mesh_stride) as usize;
+            let uniform = MeshIdUniform {
+                index,
+                _padding: [0; 3],
+            };
+            contents[offset..offset + std::mem::size_of::<MeshIdUniform>()]
+                .copy_from_slice(bytemuck::bytes_of(&uniform));
+        }
+
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Id Buffer Mesh Buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        })
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<MeshIdUniform>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("id_buffer_mesh_bind_group_layout"),
+        })
+    }
+
+    pub fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<MeshIdUniform>() as u64),
+                    }),
+                },
+            ],
+            label: Some("id_buffer_mesh_bind_group"),
+        })
+    }
+
+    // integer targets can't be blended, so this can't go through `create_render_pipeline`
+    fn create_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Id Buffer Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/picking.wgsl").into()),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Id Buffer Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[ModelVertex::layout(), InstanceRaw::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: Self::FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.texture, self.view) = Self::create_texture(device, config);
+        self.depth_texture = Texture::create_depth_texture(device, config, "Id Buffer Depth Texture");
+    }
+
+    // Draws the ids of `model`, only the instance ranges in `visible_instances` (one list per
+    // mesh, as in `render::Visibility`)
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        model: &Model,
+        instance_buffer: &wgpu::Buffer,
+        visible_instances: &[Vec<Range<u32>>],
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        let mut id_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Id Buffer Pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        id_pass.set_pipeline(&self.pipeline);
+        id_pass.set_bind_group(0, camera_bind_group, &[]);
+        id_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+
+        if model.meshes.len() > Self::MAX_MESHES as usize && !self.warned_mesh_limit.replace(true) {
+            log::warn!(
+                "The model has {} meshes, only the first {} can be picked through the id buffer",
+                model.meshes.len(),
+                Self::MAX_MESHES,
+            );
+        }
+
+        let meshes = model.meshes.iter().zip(visible_instances).take(Self::MAX_MESHES as usize);
+        for (index, (mesh, ranges)) in meshes.enumerate() {
+            id_pass.set_bind_group(1, &self.mesh_bind_group, &[index as u32 * self.mesh_stride]);
+            id_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            id_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+            for instances in ranges {
+                id_pass.draw_indexed(0..mesh.num_elements, 0, instances.clone());
+            }
+        }
+    }
+
+    // Starts copying the ids at pixel (x, y) back, None when that is outside the buffer
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue, x: u32, y: u32) -> Option<IdReadback> {
+        if x >= self.texture.width() || y >= self.texture.height() {
+            return None;
+        }
+
+        Some(IdReadback::new(device, queue, &self.texture, x, y))
+    }
+}
+
+// A pixel of the id buffer on its way back to the cpu, polled the same way as `TextureCapture`
+pub struct IdReadback {
+    buffer: wgpu::Buffer,
+    receiver: futures_channel::oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+impl IdReadback {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, x: u32, y: u32) -> Self {
+        // a single row still has to be a whole copy alignment long
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Id Readback Buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Id Readback Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        Self {
+            buffer,
+            receiver,
+        }
+    }
+
+    fn read(&self) -> Option<IdBufferHit> {
+        let ids: [u32; 2] = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned(&data[..8])
+        };
+        self.buffer.unmap();
+
+        let [instance, mesh] = ids;
+        (instance > 0).then(|| IdBufferHit {
+            instance: instance as usize - 1,
+            mesh: mesh as usize,
+        })
+    }
+
+    // None while the copy is still in flight, then whatever was under the pixel
+    pub fn try_finish(&mut self) -> Option<Result<Option<IdBufferHit>>> {
+        match self.receiver.try_recv() {
+            Result::Ok(None) => None,
+            Result::Ok(Some(Result::Ok(()))) => Some(Ok(self.read())),
+            Result::Ok(Some(Err(error))) => Some(Err(error.into())),
+            Err(error) => Some(Err(error.into())),
+        }
+    }
+
+    pub async fn finish(mut self, device: &wgpu::Device) -> Result<Option<IdBufferHit>> {
+        device.poll(wgpu::Maintain::Wait);
+        (&mut self.receiver).await??;
+
+        Ok(self.read())
+    }
+}
@@ -0,0 +1,325 @@
+use wgpu::util::DeviceExt;
+
+use crate::texture::{CubeLut, Texture};
+
+// Screen space effects, in the order they are applied: bloom is added to the hdr scene before
+// tonemapping, chromatic aberration, the vignette and color grading run in one pass over the
+// tonemapped frame and FXAA comes last. Every effect is off while its option is `None`
+
+// how many times the bloom chain halves the resolution, starting at half the target's
+pub const BLOOM_LEVELS: usize = 5;
+// size of the identity LUT used until one is loaded
+pub const DEFAULT_LUT_SIZE: u32 = 16;
+
+// the bright parts of the scene bleed into their surroundings
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bloom {
+    // hdr brightness where bloom starts, with a soft `knee` below it
+    pub threshold: f32,
+    pub knee: f32,
+    // how much of the blurred highlights is added back
+    pub intensity: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.3,
+        }
+    }
+}
+
+// Fast approximate anti-aliasing, blurs along the edges it finds in the final image
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fxaa {
+    // the longest edge searched for, in pixels
+    pub span_max: f32,
+    // lower the blur on low contrast edges
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        }
+    }
+}
+
+// darkens the corners of the frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vignette {
+    // 0-1, how dark the corners get
+    pub intensity: f32,
+    // distance from the center where the darkening is complete, 1 is a corner
+    pub radius: f32,
+    // how far in from `radius` it starts
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.4,
+            radius: 1.0,
+            smoothness: 0.6,
+        }
+    }
+}
+
+// splits the red and blue channels apart towards the edges of the frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChromaticAberration {
+    // how far the channels move at the edges, as a fraction of the frame
+    pub intensity: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self {
+            intensity: 0.01,
+        }
+    }
+}
+
+// runs the frame through the LUT set with `PostProcessing::set_lut`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColorGrading {
+    // 0-1, blends between the ungraded and graded colors
+    pub contribution: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            contribution: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PostProcessOptions {
+    pub bloom: Option<Bloom>,
+    pub fxaa: Option<Fxaa>,
+    pub vignette: Option<Vignette>,
+    pub chromatic_aberration: Option<ChromaticAberration>,
+    pub color_grading: Option<ColorGrading>,
+}
+
+impl PostProcessOptions {
+    pub fn with_bloom(mut self, bloom: Bloom) -> Self {
+        self.bloom = Some(bloom);
+        self
+    }
+
+    pub fn with_fxaa(mut self, fxaa: Fxaa) -> Self {
+        self.fxaa = Some(fxaa);
+        self
+    }
+
+    pub fn with_vignette(mut self, vignette: Vignette) -> Self {
+        self.vignette = Some(vignette);
+        self
+    }
+
+    pub fn with_chromatic_aberration(mut self, chromatic_aberration: ChromaticAberration) -> Self {
+        self.chromatic_aberration = Some(chromatic_aberration);
+        self
+    }
+
+    pub fn with_color_grading(mut self, color_grading: ColorGrading) -> Self {
+        self.color_grading = Some(color_grading);
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostProcessUniform {
+    pub bloom_threshold: f32,
+    pub bloom_knee: f32,
+    // spread over the levels, which are all summed up
+    pub bloom_intensity: f32,
+    pub bloom: u32,
+    pub fxaa_span_max: f32,
+    pub fxaa_reduce_mul: f32,
+    pub fxaa_reduce_min: f32,
+    pub fxaa: u32,
+    pub vignette_intensity: f32,
+    pub vignette_radius: f32,
+    pub vignette_smoothness: f32,
+    pub vignette: u32,
+    pub lut_domain_min: [f32; 3],
+    // 0 when chromatic aberration is off
+    pub chromatic_aberration: f32,
+    pub lut_domain_max: [f32; 3],
+    // 0 when color grading is off
+    pub lut_contribution: f32,
+}
+
+impl PostProcessUniform {
+    pub fn new() -> Self {
+        Self {
+            bloom_threshold: 0.0,
+            bloom_knee: 0.0,
+            bloom_intensity: 0.0,
+            bloom: 0,
+            fxaa_span_max: 0.0,
+            fxaa_reduce_mul: 0.0,
+            fxaa_reduce_min: 0.0,
+            fxaa: 0,
+            vignette_intensity: 0.0,
+            vignette_radius: 0.0,
+            vignette_smoothness: 0.0,
+            vignette: 0,
+            lut_domain_min: [0.0; 3],
+            chromatic_aberration: 0.0,
+            lut_domain_max: [1.0; 3],
+            lut_contribution: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, options: &PostProcessOptions) {
+        let bloom = options.bloom.unwrap_or_default();
+        self.bloom = options.bloom.is_some() as u32;
+        self.bloom_threshold = bloom.threshold.max(0.0);
+        self.bloom_knee = bloom.knee.max(0.0);
+        self.bloom_intensity = bloom.intensity.max(0.0) / BLOOM_LEVELS as f32;
+
+        let fxaa = options.fxaa.unwrap_or_default();
+        self.fxaa = options.fxaa.is_some() as u32;
+        self.fxaa_span_max = fxaa.span_max.max(1.0);
+        self.fxaa_reduce_mul = fxaa.reduce_mul.max(0.0);
+        self.fxaa_reduce_min = fxaa.reduce_min.max(f32::EPSILON);
+
+        let vignette = options.vignette.unwrap_or_default();
+        self.vignette = options.vignette.is_some() as u32;
+        self.vignette_intensity = vignette.intensity.clamp(0.0, 1.0);
+        self.vignette_radius = vignette.radius;
+        self.vignette_smoothness = vignette.smoothness.max(f32::EPSILON);
+
+        self.chromatic_aberration = options.chromatic_aberration
+            .map_or(0.0, |chromatic_aberration| chromatic_aberration.intensity.max(0.0));
+        self.lut_contribution = options.color_grading
+            .map_or(0.0, |color_grading| color_grading.contribution.clamp(0.0, 1.0));
+    }
+}
+
+impl Default for PostProcessUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The effect settings and the color grading LUT, shared by every post processing pass at the
+// same bind group layout. Changes to `options` are uploaded by `update`
+pub struct PostProcessing {
+    pub options: PostProcessOptions,
+    pub uniform: PostProcessUniform,
+    pub buffer: wgpu::Buffer,
+    pub lut: Texture,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl PostProcessing {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, options: PostProcessOptions) -> Self {
+        let uniform = PostProcessUniform::new();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Process Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let lut = Texture::from_cube_lut(device, queue, &CubeLut::identity(DEFAULT_LUT_SIZE), "Identity LUT");
+        let bind_group_layout = Self::create_bind_group_layout(device);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &buffer, &lut);
+
+        Self {
+            options,
+            uniform,
+            buffer,
+            lut,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("post_process_bind_group_layout"),
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        lut: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&lut.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&lut.sampler),
+                },
+            ],
+            label: Some("post_process_bind_group"),
+        })
+    }
+
+    // the LUT color grading uses, it only shows once `options.color_grading` is set
+    pub fn set_lut(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, lut: &CubeLut) {
+        let label = lut.title.as_deref().unwrap_or("Color Grading LUT");
+        self.lut = Texture::from_cube_lut(device, queue, lut, label);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.buffer, &self.lut);
+        self.uniform.lut_domain_min = lut.domain_min;
+        self.uniform.lut_domain_max = lut.domain_max;
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        self.uniform.update(&self.options);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
@@ -0,0 +1,325 @@
+use wgpu::util::DeviceExt;
+
+// The scene is lit and drawn into an `HDR_FORMAT` attachment, then tonemapped into an
+// `LDR_FORMAT` one which is blitted to the target. Values above 1.0 survive until tonemapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const LDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// the average luminance auto exposure maps to mid grey
+pub const KEY_VALUE: f32 = 0.18;
+pub const HISTOGRAM_BINS: u64 = 256;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Tonemapper {
+    // the fit of the ACES filmic curve by Stephen Hill
+    #[default]
+    Aces,
+    // x / (1 + x), per channel
+    Reinhard,
+    // the minimal AgX approximation, desaturates bright colors instead of skewing their hue
+    AgX,
+}
+
+impl Tonemapper {
+    pub fn next(&self) -> Self {
+        match self {
+            Tonemapper::Aces => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::AgX,
+            Tonemapper::AgX => Tonemapper::Aces,
+        }
+    }
+}
+
+// Exposure that follows the scene, from a histogram of its log luminance
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AutoExposure {
+    // the range of log2 luminance the histogram covers, anything outside is clamped
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    // how quickly the exposure catches up, per second. `f32::INFINITY` adapts immediately
+    pub adaptation_speed: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -8.0,
+            max_log_luminance: 4.0,
+            adaptation_speed: 1.5,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct TonemapOptions {
+    pub tonemapper: Tonemapper,
+    // in stops, added on top of auto exposure when it is on
+    pub exposure: f32,
+    pub auto_exposure: Option<AutoExposure>,
+}
+
+impl TonemapOptions {
+    pub fn with_tonemapper(mut self, tonemapper: Tonemapper) -> Self {
+        self.tonemapper = tonemapper;
+        self
+    }
+
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_auto_exposure(mut self, auto_exposure: AutoExposure) -> Self {
+        self.auto_exposure = Some(auto_exposure);
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TonemapUniform {
+    // linear multiplier, 2 ^ the exposure in stops
+    pub exposure: f32,
+    pub tonemapper: u32,
+    pub auto_exposure: u32,
+    // copied in from the histogram pass every frame auto exposure is on
+    pub average_luminance: f32,
+}
+
+impl TonemapUniform {
+    // where `average_luminance` lives in the buffer
+    pub const AVERAGE_LUMINANCE_OFFSET: wgpu::BufferAddress = 12;
+
+    pub fn new() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapper: Tonemapper::default() as u32,
+            auto_exposure: 0,
+            average_luminance: KEY_VALUE,
+        }
+    }
+
+    pub fn update(&mut self, options: &TonemapOptions, auto_exposure: bool) {
+        self.exposure = options.exposure.exp2();
+        self.tonemapper = options.tonemapper as u32;
+        self.auto_exposure = auto_exposure as u32;
+    }
+}
+
+impl Default for TonemapUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HistogramParams {
+    pub min_log_luminance: f32,
+    pub log_luminance_range: f32,
+    // how far to move towards this frame's luminance, 0-1
+    pub adaptation: f32,
+    pub pixel_count: u32,
+}
+
+impl HistogramParams {
+    pub fn new(auto_exposure: &AutoExposure, dt: f32, size: (u32, u32)) -> Self {
+        let adaptation = if auto_exposure.adaptation_speed.is_infinite() {
+            1.0
+        } else {
+            1.0 - (-dt * auto_exposure.adaptation_speed.max(0.0)).exp()
+        };
+
+        Self {
+            min_log_luminance: auto_exposure.min_log_luminance,
+            log_luminance_range: (auto_exposure.max_log_luminance - auto_exposure.min_log_luminance).max(f32::EPSILON),
+            adaptation,
+            pixel_count: size.0 * size.1,
+        }
+    }
+}
+
+// The buffers the auto exposure compute passes share. `luminance_buffer` keeps the adapted
+// average luminance between frames
+pub struct LuminanceHistogram {
+    pub histogram_buffer: wgpu::Buffer,
+    pub luminance_buffer: wgpu::Buffer,
+    pub params_buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl LuminanceHistogram {
+    // compute shaders and storage buffers, which the WebGL2 downlevel limits don't have
+    pub fn is_supported(device: &wgpu::Device) -> bool {
+        let limits = device.limits();
+
+        limits.max_storage_buffers_per_shader_stage >= 2
+            && limits.max_compute_invocations_per_workgroup >= HISTOGRAM_BINS as u32
+            && limits.max_compute_workgroup_size_x >= HISTOGRAM_BINS as u32
+    }
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Histogram Buffer"),
+            size: HISTOGRAM_BINS * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let luminance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Average Luminance Buffer"),
+            contents: bytemuck::cast_slice(&[KEY_VALUE]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Histogram Params Buffer"),
+            contents: bytemuck::cast_slice(&[HistogramParams::new(&AutoExposure::default(), 0.0, (1, 1))]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = Self::create_bind_group_layout(device);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: luminance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("luminance_histogram_bind_group"),
+        });
+
+        Self {
+            histogram_buffer,
+            luminance_buffer,
+            params_buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                storage(0),
+                storage(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("luminance_histogram_bind_group_layout"),
+        })
+    }
+}
+
+// Tonemapping and exposure settings, uploaded by `update`
+pub struct Tonemapping {
+    pub options: TonemapOptions,
+    pub uniform: TonemapUniform,
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    // None where compute shaders aren't available, auto exposure is ignored there
+    pub histogram: Option<LuminanceHistogram>,
+    last_update: instant::Instant,
+}
+
+impl Tonemapping {
+    pub fn new(device: &wgpu::Device, options: TonemapOptions) -> Self {
+        let uniform = TonemapUniform::new();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = Self::create_bind_group_layout(device);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("tonemap_bind_group"),
+        });
+        let histogram = LuminanceHistogram::is_supported(device).then(|| LuminanceHistogram::new(device));
+
+        Self {
+            options,
+            uniform,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            histogram,
+            last_update: instant::Instant::now(),
+        }
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("tonemap_bind_group_layout"),
+        })
+    }
+
+    // whether the histogram passes run this frame
+    pub fn auto_exposure(&self) -> Option<&AutoExposure> {
+        self.histogram.as_ref().and(self.options.auto_exposure.as_ref())
+    }
+
+    // `size` is the size of the hdr attachment, the histogram is averaged over every pixel
+    pub fn update(&mut self, queue: &wgpu::Queue, size: (u32, u32)) {
+        let now = instant::Instant::now();
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        let auto_exposure = self.auto_exposure().copied();
+        self.uniform.update(&self.options, auto_exposure.is_some());
+        // everything but `average_luminance`, that one is written by the gpu
+        let bytes = bytemuck::bytes_of(&self.uniform);
+        queue.write_buffer(&self.buffer, 0, &bytes[..TonemapUniform::AVERAGE_LUMINANCE_OFFSET as usize]);
+
+        if let (Some(auto_exposure), Some(histogram)) = (auto_exposure, &self.histogram) {
+            let params = HistogramParams::new(&auto_exposure, dt, size);
+            queue.write_buffer(&histogram.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        }
+    }
+}
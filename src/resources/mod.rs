@@ -1,5 +1,5 @@
 use std::io::{BufReader, Cursor};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use cfg_if::cfg_if;
 
@@ -9,12 +9,20 @@ use gltf::Gltf;
 
 use crate::{
     model::{
+        Aabb,
+        BoundingSphere,
         Model,
         ModelVertex,
         Mesh,
         Material,
+        MaterialTextures,
+        MaterialUniform,
+        ModelHierarchy,
+        Node,
+        Transform,
+        generate_tangents,
     },
-    texture::Texture,
+    texture::{mipmap::MipmapGenerator, Texture, TextureOptions},
 };
 
 #[cfg(target_arch = "wasm32")]
@@ -73,28 +81,63 @@ pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
 
 pub async fn load_texture(
     file_name: &str,
-    is_normal_map: bool,
+    options: &TextureOptions,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
 ) -> anyhow::Result<Texture> {
     let data = load_binary(file_name).await?;
 
-    Texture::from_bytes(device, queue, &data, file_name, is_normal_map)
+    Texture::from_bytes(device, queue, mipmaps, &data, file_name, options)
 }
 
-const DEFAULT_DIFFUSE_PATH: &str = "meshes/core/empty-texture.png";
-const DEFAULT_NORMAL_PATH: &str = "meshes/core/empty-normal.png";
+// an equirectangular panorama (.hdr, png or jpeg) as a cubemap with `face_size` pixel faces
+pub async fn load_cubemap_equirectangular(
+    file_name: &str,
+    face_size: u32,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<Texture> {
+    let data = load_binary(file_name).await?;
 
-pub async fn load_model_gltf(
+    Texture::from_equirectangular(device, queue, &data, face_size, file_name)
+}
+
+// six square images, +x, -x, +y, -y, +z, -z
+pub async fn load_cubemap_faces(
+    file_names: &[String],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<Texture> {
+    let mut faces = Vec::with_capacity(file_names.len());
+    for file_name in file_names {
+        let data = load_binary(file_name).await?;
+        faces.push(image::load_from_memory(&data)?);
+    }
+
+    let label = file_names.first().map(String::as_str).unwrap_or("Cubemap");
+    Texture::from_cube_faces(device, queue, &faces, label)
+}
+
+struct GltfData {
+    gltf: Gltf,
+    buffers: Vec<Vec<u8>>,
+    materials: Vec<Material>,
+    // index of the material used by primitives that don't reference one
+    default_material: usize,
+}
+
+async fn load_gltf_data(
     file_name: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
     layout: &wgpu::BindGroupLayout,
-) -> anyhow::Result<Model> {
-    let gltf_text = load_string(file_name).await?;
-    let gltf_cursor = Cursor::new(gltf_text);
-    let gltf_reader = BufReader::new(gltf_cursor);
-    let gltf = Gltf::from_reader(gltf_reader)?;
+) -> anyhow::Result<GltfData> {
+    // read as bytes so both .gltf (JSON) and .glb (binary container) files can be loaded.
+    // `Gltf::from_slice` checks for the GLB magic bytes ("glTF") instead of relying on the extension
+    let gltf_data = load_binary(file_name).await?;
+    let gltf = Gltf::from_slice(&gltf_data)?;
 
     let mut basepath = PathBuf::from(file_name);
     basepath.pop();
@@ -104,155 +147,457 @@ pub async fn load_model_gltf(
     for buffer in gltf.buffers() {
         match buffer.source() {
             gltf::buffer::Source::Bin => {
-                if let Some(blob) = gltf.blob.as_deref() {
-                    buffer_data.push(blob.into());
-                };
+                // the BIN chunk of a GLB container
+                let blob = gltf.blob.as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("{} references a binary chunk it does not have", file_name))?;
+                buffer_data.push(blob.into());
             }
             gltf::buffer::Source::Uri(uri) => {
-                let binary_path: PathBuf = [basepath.clone(), uri.into()].iter().collect();//m.diffuse_texture.clone().into()].iter().collect();
-                let bin = load_binary(binary_path.to_str().unwrap()).await.expect("File Not Found");
+                let binary_path: PathBuf = [basepath.clone(), uri.into()].iter().collect();
+                let bin = load_binary(binary_path.to_str().unwrap()).await?;
                 buffer_data.push(bin);
             }
         }
     }
 
+    let images = GltfImageSource {
+        file_name,
+        basepath: &basepath,
+        buffer_data: &buffer_data,
+    };
+
     let mut materials = Vec::new();
     for material in gltf.materials() {
         let pbr = material.pbr_metallic_roughness();
-        let texture_source = &pbr.base_color_texture()
-            .map(|tex| {
-                tex.texture().source().source()
+        let mut textures = MaterialTextures::new_default(device, queue);
+
+        if let Some(info) = pbr.base_color_texture() {
+            textures.base_color = load_gltf_texture(info.texture(), TextureOptions::default(), &images, device, queue, mipmaps).await?;
+        }
+        if let Some(info) = material.normal_texture() {
+            textures.normal = load_gltf_texture(info.texture(), TextureOptions::normal_map(), &images, device, queue, mipmaps).await?;
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            textures.metallic_roughness = load_gltf_texture(info.texture(), TextureOptions::linear(), &images, device, queue, mipmaps).await?;
+        }
+        if let Some(info) = material.occlusion_texture() {
+            textures.occlusion = load_gltf_texture(info.texture(), TextureOptions::linear(), &images, device, queue, mipmaps).await?;
+        }
+        if let Some(info) = material.emissive_texture() {
+            textures.emissive = load_gltf_texture(info.texture(), TextureOptions::default(), &images, device, queue, mipmaps).await?;
+        }
+
+        let uniform = MaterialUniform {
+            base_color_factor: pbr.base_color_factor(),
+            emissive_factor: material.emissive_factor(),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            occlusion_strength: material.occlusion_texture().map_or(1.0, |info| info.strength()),
+            normal_scale: material.normal_texture().map_or(1.0, |info| info.scale()),
+            ..Default::default()
+        };
+
+        let mat = Material::new(
+            device,
+            material.name().unwrap_or("Default Material"),
+            textures,
+            uniform,
+            layout,
+        );
+        materials.push(mat);
+    }
+
+    // primitives without a material (or files without any materials) still need something to bind
+    let default_material = materials.len();
+    let needs_default_material = materials.is_empty() || gltf.meshes()
+        .flat_map(|mesh| mesh.primitives())
+        .any(|primitive| primitive.material().index().is_none());
+
+    if needs_default_material {
+        materials.push(Material::new(
+            device,
+            "Default Material",
+            MaterialTextures::new_default(device, queue),
+            MaterialUniform::default(),
+            layout,
+        ));
+    }
+
+    Ok(GltfData {
+        gltf,
+        buffers: buffer_data,
+        materials,
+        default_material,
+    })
+}
+
+// where the images of a glTF file are read from, files next to it or its buffers
+struct GltfImageSource<'a> {
+    file_name: &'a str,
+    basepath: &'a Path,
+    buffer_data: &'a [Vec<u8>],
+}
+
+// `options` says how the texture is stored (sRGB or linear data), the sampler settings come from
+// the glTF sampler the texture references
+async fn load_gltf_texture(
+    texture: gltf::Texture<'_>,
+    options: TextureOptions,
+    source: &GltfImageSource<'_>,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
+) -> anyhow::Result<Texture> {
+    let options = options.with_gltf_sampler(&texture.sampler());
+
+    match texture.source().source() {
+        gltf::image::Source::View { view, .. } => {
+            // Image texture data is in the binary
+            Texture::from_bytes(
+                device,
+                queue,
+                mipmaps,
+                buffer_view_data(&view, source.buffer_data)?,
+                source.file_name,
+                &options,
+            )
+        }
+        gltf::image::Source::Uri { uri, .. } => {
+            let full_path: PathBuf = [source.basepath, Path::new(uri)].iter().collect();
+            // Image texture data is in a separate image file
+            load_texture(full_path.to_str().unwrap(), &options, device, queue, mipmaps).await
+        }
+    }
+}
+
+// the bytes a buffer view points at, ie an image embedded in a GLB binary chunk. A truncated
+// or malformed file can point past the end of its buffer
+fn buffer_view_data<'a>(view: &gltf::buffer::View, buffer_data: &'a [Vec<u8>]) -> anyhow::Result<&'a [u8]> {
+    let start = view.offset();
+    let end = start.saturating_add(view.length());
+
+    buffer_data.get(view.buffer().index())
+        .and_then(|buffer| buffer.get(start..end))
+        .ok_or_else(|| anyhow::anyhow!(
+            "buffer view {} ({}..{}) is outside of buffer {}",
+            view.index(),
+            start,
+            end,
+            view.buffer().index(),
+        ))
+}
+
+fn read_gltf_primitive(
+    primitive: &gltf::Primitive,
+    buffer_data: &[Vec<u8>],
+) -> anyhow::Result<(Vec<ModelVertex>, Vec<u32>)> {
+    // the reader quietly skips accessors it can't find the data of, so they're checked up front
+    for accessor in primitive.attributes().map(|(_, accessor)| accessor).chain(primitive.indices()) {
+        if let Some(view) = accessor.view() {
+            buffer_view_data(&view, buffer_data)?;
+        }
+    }
+
+    let reader = primitive.reader(|buffer| {
+        buffer_data.get(buffer.index()).map(Vec::as_slice)
+    });
+
+    let mut vertices = Vec::new();
+
+    if let Some(vertex_attribute) = reader.read_positions() {
+        vertex_attribute.for_each(|vertex| {
+            vertices.push(ModelVertex {
+                position: vertex,
+                tex_coords: Default::default(),
+                normal: Default::default(),
+                tangent: Default::default(),
             })
-            .expect("Issue Finding Texture Source");
-        let is_normal_map = false;
-        let default_normal_texture = load_texture(DEFAULT_NORMAL_PATH, true, device, queue).await?;
-        match texture_source {
-            gltf::image::Source::View { view, mime_type } => {
-                // Image texture data is in the binary
-                let diffuse_texture = Texture::from_bytes(
-                    device,
-                    queue,
-                    &buffer_data[view.buffer().index()],
-                    file_name,
-                    is_normal_map,
-                )
-                .expect("Issue loading Diffuse Texture");
-
-                let mat = Material::new(
-                    device,
-                    material.name().unwrap_or("Default Material"),
-                    diffuse_texture,
-                    default_normal_texture,
-                    layout,
-                );
-                materials.push(mat);
-            }
-            gltf::image::Source::Uri { uri, mime_type } => {
-                let full_path: PathBuf = [basepath.clone(), uri.into()].iter().collect();
-                let full_uri = full_path.to_str().unwrap();
-                // Image texture data is in a separate image file
-                let diffuse_texture = load_texture(full_uri, is_normal_map, device, queue).await?;
-
-                let mat = Material::new(
-                    device,
-                    material.name().unwrap_or("Default Material"),
-                    diffuse_texture,
-                    default_normal_texture,
-                    layout,
-                );
-                materials.push(mat);
-            }
+        });
+    }
+
+    // attributes with more values than there are positions are cut off
+    if let Some(normal_attribute) = reader.read_normals() {
+        for (vertex, normal) in vertices.iter_mut().zip(normal_attribute) {
+            vertex.normal = normal;
         }
     }
 
-    let mut meshes = Vec::new();
+    if let Some(tex_coord_attribute) = reader.read_tex_coords(0).map(|v| v.into_f32()) {
+        for (vertex, tex_coord) in vertices.iter_mut().zip(tex_coord_attribute) {
+            // need to flip/invert the y-axis of UV tex coords for wgpu/WebGPU
+            vertex.tex_coords = [tex_coord[0], 1.0 - tex_coord[1]];
+        }
+    }
 
-    for scene in gltf.scenes() {
-        for node in scene.nodes() {
-            let mesh = node.mesh().expect("Unable to load Mesh");
-            let primitives = mesh.primitives();
-
-            primitives.for_each(|primitive| {
-                let reader = primitive.reader(|buffer| {
-                    Some(&buffer_data[buffer.index()])
-                });
-
-                let mut vertices = Vec::new();
-
-                if let Some(vertex_attribute) = reader.read_positions() {
-                    vertex_attribute.for_each(|vertex| {
-                        vertices.push(ModelVertex {
-                            position: vertex,
-                            tex_coords: Default::default(),
-                            normal: Default::default(),
-                            tangent: Default::default(),
-                            bitangent: Default::default(),
-                        })
-                    });
-                }
+    let mut indices = Vec::new();
+    if let Some(indices_raw) = reader.read_indices() {
+        indices.append(&mut indices_raw.into_u32().collect::<Vec<u32>>());
+    } else {
+        // non-indexed geometry, every 3 vertices make a triangle
+        indices.extend(0..vertices.len() as u32);
+    }
+    if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertices.len()) {
+        anyhow::bail!("index {} is past the {} vertices of its primitive", index, vertices.len());
+    }
 
-                if let Some(normal_attribute) = reader.read_normals() {
-                    let mut normal_index = 0;
-                    normal_attribute.for_each(|normal| {
-                        vertices[normal_index].normal = normal;
+    // tangents exported with the file are what its normal maps were baked against,
+    // they're only generated when missing
+    if let Some(tangent_attribute) = reader.read_tangents() {
+        for (vertex, tangent) in vertices.iter_mut().zip(tangent_attribute) {
+            vertex.tangent = tangent;
+        }
+    } else {
+        generate_tangents(&mut vertices, &mut indices);
+    }
 
-                        normal_index += 1;
-                    });
-                }
+    Ok((vertices, indices))
+}
 
-                if let Some(tex_coord_attribute) = reader.read_tex_coords(0).map(|v| v.into_f32()) {
-                    let mut tex_coord_index = 0;
-                    tex_coord_attribute.for_each(|tex_coord| {
-                        // need to flip/invert the y-axis of UV tex coords for wgpu/WebGPU
-                        let reverse_y_tex_coords = [tex_coord[0], 1.0 - tex_coord[1]];
-                        vertices[tex_coord_index].tex_coords = reverse_y_tex_coords;
+// bake a node's world transform into the vertex data
+fn transform_vertices(vertices: &mut [ModelVertex], world: cgmath::Matrix4<f32>) {
+    use cgmath::{InnerSpace, Transform as _};
+
+    let normal_matrix = crate::instance::InstanceRaw::from_matrix(world).normal;
+    let normal_matrix = cgmath::Matrix3::from(normal_matrix);
+    // a mirroring transform flips the bitangent cross(normal, tangent) gives
+    let handedness = cgmath::SquareMatrix::determinant(&normal_matrix).signum();
+
+    for vertex in vertices.iter_mut() {
+        let position = world.transform_point(cgmath::Point3::from(vertex.position));
+        let normal = normal_matrix * cgmath::Vector3::from(vertex.normal);
+        // tangents lie in the surface, so they transform like positions do
+        let [x, y, z, w] = vertex.tangent;
+        let tangent = world.transform_vector(cgmath::Vector3::new(x, y, z));
+
+        vertex.position = position.into();
+        if normal.magnitude2() > 0.0 {
+            vertex.normal = normal.normalize().into();
+        }
+        if tangent.magnitude2() > 0.0 {
+            let tangent = tangent.normalize();
+            vertex.tangent = [tangent.x, tangent.y, tangent.z, w * handedness];
+        }
+    }
+}
 
-                        tex_coord_index += 1;
-                    });
-                }
+// the cpu side of a mesh: its positions for picking plus the bounds around them
+fn mesh_geometry(vertices: &[ModelVertex]) -> (Vec<[f32; 3]>, Aabb, BoundingSphere) {
+    let positions = vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>();
+    let bounds = Aabb::from_points(positions.iter().copied());
+    let bounding_sphere = BoundingSphere::from_points(&positions);
 
-                let mut indices = Vec::new();
-                if let Some(indices_raw) = reader.read_indices() {
-                    indices.append(&mut indices_raw.into_u32().collect::<Vec<u32>>());
-                }
+    (positions, bounds, bounding_sphere)
+}
 
-                calculate_normal_tangents(&indices, &mut vertices);
+fn create_mesh(
+    device: &wgpu::Device,
+    name: &str,
+    vertices: &[ModelVertex],
+    indices: &[u32],
+    material: usize,
+) -> Mesh {
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{:?} Vertex Buffer", name)),
+        contents: bytemuck::cast_slice(vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{:?} Index Buffer", name)),
+        contents: bytemuck::cast_slice(indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+
+    let (positions, bounds, bounding_sphere) = mesh_geometry(vertices);
+
+    Mesh {
+        name: name.to_string(),
+        vertex_buffer,
+        index_buffer,
+        num_elements: indices.len() as u32,
+        material,
+        bounds,
+        bounding_sphere,
+        positions,
+        indices: indices.to_vec(),
+    }
+}
 
-                let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some(&format!("{:?} Vertex Buffer", file_name)),
-                    contents: bytemuck::cast_slice(&vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                });
+// the root nodes of the scene to show, the default scene or else the first one. Files without
+// any scene get every node that isn't the child of another
+fn gltf_scene_roots(gltf: &Gltf) -> Vec<gltf::Node<'_>> {
+    match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => scene.nodes().collect(),
+        None => {
+            let children = gltf.nodes()
+                .flat_map(|node| node.children())
+                .map(|child| child.index())
+                .collect::<std::collections::HashSet<_>>();
+
+            gltf.nodes().filter(|node| !children.contains(&node.index())).collect()
+        },
+    }
+}
 
-                let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some(&format!("{:?} Index Buffer", file_name)),
-                    contents: bytemuck::cast_slice(&indices),
-                    usage: wgpu::BufferUsages::INDEX,
-                });
-
-                let material_name = primitive.material().name().unwrap_or_default();
-                let material_index = materials.iter().position(|m| {
-                    m.name == material_name
-                });
-
-                meshes.push(Mesh {
-                    name: file_name.to_string(),
-                    vertex_buffer,
-                    index_buffer,
-                    num_elements: indices.len() as u32,
-                    material: material_index.unwrap_or(0),
-                });
-            });
+// every node reachable from the scene roots paired with its world transform, nodes outside
+// the scene aren't drawn. Parents are always listed before their children
+fn collect_gltf_nodes(gltf: &Gltf) -> Vec<(gltf::Node<'_>, cgmath::Matrix4<f32>)> {
+    use cgmath::SquareMatrix;
+
+    let mut nodes = Vec::new();
+    let mut stack: Vec<(gltf::Node, cgmath::Matrix4<f32>)> = gltf_scene_roots(gltf).into_iter()
+        .map(|node| (node, cgmath::Matrix4::identity()))
+        .collect();
+    stack.reverse();
+
+    while let Some((node, parent_transform)) = stack.pop() {
+        let world = parent_transform * Transform::from(node.transform()).matrix();
+
+        let mut children: Vec<_> = node.children().map(|child| (child, world)).collect();
+        children.reverse();
+        stack.extend(children);
+
+        nodes.push((node, world));
+    }
+
+    nodes
+}
+
+// Loads a glTF file into a single `Model`. Every node's world transform is baked into the
+// vertices of its meshes, so nested and transformed nodes end up where they should be
+pub async fn load_model_gltf(
+    file_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<Model> {
+    let GltfData { gltf, buffers, materials, default_material } = load_gltf_data(file_name, device, queue, mipmaps, layout).await?;
+
+    let mut meshes = Vec::new();
+
+    for (node, world) in collect_gltf_nodes(&gltf) {
+        // nodes can be empty and only be used to group/transform their children
+        let Some(mesh) = node.mesh() else { continue };
+        let mesh_name = mesh.name().or(node.name()).unwrap_or(file_name);
+
+        for primitive in mesh.primitives() {
+            let (mut vertices, indices) = read_gltf_primitive(&primitive, &buffers)?;
+
+            transform_vertices(&mut vertices, world);
+
+            let material = primitive.material().index().unwrap_or(default_material);
+            meshes.push(create_mesh(device, mesh_name, &vertices, &indices, material));
         }
     }
 
     Ok(Model { meshes, materials })
 }
 
+// Loads a glTF file keeping its node tree. Each glTF mesh is only uploaded once and nodes
+// reference it, so the hierarchy can be manipulated and re-rendered without reloading
+pub async fn load_model_hierarchy_gltf(
+    file_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<ModelHierarchy> {
+    let GltfData { gltf, buffers, materials, default_material } = load_gltf_data(file_name, device, queue, mipmaps, layout).await?;
+
+    let mut meshes = Vec::new();
+    // glTF mesh index => indices of the meshes created from its primitives
+    let mut mesh_lookup = Vec::new();
+
+    for mesh in gltf.meshes() {
+        let mesh_name = mesh.name().unwrap_or(file_name);
+        let mut mesh_indices = Vec::new();
+
+        for primitive in mesh.primitives() {
+            let (vertices, indices) = read_gltf_primitive(&primitive, &buffers)?;
+
+            let material = primitive.material().index().unwrap_or(default_material);
+            mesh_indices.push(meshes.len());
+            meshes.push(create_mesh(device, mesh_name, &vertices, &indices, material));
+        }
+
+        mesh_lookup.push(mesh_indices);
+    }
+
+    // only the nodes of the scene are kept, parents before their children.
+    // glTF node index => index in `nodes`
+    let scene_nodes = collect_gltf_nodes(&gltf);
+    let mut node_lookup = vec![None; gltf.nodes().len()];
+    for (index, (node, _)) in scene_nodes.iter().enumerate() {
+        node_lookup[node.index()] = Some(index);
+    }
+
+    let mut nodes: Vec<Node> = scene_nodes.iter()
+        .map(|(node, _)| Node {
+            name: node.name().unwrap_or_default().to_string(),
+            transform: Transform::from(node.transform()),
+            meshes: node.mesh()
+                .map(|mesh| mesh_lookup[mesh.index()].clone())
+                .unwrap_or_default(),
+            children: node.children().filter_map(|child| node_lookup[child.index()]).collect(),
+            parent: None,
+        })
+        .collect();
+
+    for index in 0..nodes.len() {
+        for child in nodes[index].children.clone() {
+            nodes[child].parent = Some(index);
+        }
+    }
+
+    let roots = gltf_scene_roots(&gltf).iter()
+        .filter_map(|node| node_lookup[node.index()])
+        .collect();
+
+    Ok(ModelHierarchy::new(device, Model { meshes, materials }, nodes, roots))
+}
+
+fn parse_mtl_floats(value: &str) -> Vec<f32> {
+    value.split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect()
+}
+
+// MTL only describes Blinn-Phong materials, so map what it has onto metallic-roughness.
+// `Pr`/`Pm`/`Ke` come from the PBR extension to MTL that Blender and others export
+fn mtl_material_uniform(m: &tobj::Material) -> MaterialUniform {
+    // when there is a diffuse map it is the base color, exporters often write a `Kd` of 0
+    // next to it which would otherwise turn the texture black
+    let [r, g, b] = if m.diffuse_texture.is_empty() { m.diffuse } else { [1.0; 3] };
+
+    // shininess (Ns) is a Phong exponent, convert it to the equivalent GGX roughness
+    let roughness = m.unknown_param.get("Pr")
+        .and_then(|value| parse_mtl_floats(value).first().copied())
+        .unwrap_or_else(|| (2.0 / (m.shininess.max(0.0) + 2.0)).sqrt());
+    let metallic = m.unknown_param.get("Pm")
+        .and_then(|value| parse_mtl_floats(value).first().copied())
+        .unwrap_or(0.0);
+    let emissive = m.unknown_param.get("Ke")
+        .map(|value| parse_mtl_floats(value))
+        .filter(|values| values.len() == 3)
+        .map(|values| [values[0], values[1], values[2]])
+        // an emissive map without a factor should still show up
+        .unwrap_or(if m.unknown_param.contains_key("map_Ke") { [1.0; 3] } else { [0.0; 3] });
+
+    MaterialUniform {
+        base_color_factor: [r, g, b, m.dissolve],
+        emissive_factor: emissive,
+        metallic_factor: metallic,
+        roughness_factor: roughness.clamp(0.0, 1.0),
+        ..Default::default()
+    }
+}
+
 pub async fn load_model(
     file_name: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
     layout: &wgpu::BindGroupLayout,
 ) -> anyhow::Result<Model> {
     let obj_text = load_string(file_name).await?;
@@ -283,65 +628,54 @@ pub async fn load_model(
     let mut materials = Vec::new();
 
     for m in obj_materials? {
-        let diffuse_path: PathBuf = [basepath.clone(), m.diffuse_texture.clone().into()].iter().collect();
-        let diffuse_path_str = {
-            if m.diffuse_texture.is_empty() {
-                DEFAULT_DIFFUSE_PATH
-            } else {
-                diffuse_path.to_str().unwrap()
-            }
-        };
-        let normal_path: PathBuf = [basepath.clone(), m.normal_texture.clone().into()].iter().collect();
-        let normal_path_str = {
-            if m.normal_texture.is_empty() {
-                DEFAULT_NORMAL_PATH
-            } else {
-                normal_path.to_str().unwrap()
-            }
-        };
-
-        let diffuse_texture = load_texture(&diffuse_path_str, false, device, queue).await?;
-        let normal_texture = load_texture(&normal_path_str, true, device, queue).await?;
+        let mut textures = MaterialTextures::new_default(device, queue);
+        // MTL texture maps repeat unless told otherwise (`-clamp on`)
+        let mtl_options = TextureOptions::default().with_address_mode(wgpu::AddressMode::Repeat);
+        let mtl_normal_options = TextureOptions::normal_map().with_address_mode(wgpu::AddressMode::Repeat);
+
+        if !m.diffuse_texture.is_empty() {
+            let diffuse_path: PathBuf = [basepath.clone(), m.diffuse_texture.clone().into()].iter().collect();
+            textures.base_color = load_texture(diffuse_path.to_str().unwrap(), &mtl_options, device, queue, mipmaps).await?;
+        }
+        if !m.normal_texture.is_empty() {
+            let normal_path: PathBuf = [basepath.clone(), m.normal_texture.clone().into()].iter().collect();
+            textures.normal = load_texture(normal_path.to_str().unwrap(), &mtl_normal_options, device, queue, mipmaps).await?;
+        }
+        // emissive map from the PBR extension to the MTL format
+        if let Some(emissive_texture) = m.unknown_param.get("map_Ke") {
+            let emissive_path: PathBuf = [basepath.clone(), emissive_texture.into()].iter().collect();
+            textures.emissive = load_texture(emissive_path.to_str().unwrap(), &mtl_options, device, queue, mipmaps).await?;
+        }
 
         let material = Material::new(
             device,
             &m.name,
-            diffuse_texture,
-            normal_texture,
+            textures,
+            mtl_material_uniform(&m),
             layout,
         );
 
         materials.push(material);
     }
 
+    // meshes without a material still need something to bind
+    if materials.is_empty() {
+        materials.push(Material::new(
+            device,
+            "Default Material",
+            MaterialTextures::new_default(device, queue),
+            MaterialUniform::default(),
+            layout,
+        ));
+    }
+
     let meshes = models
         .into_iter()
         .map(|m| {
-            let mut vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [
-                        m.mesh.texcoords[i * 2],
-                        m.mesh.texcoords[i * 2 + 1], // 1 - y reverse y
-                    ],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                    // we'll calculate these later
-                    tangent: [0.0; 3],
-                    bitangent: [0.0; 3],
-                })
-                .collect::<Vec<_>>();
-
-            let indices = &m.mesh.indices;
-
-            calculate_normal_tangents(indices, &mut vertices);
+            let mut indices = m.mesh.indices.clone();
+            let mut vertices = obj_vertices(&m.mesh);
+
+            generate_tangents(&mut vertices, &mut indices);
 
             let vertex_buffer = device.create_buffer_init(
                 &wgpu::util::BufferInitDescriptor {
@@ -354,17 +688,23 @@ pub async fn load_model(
             let index_buffer = device.create_buffer_init(
                 &wgpu::util::BufferInitDescriptor {
                     label: Some(&format!("{:?} Index Buffer", file_name)),
-                    contents: bytemuck::cast_slice(&m.mesh.indices),
+                    contents: bytemuck::cast_slice(&indices),
                     usage: wgpu::BufferUsages::INDEX,
                 }
             );
 
+            let (positions, bounds, bounding_sphere) = mesh_geometry(&vertices);
+
             Mesh {
                 name: file_name.to_string(),
                 vertex_buffer,
                 index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
+                num_elements: indices.len() as u32,
                 material: m.mesh.material_id.unwrap_or(0),
+                bounds,
+                bounding_sphere,
+                positions,
+                indices,
             }
         })
         .collect::<Vec<_>>();
@@ -372,61 +712,27 @@ pub async fn load_model(
     Ok(Model { meshes, materials })
 }
 
-pub fn calculate_normal_tangents(indices: &Vec<u32>, vertices: &mut Vec<ModelVertex>) {
-    let mut triangles_included = vec![0; vertices.len()];
-
-    // operate on triangles so iterate of chunks of 3
-    for c in indices.chunks(3) {
-        let v0 = vertices[c[0] as usize];
-        let v1 = vertices[c[1] as usize];
-        let v2 = vertices[c[2] as usize];
-
-        let pos0: cgmath::Vector3<_> = v0.position.into();
-        let pos1: cgmath::Vector3<_> = v1.position.into();
-        let pos2: cgmath::Vector3<_> = v2.position.into();
-
-        let uv0: cgmath::Vector2<_> = v0.tex_coords.into();
-        let uv1: cgmath::Vector2<_> = v1.tex_coords.into();
-        let uv2: cgmath::Vector2<_> = v2.tex_coords.into();
-
-        // calculate edges of triangles
-        let delta_pos1 = pos1 - pos0;
-        let delta_pos2 = pos2 - pos0;
-
-        // this will give direction to calculate tangent and bitangent
-        let delta_uv1 = uv1 - uv0;
-        let delta_uv2 = uv2 - uv0;
-
-        // Solving the following system of equations will
-        // give us the tangent and bitangent.
-        //     delta_pos1 = delta_uv1.x * T + delta_u.y * B
-        //     delta_pos2 = delta_uv2.x * T + delta_uv2.y * B
-        let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-        // flip the bitangent to enable right-handed normal
-        // maps with wgpu texture coordinate system
-        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
-
-        // use same tangent/bitangent for each vertex in the triangle
-        vertices[c[0] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
-        vertices[c[1] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
-        vertices[c[2] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into();
-        vertices[c[0] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
-        vertices[c[1] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
-        vertices[c[2] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into();
-
-        // used to average tangent/bitangent
-        triangles_included[c[0] as usize] += 1;
-        triangles_included[c[1] as usize] += 1;
-        triangles_included[c[2] as usize] += 1;
-    }
-
-    // average the tangents/bitangents
-    for(i, n) in triangles_included.into_iter().enumerate() {
-        let denom = 1.0 / n as f32;
-        let mut v = vertices[i];
-
-        v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
-        v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
-    }
+// The vertices of an OBJ mesh loaded with `single_index`, without tangents. Meshes exported
+// without texture coordinates or normals get zeros for them
+pub fn obj_vertices(mesh: &tobj::Mesh) -> Vec<ModelVertex> {
+    (0..mesh.positions.len() / 3)
+        .map(|i| ModelVertex {
+            position: [
+                mesh.positions[i * 3],
+                mesh.positions[i * 3 + 1],
+                mesh.positions[i * 3 + 2],
+            ],
+            tex_coords: [
+                mesh.texcoords.get(i * 2).copied().unwrap_or_default(),
+                mesh.texcoords.get(i * 2 + 1).copied().unwrap_or_default(),
+            ],
+            normal: [
+                mesh.normals.get(i * 3).copied().unwrap_or_default(),
+                mesh.normals.get(i * 3 + 1).copied().unwrap_or_default(),
+                mesh.normals.get(i * 3 + 2).copied().unwrap_or_default(),
+            ],
+            // filled in by generate_tangents
+            tangent: [0.0; 4],
+        })
+        .collect()
 }
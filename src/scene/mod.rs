@@ -0,0 +1,357 @@
+use anyhow::*;
+use cgmath::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, Projection};
+use crate::instance::Instance;
+use crate::light::{Light, LightKind, MAX_LIGHTS};
+use crate::primitives::quad::QuadOptions;
+use crate::resources;
+use crate::sprite::Sprite;
+use crate::text::Text;
+
+// Everything needed to set up what the renderer draws, read from and written to json. Paths
+// are relative to the assets folder. Angles are in degrees and colors are 0-1 unless noted.
+// The renderer draws one instanced model and one node hierarchy, so that is all a scene has
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    // drawn once per instance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<ModelDescription>,
+    // a glTF node tree drawn with its own transforms instead of copies of the whole model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hierarchy: Option<String>,
+    pub lights: Vec<LightDescription>,
+    pub camera: CameraDescription,
+    pub clear_color: [f64; 4],
+    // drawn instead of the clear color and reflected by the models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skybox: Option<SkyboxDescription>,
+    pub overlay: OverlayDescription,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            model: None,
+            hierarchy: None,
+            lights: Vec::new(),
+            camera: CameraDescription::default(),
+            clear_color: [0.1, 0.2, 0.3, 1.0],
+            skybox: None,
+            overlay: OverlayDescription::default(),
+        }
+    }
+}
+
+impl Scene {
+    // what the apps start with when there is no scene file to load
+    pub fn initial() -> Self {
+        Self {
+            model: Some(ModelDescription {
+                path: "meshes/greg/greg_basic_export_applied_uv.gltf".to_string(),
+                instances: Vec::new(),
+                grid: Some(GridDescription { amount: 1, spacing: 3.0 }),
+            }),
+            lights: vec![LightDescription::from_light(&Light::point([2.0, 2.0, 2.0], [1.0, 1.0, 1.0]))],
+            overlay: OverlayDescription {
+                quad: Some(QuadDescription {
+                    position: [20.0, 20.0],
+                    size: [200.0, 100.0],
+                    color: (10, 207, 131, 0.5),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    // `file_name` is relative to the assets folder, like every other resource
+    pub async fn load(file_name: &str) -> Result<Self> {
+        let json = resources::load_string(file_name).await?;
+
+        Self::from_json(&json).with_context(|| format!("Unable to parse scene {}", file_name))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    // The renderer draws up to MAX_LIGHTS lights, and a skybox comes from a panorama or six
+    // faces, not both
+    pub fn validate(&self) -> Result<()> {
+        if self.lights.len() > MAX_LIGHTS {
+            bail!("A scene can have up to {} lights, found {}", MAX_LIGHTS, self.lights.len());
+        }
+
+        if let Some(skybox) = &self.skybox {
+            if skybox.equirectangular.is_some() == skybox.faces.is_some() {
+                bail!("A skybox needs either an equirectangular panorama or six faces");
+            }
+            // the upper bound depends on the device, `Texture::from_equirectangular` checks it
+            if skybox.face_size == 0 {
+                bail!("A skybox's faces have to be at least one pixel wide");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelDescription {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<InstanceDescription>,
+    // added after `instances`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<GridDescription>,
+}
+
+impl ModelDescription {
+    pub fn to_instances(&self) -> Vec<Instance> {
+        let grid = self.grid
+            .map(|grid| crate::app::create_instance_grid(grid.amount, grid.spacing))
+            .unwrap_or_default();
+
+        self.instances.iter()
+            .map(InstanceDescription::to_instance)
+            .chain(grid)
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstanceDescription {
+    pub position: [f32; 3],
+    // around x, then y, then z
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    pub tint: [f32; 4],
+    pub custom: [f32; 4],
+}
+
+impl Default for InstanceDescription {
+    fn default() -> Self {
+        Self::from_instance(&Instance::default())
+    }
+}
+
+impl InstanceDescription {
+    pub fn from_instance(instance: &Instance) -> Self {
+        let euler = Euler::from(instance.rotation);
+
+        Self {
+            position: instance.position.into(),
+            rotation: [Deg::from(euler.x).0, Deg::from(euler.y).0, Deg::from(euler.z).0],
+            scale: instance.scale.into(),
+            tint: instance.tint,
+            custom: instance.custom,
+        }
+    }
+
+    pub fn to_instance(&self) -> Instance {
+        let [x, y, z] = self.rotation;
+        let rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z)));
+
+        Instance::new(self.position.into(), rotation)
+            .with_scale(self.scale.into())
+            .with_tint(self.tint)
+            .with_custom(self.custom)
+    }
+}
+
+// `amount` x `amount` copies on the xz plane, the same layout the app starts with
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridDescription {
+    pub amount: u32,
+    pub spacing: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKindDescription {
+    Point,
+    Directional,
+    Spot,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightDescription {
+    pub kind: LightKindDescription,
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub attenuation: [f32; 3],
+    pub inner_cone_angle: f32,
+    pub outer_cone_angle: f32,
+    pub cast_shadows: bool,
+}
+
+impl Default for LightDescription {
+    fn default() -> Self {
+        Self::from_light(&Light::default())
+    }
+}
+
+impl LightDescription {
+    pub fn from_light(light: &Light) -> Self {
+        Self {
+            kind: match light.kind {
+                LightKind::Point => LightKindDescription::Point,
+                LightKind::Directional => LightKindDescription::Directional,
+                LightKind::Spot => LightKindDescription::Spot,
+            },
+            position: light.position,
+            direction: light.direction,
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+            attenuation: light.attenuation,
+            inner_cone_angle: Deg::from(light.inner_cone_angle).0,
+            outer_cone_angle: Deg::from(light.outer_cone_angle).0,
+            cast_shadows: light.cast_shadows,
+        }
+    }
+
+    pub fn to_light(&self) -> Light {
+        Light {
+            kind: match self.kind {
+                LightKindDescription::Point => LightKind::Point,
+                LightKindDescription::Directional => LightKind::Directional,
+                LightKindDescription::Spot => LightKind::Spot,
+            },
+            position: self.position,
+            direction: self.direction,
+            color: self.color,
+            intensity: self.intensity,
+            range: self.range,
+            attenuation: self.attenuation,
+            inner_cone_angle: Deg(self.inner_cone_angle).into(),
+            outer_cone_angle: Deg(self.outer_cone_angle).into(),
+            cast_shadows: self.cast_shadows,
+        }
+    }
+}
+
+// where the 3d camera starts and its perspective projection
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 5.0, 10.0],
+            yaw: -90.0,
+            pitch: -20.0,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn from_camera(camera: &Camera, projection: &Projection) -> Self {
+        Self {
+            position: camera.position.into(),
+            yaw: Deg::from(camera.yaw).0,
+            pitch: Deg::from(camera.pitch).0,
+            fovy: Deg::from(projection.fovy()).0,
+            znear: projection.znear(),
+            zfar: projection.zfar(),
+        }
+    }
+
+    pub fn to_camera(&self) -> Camera {
+        Camera::new(self.position, Deg(self.yaw), Deg(self.pitch))
+    }
+
+    pub fn to_projection(&self, width: u32, height: u32) -> Projection {
+        Projection::new(width, height, Deg(self.fovy), self.znear, self.zfar)
+    }
+}
+
+// The environment cubemap, either resampled from an equirectangular panorama or put together
+// from six square images
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkyboxDescription {
+    // a .hdr (or sRGB png/jpeg) panorama, straight up at the top
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equirectangular: Option<String>,
+    // +x, -x, +y, -y, +z, -z
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub faces: Option<[String; 6]>,
+    // pixels per side of each face the panorama is resampled into
+    pub face_size: u32,
+    pub intensity: f32,
+    pub reflection_intensity: f32,
+}
+
+impl Default for SkyboxDescription {
+    fn default() -> Self {
+        Self {
+            equirectangular: None,
+            faces: None,
+            face_size: 256,
+            intensity: 1.0,
+            reflection_intensity: 1.0,
+        }
+    }
+}
+
+// 2d items drawn over the scene, in logical pixels from the top left corner
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayDescription {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quad: Option<QuadDescription>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sprites: Vec<Sprite>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub text: Vec<Text>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuadDescription {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    // 0-255 rgb with a 0-1 alpha
+    pub color: (u32, u32, u32, f32),
+}
+
+impl QuadDescription {
+    pub fn from_options(options: &QuadOptions) -> Self {
+        Self {
+            position: options.position,
+            size: [options.dimensions.0, options.dimensions.1],
+            color: options.color,
+        }
+    }
+
+    pub fn to_options(&self) -> QuadOptions {
+        QuadOptions {
+            position: self.position,
+            color: self.color,
+            dimensions: (self.size[0], self.size[1]),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+// Copies the tonemapped frame to the target
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureLoad(t_source, vec2<i32>(in.clip_position.xy), 0);
+}
+
+// for targets without an sRGB format (usually the canvas on the web), which store what they
+// are given as is
+@fragment
+fn fs_encode_srgb(in: VertexOutput) -> @location(0) vec4<f32> {
+  let color = textureLoad(t_source, vec2<i32>(in.clip_position.xy), 0);
+  let low = color.rgb * 12.92;
+  let high = 1.055 * pow(color.rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+  return vec4<f32>(select(high, low, color.rgb <= vec3<f32>(0.0031308)), color.a);
+}
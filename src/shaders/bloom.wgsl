@@ -0,0 +1,77 @@
+// Bloom: the bright parts of the hdr scene are thresholded into half resolution, blurred down
+// a chain of smaller attachments with the dual filter and blurred back up, each level adding
+// the one below it. Tonemapping adds the top of the chain to the scene
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@group(1) @binding(0)
+var<uniform> post: PostProcess;
+
+// upsampling only, the level of the downsample chain at the size being drawn
+@group(2) @binding(0)
+var t_level: texture_2d<f32>;
+@group(2) @binding(1)
+var s_level: sampler;
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+  return textureSampleLevel(t_source, s_source, uv, 0.0).rgb;
+}
+
+// the center and four diagonal taps, each one bilinear over 4 texels of the source
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+  let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+  var sum = sample_source(uv) * 4.0;
+  sum += sample_source(uv - texel);
+  sum += sample_source(uv + texel);
+  sum += sample_source(uv + vec2<f32>(texel.x, -texel.y));
+  sum += sample_source(uv - vec2<f32>(texel.x, -texel.y));
+
+  return sum / 8.0;
+}
+
+// keeps what is above the threshold, easing in over the knee below it
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+  let brightness = max(color.r, max(color.g, color.b));
+  let knee = post.bloom_knee;
+
+  var soft = clamp(brightness - post.bloom_threshold + knee, 0.0, 2.0 * knee);
+  soft = soft * soft / (4.0 * knee + 0.00001);
+  let contribution = max(soft, brightness - post.bloom_threshold) / max(brightness, 0.00001);
+
+  return color * max(contribution, 0.0);
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+  // a single very bright pixel would otherwise flicker as the camera moves
+  let color = min(downsample(in.uv), vec3<f32>(1000.0));
+
+  return vec4<f32>(threshold(color), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+  return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+  let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+  var sum = sample_source(in.uv + vec2<f32>(-texel.x * 2.0, 0.0));
+  sum += sample_source(in.uv + vec2<f32>(-texel.x, texel.y)) * 2.0;
+  sum += sample_source(in.uv + vec2<f32>(0.0, texel.y * 2.0));
+  sum += sample_source(in.uv + vec2<f32>(texel.x, texel.y)) * 2.0;
+  sum += sample_source(in.uv + vec2<f32>(texel.x * 2.0, 0.0));
+  sum += sample_source(in.uv + vec2<f32>(texel.x, -texel.y)) * 2.0;
+  sum += sample_source(in.uv + vec2<f32>(0.0, -texel.y * 2.0));
+  sum += sample_source(in.uv + vec2<f32>(-texel.x, -texel.y)) * 2.0;
+
+  let level = textureSampleLevel(t_level, s_level, in.uv, 0.0).rgb;
+
+  return vec4<f32>(sum / 12.0 + level, 1.0);
+}
@@ -0,0 +1,80 @@
+// Auto exposure: a histogram of the log luminance of the hdr scene, then its average which the
+// adapted luminance moves towards
+
+struct Params {
+  min_log_luminance: f32,
+  log_luminance_range: f32,
+  adaptation: f32,
+  pixel_count: u32,
+};
+
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+
+@group(1) @binding(0)
+var<storage, read_write> histogram: array<atomic<u32>, 256>;
+@group(1) @binding(1)
+var<storage, read_write> average_luminance: f32;
+@group(1) @binding(2)
+var<uniform> params: Params;
+
+var<workgroup> local_bins: array<atomic<u32>, 256>;
+var<workgroup> weighted: array<f32, 256>;
+
+// bin 0 is for pixels too dark to count, the rest cover the luminance range
+fn luminance_bin(color: vec3<f32>) -> u32 {
+  let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+  if (luminance < 0.0001) {
+    return 0u;
+  }
+
+  let log_luminance = clamp((log2(luminance) - params.min_log_luminance) / params.log_luminance_range, 0.0, 1.0);
+
+  return u32(log_luminance * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn build_histogram(
+  @builtin(global_invocation_id) id: vec3<u32>,
+  @builtin(local_invocation_index) index: u32,
+) {
+  atomicStore(&local_bins[index], 0u);
+  workgroupBarrier();
+
+  let size = textureDimensions(t_hdr);
+  if (id.x < size.x && id.y < size.y) {
+    let color = textureLoad(t_hdr, vec2<i32>(id.xy), 0).rgb;
+    atomicAdd(&local_bins[luminance_bin(color)], 1u);
+  }
+  workgroupBarrier();
+
+  atomicAdd(&histogram[index], atomicLoad(&local_bins[index]));
+}
+
+// a single workgroup, one invocation per bin. Clears the histogram for the next frame
+@compute @workgroup_size(256)
+fn average_histogram(@builtin(local_invocation_index) index: u32) {
+  let count = atomicExchange(&histogram[index], 0u);
+  weighted[index] = f32(count) * f32(index);
+  workgroupBarrier();
+
+  for (var stride = 128u; stride > 0u; stride = stride >> 1u) {
+    if (index < stride) {
+      weighted[index] += weighted[index + stride];
+    }
+    workgroupBarrier();
+  }
+
+  if (index == 0u) {
+    // `count` is the number of pixels in bin 0 here
+    let lit = f32(params.pixel_count) - f32(count);
+    var log_average = params.min_log_luminance;
+    if (lit > 0.0) {
+      let bin = weighted[0] / lit - 1.0;
+      log_average = bin / 254.0 * params.log_luminance_range + params.min_log_luminance;
+    }
+
+    let luminance = exp2(log_average);
+    average_luminance = average_luminance + (luminance - average_luminance) * params.adaptation;
+  }
+}
@@ -0,0 +1,21 @@
+// Shared by the full screen passes, prepended to the pass' own shader
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  // 0, 0 at the top left of the target
+  @location(0) uv: vec2<f32>,
+};
+
+// a single triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let x = f32((vertex_index << 1u) & 2u);
+  let y = f32(vertex_index & 2u);
+
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(x * 2.0 - 1.0, y * 2.0 - 1.0, 0.0, 1.0);
+  out.uv = vec2<f32>(x, 1.0 - y);
+
+  return out;
+}
+
@@ -0,0 +1,70 @@
+// FXAA, the edge direction is estimated from the luma of the four diagonal neighbours and the
+// pixel is blurred along it. Based on Timothy Lottes' original (pre 3.x) version
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@group(1) @binding(0)
+var<uniform> post: PostProcess;
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+  return textureSampleLevel(t_source, s_source, uv, 0.0).rgb;
+}
+
+// perceptual luma, the source is linear so it is roughly gamma encoded first
+fn luma(color: vec3<f32>) -> f32 {
+  return dot(sqrt(color), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let center = textureLoad(t_source, vec2<i32>(in.clip_position.xy), 0);
+  if (post.fxaa == 0u) {
+    return center;
+  }
+
+  let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+  let luma_nw = luma(sample_source(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+  let luma_ne = luma(sample_source(in.uv + vec2<f32>(1.0, -1.0) * texel));
+  let luma_sw = luma(sample_source(in.uv + vec2<f32>(-1.0, 1.0) * texel));
+  let luma_se = luma(sample_source(in.uv + vec2<f32>(1.0, 1.0) * texel));
+  let luma_m = luma(center.rgb);
+
+  let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+  let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+  // perpendicular to the luma gradient, so along the edge
+  var direction = vec2<f32>(
+    -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+    (luma_nw + luma_sw) - (luma_ne + luma_se),
+  );
+  let direction_reduce = max(
+    (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * post.fxaa_reduce_mul,
+    post.fxaa_reduce_min,
+  );
+  let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+  direction = clamp(
+    direction * inverse_direction_min,
+    vec2<f32>(-post.fxaa_span_max),
+    vec2<f32>(post.fxaa_span_max),
+  ) * texel;
+
+  let near = 0.5 * (
+    sample_source(in.uv + direction * (1.0 / 3.0 - 0.5))
+    + sample_source(in.uv + direction * (2.0 / 3.0 - 0.5))
+  );
+  let far = near * 0.5 + 0.25 * (
+    sample_source(in.uv + direction * -0.5)
+    + sample_source(in.uv + direction * 0.5)
+  );
+
+  // the wider blur crossed into another edge, keep the narrow one
+  let luma_far = luma(far);
+  if (luma_far < luma_min || luma_far > luma_max) {
+    return vec4<f32>(near, center.a);
+  }
+
+  return vec4<f32>(far, center.a);
+}
@@ -7,12 +7,29 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+const MAX_LIGHTS: u32 = 16u;
+
 struct Light {
   position: vec3<f32>,
+  kind: u32,
+  direction: vec3<f32>,
+  range: f32,
   color: vec3<f32>,
+  intensity: f32,
+  attenuation: vec3<f32>,
+  inner_cone_cos: f32,
+  outer_cone_cos: f32,
+  // -1 when the light doesn't cast shadows
+  shadow_index: i32,
+  shadow_view_projection: mat4x4<f32>,
+}
+
+struct Lights {
+  count: u32,
+  lights: array<Light, MAX_LIGHTS>,
 }
 @group(1) @binding(0)
-var<uniform> light: Light;
+var<uniform> lights: Lights;
 
 struct VertexInput {
   @location(0) position: vec3<f32>,
@@ -26,8 +43,11 @@ struct VertexOutput {
 @vertex
 fn vs_main(
   model: VertexInput,
+  // one instance is drawn per light
+  @builtin(instance_index) instance_index: u32,
 ) -> VertexOutput {
   let scale = 0.25;
+  let light = lights.lights[instance_index];
   var out: VertexOutput;
 
   out.clip_position = camera.view_projection * vec4<f32>(model.position * scale + light.position, 1.0);
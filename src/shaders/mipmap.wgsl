@@ -0,0 +1,42 @@
+// Renders one mip level from the level above it
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) tex_coords: vec2<f32>,
+};
+
+// a single triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let x = f32((vertex_index << 1u) & 2u);
+  let y = f32(vertex_index & 2u);
+
+  var out: VertexOutput;
+
+  out.clip_position = vec4<f32>(x * 2.0 - 1.0, y * 2.0 - 1.0, 0.0, 1.0);
+  out.tex_coords = vec2<f32>(x, 1.0 - y);
+
+  return out;
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+// sampling halfway between four texels with a linear sampler averages them
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSample(t_source, s_source, in.tex_coords);
+}
+
+// averaged normals get shorter, bring them back to unit length
+@fragment
+fn fs_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+  let texel = textureSample(t_source, s_source, in.tex_coords);
+  let normal = texel.xyz * 2.0 - 1.0;
+  let length = length(normal);
+  let renormalized = select(vec3<f32>(0.0, 0.0, 1.0), normal / length, length > 0.0);
+
+  return vec4<f32>(renormalized * 0.5 + 0.5, texel.a);
+}
@@ -0,0 +1,55 @@
+// Writes which instance and mesh covers each pixel, for picking on the gpu
+
+struct InstanceInput {
+  @location(5) model_matrix_0: vec4<f32>,
+  @location(6) model_matrix_1: vec4<f32>,
+  @location(7) model_matrix_2: vec4<f32>,
+  @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct CameraUniform {
+  view_position: vec4<f32>,
+  view_projection: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct MeshId {
+  index: u32,
+}
+@group(1) @binding(0)
+var<uniform> mesh_id: MeshId;
+
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+}
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) @interpolate(flat) instance: u32,
+}
+
+@vertex
+fn vs_main(
+  model: VertexInput,
+  instance: InstanceInput,
+  @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+
+  var out: VertexOutput;
+  out.clip_position = camera.view_projection * model_matrix * vec4<f32>(model.position, 1.0);
+  out.instance = instance_index;
+  return out;
+}
+
+// 0 is left for the background, so the instance is stored one higher
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec2<u32> {
+  return vec2<u32>(in.instance + 1u, mesh_id.index);
+}
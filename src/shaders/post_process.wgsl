@@ -0,0 +1,63 @@
+// Chromatic aberration, the vignette and color grading, over the tonemapped frame
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@group(1) @binding(0)
+var<uniform> post: PostProcess;
+@group(1) @binding(1)
+var t_lut: texture_3d<f32>;
+@group(1) @binding(2)
+var s_lut: sampler;
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+  let low = color * 12.92;
+  let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+  return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+  let low = color / 12.92;
+  let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+
+  return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// .cube LUTs map display encoded colors, the texture holds them that way too
+fn grade(color: vec3<f32>) -> vec3<f32> {
+  let encoded = linear_to_srgb(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)));
+  let domain = (encoded - post.lut_domain_min) / (post.lut_domain_max - post.lut_domain_min);
+  // the centers of the first and last texels are the ends of the table
+  let size = f32(textureDimensions(t_lut).x);
+  let uvw = clamp(domain, vec3<f32>(0.0), vec3<f32>(1.0)) * ((size - 1.0) / size) + 0.5 / size;
+
+  return srgb_to_linear(textureSampleLevel(t_lut, s_lut, uvw, 0.0).rgb);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  var color = textureLoad(t_source, vec2<i32>(in.clip_position.xy), 0).rgb;
+
+  // red and blue are pushed out from and pulled in towards the center
+  if (post.chromatic_aberration > 0.0) {
+    let offset = (in.uv - 0.5) * post.chromatic_aberration;
+    color.r = textureSampleLevel(t_source, s_source, in.uv + offset, 0.0).r;
+    color.b = textureSampleLevel(t_source, s_source, in.uv - offset, 0.0).b;
+  }
+
+  if (post.vignette != 0u) {
+    // 0 in the center and 1 in the corners
+    let distance = length(in.uv - 0.5) * sqrt(2.0);
+    let darkening = smoothstep(post.vignette_radius - post.vignette_smoothness, post.vignette_radius, distance);
+    color *= 1.0 - post.vignette_intensity * darkening;
+  }
+
+  if (post.lut_contribution > 0.0) {
+    color = mix(color, grade(color), post.lut_contribution);
+  }
+
+  return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,21 @@
+// The settings of every screen space effect, matches PostProcessUniform in render/postprocess.rs.
+// Prepended to the shaders of the passes that read them
+
+struct PostProcess {
+  bloom_threshold: f32,
+  bloom_knee: f32,
+  bloom_intensity: f32,
+  bloom: u32,
+  fxaa_span_max: f32,
+  fxaa_reduce_mul: f32,
+  fxaa_reduce_min: f32,
+  fxaa: u32,
+  vignette_intensity: f32,
+  vignette_radius: f32,
+  vignette_smoothness: f32,
+  vignette: u32,
+  lut_domain_min: vec3<f32>,
+  chromatic_aberration: f32,
+  lut_domain_max: vec3<f32>,
+  lut_contribution: f32,
+};
@@ -27,18 +27,12 @@ fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
   var out: VertexOutput;
-  var scale = 0.25;
   out.color = vec4<f32>(model.color);
-  // out.clip_position = vec4<f32>(model.position, 1.0);
-  var clip_pos = vec4<f32>(model.position, 1.0);
-  // out.clip_position = camera.view_projection * quad_model.model * vec4<f32>(model.position, 1.0);
 
+  // the vertices are in pixels from the quad's top left corner, the model moves them to
+  // where the quad is on screen and the ortho camera takes pixels to clip space
   var world_pos = quad_model.model * vec4<f32>(model.position, 1.0);
-  // var clip_pos = camera.view_projection * camera.view_position * world_pos;
-  var vp = camera.view_position * camera.view_projection;
-  // var clip_pos = world_pos * vp;
-
-  out.clip_position = clip_pos;
+  out.clip_position = camera.view_projection * world_pos;
 
   return out;
 }
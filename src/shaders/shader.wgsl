@@ -9,6 +9,9 @@ struct InstanceInput {
   @location(9) normal_matrix_0: vec3<f32>,
   @location(10) normal_matrix_1: vec3<f32>,
   @location(11) normal_matrix_2: vec3<f32>,
+  @location(12) tint: vec4<f32>,
+  // free for per-instance effects, the default shading doesn't look at it
+  @location(13) custom: vec4<f32>,
 }
 
 struct CameraUniform {
@@ -18,27 +21,80 @@ struct CameraUniform {
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+const MAX_LIGHTS: u32 = 16u;
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
 struct Light {
   position: vec3<f32>,
+  kind: u32,
+  direction: vec3<f32>,
+  range: f32,
   color: vec3<f32>,
+  intensity: f32,
+  attenuation: vec3<f32>,
+  inner_cone_cos: f32,
+  outer_cone_cos: f32,
+  // -1 when the light doesn't cast shadows
+  shadow_index: i32,
+  shadow_view_projection: mat4x4<f32>,
+}
+
+struct Lights {
+  count: u32,
+  lights: array<Light, MAX_LIGHTS>,
 }
 @group(2) @binding(0)
-var<uniform> light: Light;
+var<uniform> lights: Lights;
+
+// matches MAX_SHADOWS in light/shadow.rs, the layers of t_shadow
+const MAX_SHADOWS: i32 = 4;
+
+struct ShadowUniform {
+  depth_bias: f32,
+  normal_bias: f32,
+  pcf_radius: u32,
+  texel_size: f32,
+}
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+@group(2) @binding(3)
+var<uniform> shadow: ShadowUniform;
+
+// matches EnvironmentUniform in render/environment.rs, reflection_intensity is 0 without one
+struct Environment {
+  inverse_view_projection: mat4x4<f32>,
+  intensity: f32,
+  reflection_intensity: f32,
+  max_lod: f32,
+}
+@group(3) @binding(0)
+var<uniform> environment: Environment;
+@group(3) @binding(1)
+var t_environment: texture_cube<f32>;
+@group(3) @binding(2)
+var s_environment: sampler;
 
 struct VertexInput {
   @location(0) position: vec3<f32>,
   @location(1) tex_coords: vec2<f32>,
   @location(2) normal: vec3<f32>,
-  @location(3) tangent: vec3<f32>,
-  @location(4) bitangent: vec3<f32>,
+  // w is the handedness of the tangent space
+  @location(3) tangent: vec4<f32>,
 }
 
 struct VertexOutput {
   @builtin(position) clip_position: vec4<f32>,
   @location(0) tex_coords: vec2<f32>,
-  @location(1) tangent_position: vec3<f32>,
-  @location(2) tangent_light_position: vec3<f32>,
-  @location(3) tangent_view_position: vec3<f32>,
+  @location(1) world_position: vec3<f32>,
+  @location(2) world_normal: vec3<f32>,
+  @location(3) world_tangent: vec3<f32>,
+  @location(4) world_bitangent: vec3<f32>,
+  @location(5) tint: vec4<f32>,
+  @location(6) custom: vec4<f32>,
 };
 
 @vertex
@@ -59,63 +115,239 @@ fn vs_main(
     instance.normal_matrix_2,
   );
 
-  // Contruct the tangent matrix
-  let world_normal = normalize(normal_matrix * model.normal);
-  let world_tangent = normalize(normal_matrix * model.tangent);
-  let world_bitangent = normalize(normal_matrix * model.bitangent);
-  let tangent_matrix = transpose(mat3x3<f32>(
-    world_tangent,
-    world_bitangent,
-    world_normal,
-  ));
-
   let world_position = model_matrix * vec4<f32>(model.position, 1.0);
 
   var out: VertexOutput;
 
   out.clip_position = camera.view_projection * world_position;
   out.tex_coords = model.tex_coords;
-  out.tangent_position = tangent_matrix * world_position.xyz;
-  out.tangent_view_position = tangent_matrix * camera.view_position.zyx;
-  out.tangent_light_position = tangent_matrix * light.position;
+  out.world_position = world_position.xyz;
+  // lighting is done in world space since every light would need its own tangent space position
+  out.world_normal = normalize(normal_matrix * model.normal);
+  // tangents lie in the surface so they transform like positions, not like normals
+  out.world_tangent = normalize((model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz);
+  // MikkTSpace: the bitangent is rebuilt from the normal and tangent instead of being stored.
+  // A mirroring model matrix flips the cross product, the determinant's sign undoes that
+  let handedness = model.tangent.w * sign(determinant(normal_matrix));
+  out.world_bitangent = cross(out.world_normal, out.world_tangent) * handedness;
+  out.tint = instance.tint;
+  out.custom = instance.custom;
 
   return out;
 }
 
 // Fragment Shader
 
+struct MaterialUniform {
+  base_color_factor: vec4<f32>,
+  emissive_factor: vec3<f32>,
+  metallic_factor: f32,
+  roughness_factor: f32,
+  occlusion_strength: f32,
+  normal_scale: f32,
+}
+
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
-@group(0)@binding(1)
-var s_diffuse: sampler;
+var t_base_color: texture_2d<f32>;
+@group(0) @binding(1)
+var s_base_color: sampler;
 @group(0) @binding(2)
 var t_normal: texture_2d<f32>;
 @group(0) @binding(3)
 var s_normal: sampler;
+@group(0) @binding(4)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(5)
+var s_metallic_roughness: sampler;
+@group(0) @binding(6)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(7)
+var s_occlusion: sampler;
+@group(0) @binding(8)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(9)
+var s_emissive: sampler;
+@group(0) @binding(10)
+var<uniform> material: MaterialUniform;
+
+const PI: f32 = 3.14159265359;
+
+// GGX / Trowbridge-Reitz normal distribution
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+  let a = roughness * roughness;
+  let a2 = a * a;
+  let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+
+  return a2 / (PI * denom * denom);
+}
+
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+  let r = roughness + 1.0;
+  let k = (r * r) / 8.0;
+
+  return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+// Smith's method, shadowing from both the view and light direction
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+  return geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+  return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// rough surfaces don't get as bright at grazing angles
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+  return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// how much of the light reaches `world_position`, and from which direction
+struct LightSample {
+  direction: vec3<f32>,
+  radiance: vec3<f32>,
+}
+
+fn sample_light(light: Light, world_position: vec3<f32>) -> LightSample {
+  var out: LightSample;
+
+  if light.kind == LIGHT_DIRECTIONAL {
+    out.direction = -light.direction;
+    out.radiance = light.color * light.intensity;
+    return out;
+  }
+
+  let to_light = light.position - world_position;
+  let distance = length(to_light);
+  out.direction = to_light / max(distance, 0.0001);
+
+  var attenuation = 1.0 / max(
+    light.attenuation.x + light.attenuation.y * distance + light.attenuation.z * distance * distance,
+    0.0001,
+  );
+
+  // smoothly fade to zero at the range instead of cutting off
+  if light.range > 0.0 {
+    let falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+    attenuation *= falloff * falloff;
+  }
+
+  if light.kind == LIGHT_SPOT {
+    let cos_angle = dot(light.direction, -out.direction);
+    attenuation *= smoothstep(light.outer_cone_cos, light.inner_cone_cos, cos_angle);
+  }
+
+  out.radiance = light.color * light.intensity * attenuation;
+
+  return out;
+}
+
+// 1.0 when fully lit, 0.0 when fully in shadow. Averages a square of comparisons (PCF) so
+// the shadow edges are soft
+fn shadow_factor(light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+  if light.shadow_index < 0 || light.shadow_index >= MAX_SHADOWS {
+    return 1.0;
+  }
+
+  let biased_position = world_position + normal * shadow.normal_bias;
+  let light_position = light.shadow_view_projection * vec4<f32>(biased_position, 1.0);
+  let ndc = light_position.xyz / light_position.w;
+  // ndc y points up, texture coordinates point down
+  let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+  let depth = ndc.z - shadow.depth_bias;
+
+  // everything outside the light's frustum is lit
+  if light_position.w <= 0.0 || any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || depth > 1.0 {
+    return 1.0;
+  }
+
+  let radius = i32(shadow.pcf_radius);
+  var lit = 0.0;
+  for (var y = -radius; y <= radius; y += 1) {
+    for (var x = -radius; x <= radius; x += 1) {
+      let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+      // the Level variant can be called from non-uniform control flow
+      lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, light.shadow_index, depth);
+    }
+  }
+  let samples = f32((2 * radius + 1) * (2 * radius + 1));
+
+  return lit / samples;
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
   let tex_coords = vec2f(in.tex_coords.x, 1.0 - in.tex_coords.y);
-  let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, tex_coords);
+  let base_color = textureSample(t_base_color, s_base_color, tex_coords) * material.base_color_factor * in.tint;
   let object_normal: vec4<f32> = textureSample(t_normal, s_normal, tex_coords);
+  // roughness is stored in the green channel, metallic in the blue channel
+  let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, tex_coords);
+  let occlusion = textureSample(t_occlusion, s_occlusion, tex_coords).r;
+  let emissive = textureSample(t_emissive, s_emissive, tex_coords).rgb * material.emissive_factor;
+
+  let metallic = clamp(metallic_roughness.b * material.metallic_factor, 0.0, 1.0);
+  // very low roughness makes the highlight disappear between pixels
+  let roughness = clamp(metallic_roughness.g * material.roughness_factor, 0.04, 1.0);
+  let albedo = base_color.rgb;
+
+  let tangent_matrix = mat3x3<f32>(
+    normalize(in.world_tangent),
+    normalize(in.world_bitangent),
+    normalize(in.world_normal),
+  );
+  let scaled_normal = (object_normal.xyz * 2.0 - 1.0) * vec3<f32>(material.normal_scale, material.normal_scale, 1.0);
+  let normal = normalize(tangent_matrix * scaled_normal);
+  let view_direction = normalize(camera.view_position.xyz - in.world_position);
+  let n_dot_v = max(dot(normal, view_direction), 0.0001);
+
+  // dielectrics reflect about 4% head on, metals tint the reflection with their albedo
+  let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+  var direct_color = vec3<f32>(0.0);
+  var ambient_light = vec3<f32>(0.0);
+
+  for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i += 1u) {
+    let light = lights.lights[i];
+    let light_sample = sample_light(light, in.world_position);
+
+    let light_direction = light_sample.direction;
+    let half_direction = normalize(view_direction + light_direction);
+
+    let n_dot_l = max(dot(normal, light_direction), 0.0);
+    let n_dot_h = max(dot(normal, half_direction), 0.0);
+    let h_dot_v = max(dot(half_direction, view_direction), 0.0);
 
-  let tangent_normal = object_normal.xyz * 2.0 - 1.0;
-  let light_direction = normalize(in.tangent_light_position - in.tangent_position);
-  let view_direction = normalize(in.tangent_view_position - in.tangent_position);
+    // Cook-Torrance specular BRDF
+    let d = distribution_ggx(n_dot_h, roughness);
+    let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+    let f = fresnel_schlick(h_dot_v, f0);
+    let specular = (d * g * f) / (4.0 * n_dot_v * n_dot_l + 0.0001);
 
-  let half_direction = normalize(view_direction + light_direction);
+    // whatever isn't reflected is refracted, metals have no diffuse part
+    let k_diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic);
+    let diffuse = k_diffuse * albedo / PI;
 
-  let specular_strength = pow(max(dot(tangent_normal, half_direction), 0.0), 32.0);
+    // scaled so a white surface facing a light of intensity 1 ends up close to white
+    let radiance = light_sample.radiance * PI;
+    let shadow = shadow_factor(light, in.world_position, normalize(in.world_normal));
+    direct_color += (diffuse + specular) * radiance * n_dot_l * shadow;
 
-  let specular_color = specular_strength * light.color;
+    ambient_light += light.color * light.intensity;
+  }
 
   let ambient_strength = 0.1;
-  let ambient_color = light.color * ambient_strength;
+  let ambient_occlusion = mix(1.0, occlusion, material.occlusion_strength);
+  let ambient_color = ambient_light * ambient_strength * albedo * ambient_occlusion;
 
-  let diffuse_strength = max(dot(tangent_normal, light_direction), 0.0);
-  let diffuse_color = light.color * diffuse_strength;
+  // the environment seen in the mirror direction, blurrier further down the mips for
+  // rougher surfaces
+  let reflection_direction = reflect(-view_direction, normal);
+  let lod = roughness * environment.max_lod;
+  let environment_color = textureSampleLevel(t_environment, s_environment, reflection_direction, lod).rgb;
+  let environment_fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+  let reflection = environment_color * environment_fresnel * environment.reflection_intensity * ambient_occlusion;
 
-  let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
+  let result = ambient_color + direct_color + reflection + emissive;
 
-  return vec4<f32>(result, object_color.a);
+  return vec4<f32>(result, base_color.a);
 }
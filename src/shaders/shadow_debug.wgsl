@@ -0,0 +1,40 @@
+// Draws one layer of the shadow map into the current viewport
+
+// bound as an unfilterable float texture, GLSL can't textureLoad from depth textures
+@group(0) @binding(0)
+var t_shadow: texture_2d_array<f32>;
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) tex_coords: vec2<f32>,
+  // the instance index is used to pick the layer so no uniform is needed
+  @location(1) @interpolate(flat) layer: u32,
+};
+
+// a single triangle covering the whole viewport
+@vertex
+fn vs_main(
+  @builtin(vertex_index) vertex_index: u32,
+  @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+  let x = f32((vertex_index << 1u) & 2u);
+  let y = f32(vertex_index & 2u);
+
+  var out: VertexOutput;
+
+  // counter clockwise so it isn't culled
+  out.clip_position = vec4<f32>(x * 2.0 - 1.0, y * 2.0 - 1.0, 0.0, 1.0);
+  out.tex_coords = vec2<f32>(x, 1.0 - y);
+  out.layer = instance_index;
+
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let size = vec2<f32>(textureDimensions(t_shadow));
+  let coords = vec2<i32>(clamp(in.tex_coords * size, vec2<f32>(0.0), size - 1.0));
+  let depth = textureLoad(t_shadow, coords, i32(in.layer), 0).r;
+
+  return vec4<f32>(vec3<f32>(depth), 1.0);
+}
@@ -0,0 +1,44 @@
+// Draws the environment cubemap on the far plane, behind everything the opaque pass drew
+
+// matches EnvironmentUniform in render/environment.rs
+struct Environment {
+  inverse_view_projection: mat4x4<f32>,
+  intensity: f32,
+  reflection_intensity: f32,
+  max_lod: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> environment: Environment;
+@group(0) @binding(1)
+var t_environment: texture_cube<f32>;
+@group(0) @binding(2)
+var s_environment: sampler;
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) ndc: vec2<f32>,
+};
+
+// a single triangle covering the whole target, at depth 1 so only the cleared pixels pass
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  let x = f32((vertex_index << 1u) & 2u);
+  let y = f32(vertex_index & 2u);
+
+  var out: VertexOutput;
+  out.ndc = vec2<f32>(x * 2.0 - 1.0, y * 2.0 - 1.0);
+  out.clip_position = vec4<f32>(out.ndc, 1.0, 1.0);
+
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  // the view has no translation, so the point on the far plane is the direction from the camera
+  let far = environment.inverse_view_projection * vec4<f32>(in.ndc, 1.0, 1.0);
+  let direction = far.xyz / far.w;
+  let color = textureSampleLevel(t_environment, s_environment, direction, 0.0).rgb;
+
+  return vec4<f32>(color * environment.intensity, 1.0);
+}
@@ -0,0 +1,65 @@
+// Batched sprites, every instance is a quad in screen pixels
+
+struct CameraUniform {
+  view_position: vec4<f32>,
+  view_projection: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var s_atlas: sampler;
+
+struct SpriteInput {
+  @location(0) position: vec2<f32>,
+  @location(1) size: vec2<f32>,
+  @location(2) origin: vec2<f32>,
+  @location(3) rotation: f32,
+  @location(4) uv_min: vec2<f32>,
+  @location(5) uv_max: vec2<f32>,
+  @location(6) color: vec4<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+  @builtin(vertex_index) vertex_index: u32,
+  sprite: SpriteInput,
+) -> VertexOutput {
+  // two triangles, (0, 0) is the top left corner
+  var corners = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+  );
+  let corner = corners[vertex_index];
+
+  // y points down, so this turns clockwise on screen
+  let c = cos(sprite.rotation);
+  let s = sin(sprite.rotation);
+  let offset = (corner - sprite.origin) * sprite.size;
+  let rotated = vec2<f32>(offset.x * c - offset.y * s, offset.x * s + offset.y * c);
+  let pivot = sprite.position + sprite.origin * sprite.size;
+
+  var out: VertexOutput;
+  out.clip_position = camera.view_projection * vec4<f32>(pivot + rotated, 0.0, 1.0);
+  out.uv = mix(sprite.uv_min, sprite.uv_max, corner);
+  out.color = sprite.color;
+
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSample(t_atlas, s_atlas, in.uv) * in.color;
+}
@@ -0,0 +1,70 @@
+// Glyph quads from a single channel atlas, laid out in screen pixels
+
+struct CameraUniform {
+  view_position: vec4<f32>,
+  view_projection: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var t_glyphs: texture_2d<f32>;
+@group(1) @binding(1)
+var s_glyphs: sampler;
+
+// the same instances as sprites, glyphs are never rotated
+struct GlyphInput {
+  @location(0) position: vec2<f32>,
+  @location(1) size: vec2<f32>,
+  @location(4) uv_min: vec2<f32>,
+  @location(5) uv_max: vec2<f32>,
+  @location(6) color: vec4<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+  @builtin(vertex_index) vertex_index: u32,
+  glyph: GlyphInput,
+) -> VertexOutput {
+  var corners = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+  );
+  let corner = corners[vertex_index];
+
+  var out: VertexOutput;
+  out.clip_position = camera.view_projection * vec4<f32>(glyph.position + corner * glyph.size, 0.0, 1.0);
+  out.uv = mix(glyph.uv_min, glyph.uv_max, corner);
+  out.color = glyph.color;
+
+  return out;
+}
+
+// the atlas holds coverage
+@fragment
+fn fs_bitmap(in: VertexOutput) -> @location(0) vec4<f32> {
+  let coverage = textureSample(t_glyphs, s_glyphs, in.uv).r;
+
+  return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
+
+// the atlas holds the distance to the outline, 0.5 being on it. Smoothing over a screen pixel
+// keeps edges sharp at any size
+@fragment
+fn fs_sdf(in: VertexOutput) -> @location(0) vec4<f32> {
+  let distance = textureSample(t_glyphs, s_glyphs, in.uv).r;
+  let width = max(fwidth(distance) * 0.5, 0.001);
+  let coverage = smoothstep(0.5 - width, 0.5 + width, distance);
+
+  return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,124 @@
+// Maps the hdr scene into 0-1 with the chosen curve, after exposure. Bloom is added first
+
+struct Tonemap {
+  exposure: f32,
+  // 0 aces, 1 reinhard, 2 agx
+  tonemapper: u32,
+  auto_exposure: u32,
+  average_luminance: f32,
+};
+
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+
+@group(1) @binding(0)
+var<uniform> tonemap: Tonemap;
+
+// the top of the bloom chain, at half resolution
+@group(2) @binding(0)
+var t_bloom: texture_2d<f32>;
+@group(2) @binding(1)
+var s_bloom: sampler;
+
+// only the bloom settings are used here
+@group(3) @binding(0)
+var<uniform> post: PostProcess;
+
+// matches KEY_VALUE in render/tonemap.rs
+const KEY_VALUE: f32 = 0.18;
+
+fn rrt_and_odt_fit(v: vec3<f32>) -> vec3<f32> {
+  let a = v * (v + 0.0245786) - 0.000090537;
+  let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+
+  return a / b;
+}
+
+fn aces(color: vec3<f32>) -> vec3<f32> {
+  // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+  let input = mat3x3<f32>(
+    0.59719, 0.07600, 0.02840,
+    0.35458, 0.90834, 0.13383,
+    0.04823, 0.01566, 0.83777,
+  );
+  // ODT_SAT => XYZ => D60_2_D65 => sRGB
+  let output = mat3x3<f32>(
+    1.60475, -0.10208, -0.00327,
+    -0.53108, 1.10813, -0.07276,
+    -0.07367, -0.00605, 1.07602,
+  );
+
+  return clamp(output * rrt_and_odt_fit(input * color), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+  return color / (1.0 + color);
+}
+
+// 6th order polynomial fit of the AgX base contrast curve
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+  let x2 = x * x;
+  let x4 = x2 * x2;
+
+  return 15.5 * x4 * x2
+    - 40.14 * x4 * x
+    + 31.96 * x4
+    - 6.868 * x2 * x
+    + 0.4298 * x2
+    + 0.1191 * x
+    - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+  let inset = mat3x3<f32>(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+  );
+  let outset = mat3x3<f32>(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+  );
+  let min_ev = -12.47393;
+  let max_ev = 4.026069;
+
+  var x = inset * color;
+  x = clamp(log2(max(x, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+  x = (x - min_ev) / (max_ev - min_ev);
+  x = agx_contrast(x);
+  x = outset * x;
+
+  // the curve gives display encoded values, back to linear for the sRGB attachment
+  return pow(max(x, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  var hdr = textureLoad(t_hdr, vec2<i32>(in.clip_position.xy), 0).rgb;
+  if (post.bloom != 0u) {
+    let uv = in.clip_position.xy / vec2<f32>(textureDimensions(t_hdr));
+    hdr += textureSampleLevel(t_bloom, s_bloom, uv, 0.0).rgb * post.bloom_intensity;
+  }
+
+  var exposure = tonemap.exposure;
+  if (tonemap.auto_exposure != 0u) {
+    exposure *= KEY_VALUE / max(tonemap.average_luminance, 0.0001);
+  }
+
+  let color = max(hdr * exposure, vec3<f32>(0.0));
+  var mapped: vec3<f32>;
+  switch tonemap.tonemapper {
+    case 1u: {
+      mapped = reinhard(color);
+    }
+    case 2u: {
+      mapped = agx(color);
+    }
+    default: {
+      mapped = aces(color);
+    }
+  }
+
+  return vec4<f32>(mapped, 1.0);
+}
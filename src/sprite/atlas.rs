@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+
+use crate::texture::mipmap::MipmapGenerator;
+use crate::texture::{Texture, TextureOptions};
+
+// A rectangle of an atlas in pixels, from its top left corner
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SpriteRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl SpriteRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    // cell (column, row) of a sheet laid out in a grid of equally sized cells
+    pub fn from_grid(column: u32, row: u32, cell_width: f32, cell_height: f32) -> Self {
+        Self::new(column as f32 * cell_width, row as f32 * cell_height, cell_width, cell_height)
+    }
+}
+
+// A texture holding one or more sprites. Regions can be looked up by name or addressed
+// directly with a `SpriteRect`
+pub struct SpriteAtlas {
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+    pub width: u32,
+    pub height: u32,
+    pub regions: HashMap<String, SpriteRect>,
+}
+
+impl SpriteAtlas {
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, texture: Texture) -> Self {
+        let bind_group = Self::create_bind_group(device, layout, &texture);
+        let width = texture.texture.width();
+        let height = texture.texture.height();
+
+        Self {
+            texture,
+            bind_group,
+            width,
+            height,
+            regions: HashMap::new(),
+        }
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
+        layout: &wgpu::BindGroupLayout,
+        bytes: &[u8],
+        label: &str,
+        options: &TextureOptions,
+    ) -> Result<Self> {
+        let texture = Texture::from_bytes(device, queue, mipmaps, bytes, label, options)?;
+
+        Ok(Self::new(device, layout, texture))
+    }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("sprite_atlas_bind_group_layout"),
+        })
+    }
+
+    pub fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("sprite_atlas_bind_group"),
+        })
+    }
+
+    pub fn add_region(&mut self, name: impl Into<String>, rect: SpriteRect) {
+        self.regions.insert(name.into(), rect);
+    }
+
+    pub fn region(&self, name: &str) -> Option<SpriteRect> {
+        self.regions.get(name).copied()
+    }
+
+    // the whole texture
+    pub fn full_rect(&self) -> SpriteRect {
+        SpriteRect::new(0.0, 0.0, self.width as f32, self.height as f32)
+    }
+
+    // texture coordinates of the top left and bottom right corner of `rect`
+    pub fn uv_rect(&self, rect: &SpriteRect) -> ([f32; 2], [f32; 2]) {
+        uv_rect(rect, self.width, self.height)
+    }
+}
+
+// split out so it can be checked without a texture
+pub fn uv_rect(rect: &SpriteRect, width: u32, height: u32) -> ([f32; 2], [f32; 2]) {
+    let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+
+    (
+        [rect.x / width, rect.y / height],
+        [(rect.x + rect.width) / width, (rect.y + rect.height) / height],
+    )
+}
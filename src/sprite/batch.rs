@@ -0,0 +1,429 @@
+use std::ops::Range;
+
+use anyhow::*;
+
+use crate::sprite::{SpriteAtlas, SpriteRect};
+use crate::texture::mipmap::MipmapGenerator;
+use crate::texture::{Texture, TextureOptions};
+
+// A textured quad in screen pixels, y pointing down from the top left of the target
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Sprite {
+    // index of the atlas in the `SpriteBatch`
+    pub atlas: usize,
+    // part of the atlas to show, the whole atlas when None
+    pub source: Option<SpriteRect>,
+    // top left corner before rotating
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    // point the sprite rotates around, as a fraction of its size
+    pub origin: [f32; 2],
+    // radians, clockwise on screen
+    pub rotation: f32,
+    // multiplies the atlas color
+    pub color: [f32; 4],
+    // higher layers are drawn on top, sprites on the same layer in the order they were added
+    pub layer: i32,
+}
+
+// the whole of the first atlas, but without a size
+impl Default for Sprite {
+    fn default() -> Self {
+        Self::new(0, [0.0, 0.0], [0.0, 0.0])
+    }
+}
+
+impl Sprite {
+    pub fn new(atlas: usize, position: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            atlas,
+            source: None,
+            position,
+            size,
+            origin: [0.5, 0.5],
+            rotation: 0.0,
+            color: [1.0; 4],
+            layer: 0,
+        }
+    }
+
+    pub fn with_source(mut self, source: SpriteRect) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_origin(mut self, origin: [f32; 2]) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_rotation<R: Into<cgmath::Rad<f32>>>(mut self, rotation: R) -> Self {
+        self.rotation = rotation.into().0;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+}
+
+// one sprite as the shader sees it, the quad's corners are made up from the vertex index
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub origin: [f32; 2],
+    pub rotation: f32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl SpriteInstance {
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                // position
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // size
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // origin
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // rotation
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // uv_min
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // uv_max
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // color
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+// consecutive sprites sharing an atlas, drawn with a single call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteDraw {
+    pub atlas: usize,
+    pub instances: Range<u32>,
+}
+
+// Collects sprites and draws them with as few draw calls as possible. Sprites are sorted by
+// layer and then by atlas, so a frame with a handful of atlases only needs a handful of draws.
+// Sprites stay in the batch until `clear`, a scene that doesn't change doesn't need to be
+// pushed again every frame
+pub struct SpriteBatch {
+    pub pipeline: wgpu::RenderPipeline,
+    layout: wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    pub atlas_bind_group_layout: wgpu::BindGroupLayout,
+    pub atlases: Vec<SpriteAtlas>,
+    sprites: Vec<Sprite>,
+    pub buffer: wgpu::Buffer,
+    // how many sprites fit in `buffer`
+    capacity: usize,
+    draws: Vec<SpriteDraw>,
+    // the sprites changed since the last `prepare`
+    dirty: bool,
+}
+
+impl SpriteBatch {
+    pub const INITIAL_CAPACITY: usize = 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let atlas_bind_group_layout = SpriteAtlas::create_bind_group_layout(device);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &atlas_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &layout, color_format, sample_count);
+
+        Self {
+            pipeline,
+            layout,
+            color_format,
+            atlas_bind_group_layout,
+            atlases: Vec::new(),
+            sprites: Vec::new(),
+            buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            draws: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    // the pipeline has to match the sample count of the attachments it draws into
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(device, &self.layout, self.color_format, sample_count);
+    }
+
+    pub fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Buffer"),
+            size: (capacity * std::mem::size_of::<SpriteInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Sprites are drawn over the scene in the same pass, so they ignore its depth and don't
+    // write their own. Quads are never culled, a negative size mirrors the sprite
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/sprite.wgsl").into()),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprite Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[SpriteInstance::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    // returns the index to use as `Sprite::atlas`
+    pub fn add_atlas(&mut self, atlas: SpriteAtlas) -> usize {
+        self.atlases.push(atlas);
+        self.atlases.len() - 1
+    }
+
+    pub fn load_atlas(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
+        bytes: &[u8],
+        label: &str,
+        options: &TextureOptions,
+    ) -> Result<usize> {
+        let atlas = SpriteAtlas::from_bytes(device, queue, mipmaps, &self.atlas_bind_group_layout, bytes, label, options)?;
+
+        Ok(self.add_atlas(atlas))
+    }
+
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+        self.dirty = true;
+    }
+
+    pub fn extend(&mut self, sprites: impl IntoIterator<Item = Sprite>) {
+        self.sprites.extend(sprites);
+        self.dirty = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+        self.dirty = true;
+    }
+
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // the draw calls the last `prepare` came up with
+    pub fn draws(&self) -> &[SpriteDraw] {
+        &self.draws
+    }
+
+    // Sorts the sprites, uploads them and works out the draw calls. Does nothing when the
+    // sprites haven't changed since the last time
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let atlas_sizes = self.atlases.iter()
+            .map(|atlas| (atlas.width, atlas.height))
+            .collect::<Vec<_>>();
+        let (instances, draws) = batch_sprites(&self.sprites, &atlas_sizes);
+        self.draws = draws;
+
+        if instances.is_empty() {
+            return;
+        }
+
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().max(self.capacity * 2);
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&instances));
+    }
+}
+
+// Sorts `sprites` into draw order and groups them into draw calls. Sprites pointing at an
+// atlas that doesn't exist are skipped. `atlas_sizes` is the size of every atlas in pixels
+pub fn batch_sprites(sprites: &[Sprite], atlas_sizes: &[(u32, u32)]) -> (Vec<SpriteInstance>, Vec<SpriteDraw>) {
+    let mut order = sprites.iter()
+        .filter(|sprite| sprite.atlas < atlas_sizes.len())
+        .collect::<Vec<_>>();
+    // stable, so sprites with the same layer and atlas keep the order they were added in
+    order.sort_by_key(|sprite| (sprite.layer, sprite.atlas));
+
+    let mut instances = Vec::with_capacity(order.len());
+    let mut draws: Vec<SpriteDraw> = Vec::new();
+
+    for sprite in order {
+        let (width, height) = atlas_sizes[sprite.atlas];
+        let source = sprite.source
+            .unwrap_or_else(|| SpriteRect::new(0.0, 0.0, width as f32, height as f32));
+        let (uv_min, uv_max) = crate::sprite::uv_rect(&source, width, height);
+
+        let index = instances.len() as u32;
+        instances.push(SpriteInstance {
+            position: sprite.position,
+            size: sprite.size,
+            origin: sprite.origin,
+            rotation: sprite.rotation,
+            uv_min,
+            uv_max,
+            color: sprite.color,
+        });
+
+        match draws.last_mut() {
+            Some(draw) if draw.atlas == sprite.atlas => draw.instances.end = index + 1,
+            _ => draws.push(SpriteDraw {
+                atlas: sprite.atlas,
+                instances: index..index + 1,
+            }),
+        }
+    }
+
+    (instances, draws)
+}
+
+pub trait DrawSprites<'a> {
+    fn draw_sprites(
+        &mut self,
+        batch: &'a SpriteBatch,
+        camera_bind_group: &'a wgpu::BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawSprites<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_sprites(
+        &mut self,
+        batch: &'b SpriteBatch,
+        camera_bind_group: &'b wgpu::BindGroup,
+    ) {
+        if batch.draws.is_empty() {
+            return;
+        }
+
+        self.set_pipeline(&batch.pipeline);
+        self.set_vertex_buffer(0, batch.buffer.slice(..));
+        self.set_bind_group(0, camera_bind_group, &[]);
+
+        for draw in &batch.draws {
+            self.set_bind_group(1, &batch.atlases[draw.atlas].bind_group, &[]);
+            // two triangles per sprite
+            self.draw(0..6, draw.instances.clone());
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod atlas;
+pub mod batch;
+
+pub use atlas::{uv_rect, SpriteAtlas, SpriteRect};
+pub use batch::{batch_sprites, DrawSprites, Sprite, SpriteBatch, SpriteDraw, SpriteInstance};
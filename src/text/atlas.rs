@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font as _, GlyphId, PxScale};
+
+use crate::sprite::{SpriteAtlas, SpriteRect};
+use crate::text::Font;
+
+// how glyphs are stored in the atlas
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GlyphMode {
+    // coverage rasterized at every size text is drawn at, sharpest at small sizes
+    Bitmap,
+    // Signed distance to the outline, rasterized once at `size` and scaled to whatever size
+    // the text is drawn at. `spread` is how far from the outline the distance is kept, in
+    // pixels at `size`
+    Sdf { size: f32, spread: f32 },
+}
+
+impl GlyphMode {
+    pub fn sdf() -> Self {
+        Self::Sdf { size: 48.0, spread: 6.0 }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextOptions {
+    pub mode: GlyphMode,
+    // width and height of the atlas texture
+    pub atlas_size: u32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            mode: GlyphMode::Bitmap,
+            atlas_size: 1024,
+        }
+    }
+}
+
+impl TextOptions {
+    pub fn with_mode(mut self, mode: GlyphMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_atlas_size(mut self, atlas_size: u32) -> Self {
+        self.atlas_size = atlas_size;
+        self
+    }
+}
+
+// where a rasterized glyph is in the atlas and how to place it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasGlyph {
+    pub rect: SpriteRect,
+    // from the start of the glyph's baseline to the top left corner of `rect`, in pixels at
+    // the size it was rasterized at
+    pub offset: [f32; 2],
+    // the size `rect` was rasterized at
+    pub size: f32,
+}
+
+// glyphs are cached by id, and by size too for bitmaps
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    id: GlyphId,
+    // size in 1/64 pixels, 0 for distance fields
+    size: u32,
+}
+
+// Packs glyphs into a single channel texture as they are first used. Rows of glyphs are filled
+// left to right and a new row starts below the tallest glyph of the last one. Changes are kept
+// on the cpu until `upload`
+pub struct GlyphAtlas {
+    pub mode: GlyphMode,
+    pub texture: wgpu::Texture,
+    pub bind_group: wgpu::BindGroup,
+    pub size: u32,
+    pixels: Vec<u8>,
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    // top left corner of the next glyph and the height of the current row
+    cursor: (u32, u32),
+    row_height: u32,
+    dirty: bool,
+}
+
+impl GlyphAtlas {
+    // empty pixels between glyphs so filtering doesn't bleed into the neighbours
+    const PADDING: u32 = 1;
+
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, options: &TextOptions) -> Self {
+        let size = options.atlas_size;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("glyph_atlas_bind_group"),
+        });
+
+        Self {
+            mode: options.mode,
+            texture,
+            bind_group,
+            size,
+            pixels: vec![0; (size * size) as usize],
+            glyphs: HashMap::new(),
+            cursor: (Self::PADDING, Self::PADDING),
+            row_height: 0,
+            dirty: false,
+        }
+    }
+
+    // the same layout as sprite atlases, a filterable texture and its sampler
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        SpriteAtlas::create_bind_group_layout(device)
+    }
+
+    // Rasterizes the glyph the first time it is asked for. None for glyphs without an outline
+    // (spaces) and for glyphs that no longer fit
+    pub fn glyph(&mut self, font: &Font, id: GlyphId, size: f32) -> Option<AtlasGlyph> {
+        let key = match self.mode {
+            GlyphMode::Bitmap => GlyphKey { id, size: (size * 64.0).round() as u32 },
+            GlyphMode::Sdf { .. } => GlyphKey { id, size: 0 },
+        };
+
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let glyph = self.rasterize(font, id, size);
+        self.glyphs.insert(key, glyph);
+
+        glyph
+    }
+
+    fn rasterize(&mut self, font: &Font, id: GlyphId, size: f32) -> Option<AtlasGlyph> {
+        let (size, spread) = match self.mode {
+            GlyphMode::Bitmap => (size, 0),
+            GlyphMode::Sdf { size, spread } => (size, spread.ceil() as u32),
+        };
+
+        let outlined = font.font.outline_glyph(id.with_scale(PxScale::from(size)))?;
+        let bounds = outlined.px_bounds();
+        let width = bounds.width() as u32 + spread * 2;
+        let height = bounds.height() as u32 + spread * 2;
+
+        let mut coverage = vec![0; (width * height) as usize];
+        outlined.draw(|x, y, c| {
+            let index = (y + spread) * width + x + spread;
+            coverage[index as usize] = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        });
+        if spread > 0 {
+            coverage = signed_distance_field(&coverage, width, height, spread as f32);
+        }
+
+        let Some((x, y)) = self.allocate(width, height) else {
+            log::warn!("The glyph atlas is full, glyph {:?} at {}px is not drawn", id, size);
+            return None;
+        };
+        for row in 0..height {
+            let source = (row * width) as usize;
+            let destination = ((y + row) * self.size + x) as usize;
+            self.pixels[destination..destination + width as usize]
+                .copy_from_slice(&coverage[source..source + width as usize]);
+        }
+        self.dirty = true;
+
+        Some(AtlasGlyph {
+            rect: SpriteRect::new(x as f32, y as f32, width as f32, height as f32),
+            offset: [bounds.min.x - spread as f32, bounds.min.y - spread as f32],
+            size,
+        })
+    }
+
+    // finds room for a `width` x `height` glyph, returns its top left corner
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (mut x, mut y) = self.cursor;
+
+        if x + width + Self::PADDING > self.size {
+            x = Self::PADDING;
+            y += self.row_height + Self::PADDING;
+            self.row_height = 0;
+        }
+        if x + width + Self::PADDING > self.size || y + height + Self::PADDING > self.size {
+            return None;
+        }
+
+        self.cursor = (x + width + Self::PADDING, y);
+        self.row_height = self.row_height.max(height);
+
+        Some((x, y))
+    }
+
+    // forgets every glyph, they are rasterized again when they are next used
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.glyphs.clear();
+        self.cursor = (Self::PADDING, Self::PADDING);
+        self.row_height = 0;
+        self.dirty = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.values().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // writes the texture when glyphs were added since the last upload
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.size),
+                rows_per_image: Some(self.size),
+            },
+            wgpu::Extent3d {
+                width: self.size,
+                height: self.size,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+// Turns coverage into a distance field. 0.5 is the outline, values go up to 1 `spread` pixels
+// inside of it and down to 0 `spread` pixels outside. Partly covered pixels say how far the
+// outline is from their center, which keeps edges smooth when the field is scaled up. Brute
+// force, glyphs are small and only done once
+pub fn signed_distance_field(coverage: &[u8], width: u32, height: u32, spread: f32) -> Vec<u8> {
+    let (width, height) = (width as i32, height as i32);
+    let index = |x: i32, y: i32| (y * width + x) as usize;
+    // how far inside the outline a pixel's center is, negative outside
+    let edge_distance = |x: i32, y: i32| coverage[index(x, y)] as f32 / 255.0 - 0.5;
+    let inside = |x: i32, y: i32| coverage[index(x, y)] >= 128;
+
+    // pixels the outline passes through or runs along
+    let is_edge = |x: i32, y: i32| {
+        let c = coverage[index(x, y)];
+        (c > 0 && c < 255) || [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            nx >= 0 && ny >= 0 && nx < width && ny < height && inside(nx, ny) != inside(x, y)
+        })
+    };
+    let edges = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| is_edge(x, y))
+        .map(|(x, y)| (x, y, edge_distance(x, y)))
+        .collect::<Vec<_>>();
+
+    let mut field = Vec::with_capacity(coverage.len());
+    for y in 0..height {
+        for x in 0..width {
+            let is_inside = inside(x, y);
+            let mut closest = spread;
+
+            for &(ex, ey, edge) in &edges {
+                let (dx, dy) = ((ex - x) as f32, (ey - y) as f32);
+                if dx.abs() > spread + 1.0 || dy.abs() > spread + 1.0 {
+                    continue;
+                }
+                let to_edge = (dx * dx + dy * dy).sqrt();
+                let distance = if is_inside { to_edge + edge } else { to_edge - edge };
+                closest = closest.min(distance.max(0.0));
+            }
+
+            let signed = if is_inside { closest } else { -closest };
+            let value = (0.5 + signed / (2.0 * spread)).clamp(0.0, 1.0);
+            field.push((value * 255.0).round() as u8);
+        }
+    }
+
+    field
+}
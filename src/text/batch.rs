@@ -0,0 +1,261 @@
+use crate::sprite::SpriteInstance;
+use crate::text::{layout_text, Font, GlyphAtlas, GlyphMode, Text, TextOptions};
+use crate::texture::Texture;
+
+// Lays out and draws text with the ortho camera. Every glyph is a quad with the same layout as
+// a sprite, all of them drawn with a single call from one glyph atlas. Like sprites, text
+// stays in the batch until it is removed or the batch is cleared
+pub struct TextBatch {
+    pub pipeline: wgpu::RenderPipeline,
+    layout: wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    pub font: Font,
+    pub atlas: GlyphAtlas,
+    texts: Vec<Text>,
+    pub buffer: wgpu::Buffer,
+    // how many glyphs fit in `buffer`
+    capacity: usize,
+    // glyphs in `buffer`
+    glyph_count: u32,
+    // the text changed since the last `prepare`
+    dirty: bool,
+}
+
+impl TextBatch {
+    pub const INITIAL_CAPACITY: usize = 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        font: Font,
+        options: TextOptions,
+        sample_count: u32,
+    ) -> Self {
+        let atlas_bind_group_layout = GlyphAtlas::create_bind_group_layout(device);
+        let atlas = GlyphAtlas::new(device, &atlas_bind_group_layout, &options);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &atlas_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &layout, color_format, options.mode, sample_count);
+
+        Self {
+            pipeline,
+            layout,
+            color_format,
+            font,
+            atlas,
+            texts: Vec::new(),
+            buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            glyph_count: 0,
+            dirty: false,
+        }
+    }
+
+    // the pipeline has to match the sample count of the attachments it draws into
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(device, &self.layout, self.color_format, self.atlas.mode, sample_count);
+    }
+
+    pub fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Buffer"),
+            size: (capacity * std::mem::size_of::<SpriteInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // drawn over everything like sprites, the fragment stage depends on how glyphs are stored
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        color_format: wgpu::TextureFormat,
+        mode: GlyphMode,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/text.wgsl").into()),
+        });
+        let fragment_entry_point = match mode {
+            GlyphMode::Bitmap => "fs_bitmap",
+            GlyphMode::Sdf { .. } => "fs_sdf",
+        };
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[SpriteInstance::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: fragment_entry_point,
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    // returns the index to use with `set`
+    pub fn push(&mut self, text: Text) -> usize {
+        self.texts.push(text);
+        self.dirty = true;
+
+        self.texts.len() - 1
+    }
+
+    // replaces the text at `index`, for readouts that change every frame
+    pub fn set(&mut self, index: usize, text: Text) {
+        self.texts[index] = text;
+        self.dirty = true;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Text> {
+        self.texts.get(index)
+    }
+
+    // the text is assumed to change, it is laid out again either way
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Text> {
+        self.dirty = true;
+        self.texts.get_mut(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.texts.clear();
+        self.dirty = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // glyph quads drawn by the last `prepare`
+    pub fn glyph_count(&self) -> u32 {
+        self.glyph_count
+    }
+
+    // Lays out the text, rasterizes any glyphs the atlas doesn't have yet and uploads the quads.
+    // Does nothing when the text hasn't changed since the last time
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let mut instances = Vec::new();
+        for text in &self.texts {
+            let layout = layout_text(&self.font, text);
+
+            for glyph in &layout.glyphs {
+                let Some(atlas_glyph) = self.atlas.glyph(&self.font, glyph.id, text.size) else {
+                    continue;
+                };
+
+                let scale = text.size / atlas_glyph.size;
+                let [x, y] = glyph.position;
+                let mut position = [x + atlas_glyph.offset[0] * scale, y + atlas_glyph.offset[1] * scale];
+                // bitmaps are only sharp when their pixels line up with the screen's
+                if self.atlas.mode == GlyphMode::Bitmap {
+                    position = position.map(f32::round);
+                }
+                let (uv_min, uv_max) = crate::sprite::uv_rect(&atlas_glyph.rect, self.atlas.size, self.atlas.size);
+
+                instances.push(SpriteInstance {
+                    position,
+                    size: [atlas_glyph.rect.width * scale, atlas_glyph.rect.height * scale],
+                    origin: [0.0, 0.0],
+                    rotation: 0.0,
+                    uv_min,
+                    uv_max,
+                    color: text.color,
+                });
+            }
+        }
+
+        self.atlas.upload(queue);
+        self.glyph_count = instances.len() as u32;
+        if instances.is_empty() {
+            return;
+        }
+
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().max(self.capacity * 2);
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&instances));
+    }
+}
+
+pub trait DrawText<'a> {
+    fn draw_text(
+        &mut self,
+        batch: &'a TextBatch,
+        camera_bind_group: &'a wgpu::BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawText<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_text(
+        &mut self,
+        batch: &'b TextBatch,
+        camera_bind_group: &'b wgpu::BindGroup,
+    ) {
+        if batch.glyph_count == 0 {
+            return;
+        }
+
+        self.set_pipeline(&batch.pipeline);
+        self.set_vertex_buffer(0, batch.buffer.slice(..));
+        self.set_bind_group(0, camera_bind_group, &[]);
+        self.set_bind_group(1, &batch.atlas.bind_group, &[]);
+        // two triangles per glyph
+        self.draw(0..6, 0..batch.glyph_count);
+    }
+}
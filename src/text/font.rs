@@ -0,0 +1,63 @@
+use ab_glyph::{Font as _, FontArc, GlyphId, PxScale, ScaleFont};
+use anyhow::*;
+
+use crate::resources;
+
+// A parsed TrueType/OpenType font, cheap to clone
+#[derive(Clone)]
+pub struct Font {
+    pub font: FontArc,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let font = FontArc::try_from_vec(bytes)?;
+
+        Ok(Self { font })
+    }
+
+    // `file_name` is relative to the assets folder, like every other resource
+    pub async fn load(file_name: &str) -> Result<Self> {
+        let bytes = resources::load_binary(file_name).await?;
+
+        Self::from_bytes(bytes).with_context(|| format!("Unable to parse font {}", file_name))
+    }
+
+    pub fn glyph_id(&self, c: char) -> GlyphId {
+        self.font.glyph_id(c)
+    }
+
+    // `size` is the height of a line from the lowest descender to the highest ascender in pixels
+    pub fn metrics(&self, size: f32) -> FontMetrics {
+        let scaled = self.font.as_scaled(PxScale::from(size));
+
+        FontMetrics {
+            ascent: scaled.ascent(),
+            descent: scaled.descent(),
+            line_gap: scaled.line_gap(),
+        }
+    }
+
+    pub fn advance(&self, id: GlyphId, size: f32) -> f32 {
+        self.font.as_scaled(PxScale::from(size)).h_advance(id)
+    }
+
+    // added to the advance of `first` when it is followed by `second`, usually negative
+    pub fn kern(&self, first: GlyphId, second: GlyphId, size: f32) -> f32 {
+        self.font.as_scaled(PxScale::from(size)).kern(first, second)
+    }
+}
+
+// in pixels for a given size, the descent is negative
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
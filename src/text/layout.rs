@@ -0,0 +1,153 @@
+use ab_glyph::GlyphId;
+
+use crate::text::Font;
+
+// A string to draw, positioned by the top left corner of its first line in logical pixels
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Text {
+    pub content: String,
+    pub position: [f32; 2],
+    // height of a line from descender to ascender in pixels
+    pub size: f32,
+    pub color: [f32; 4],
+    // lines are wrapped between words once they would get wider than this
+    pub max_width: Option<f32>,
+    // multiplies the font's line height
+    pub line_spacing: f32,
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self::new("", [0.0, 0.0], 16.0)
+    }
+}
+
+impl Text {
+    pub fn new(content: impl Into<String>, position: [f32; 2], size: f32) -> Self {
+        Self {
+            content: content.into(),
+            position,
+            size,
+            color: [1.0; 4],
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PositionedGlyph {
+    pub id: GlyphId,
+    pub character: char,
+    // where the glyph's baseline starts, in the same space as `Text::position`
+    pub position: [f32; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    // only the glyphs that draw something, whitespace just moves the ones after it
+    pub glyphs: Vec<PositionedGlyph>,
+    pub lines: usize,
+    // from `Text::position` to the right of the widest line and the bottom of the last one
+    pub size: [f32; 2],
+}
+
+// Places every glyph of `text`. Pairs of glyphs are kerned, `\n` starts a new line and with a
+// `max_width` lines are broken between words. A word that doesn't fit on a line of its own is
+// broken wherever it runs out of room
+pub fn layout_text(font: &Font, text: &Text) -> TextLayout {
+    let metrics = font.metrics(text.size);
+    let line_height = metrics.line_height() * text.line_spacing;
+    let [origin_x, origin_y] = text.position;
+    let fits = |x: f32| text.max_width.map_or(true, |max_width| x <= max_width);
+
+    let mut glyphs = Vec::new();
+    let mut lines = 1;
+    let mut width: f32 = 0.0;
+    let mut y = 0.0;
+
+    for (index, paragraph) in text.content.split('\n').enumerate() {
+        if index > 0 {
+            lines += 1;
+            y += line_height;
+        }
+
+        let mut x = 0.0;
+        let mut previous: Option<GlyphId> = None;
+
+        for word in paragraph.split_inclusive(char::is_whitespace) {
+            // move the whole word down when it doesn't fit after what is already on the line
+            let visible = word.trim_end();
+            if x > 0.0 && !visible.is_empty() && !fits(x + measure(font, text.size, visible, previous)) {
+                lines += 1;
+                y += line_height;
+                x = 0.0;
+                previous = None;
+            }
+
+            for character in word.chars() {
+                let id = font.glyph_id(character);
+                if let Some(previous) = previous {
+                    x += font.kern(previous, id, text.size);
+                }
+
+                let advance = font.advance(id, text.size);
+                if !character.is_whitespace() {
+                    if x > 0.0 && !fits(x + advance) {
+                        lines += 1;
+                        y += line_height;
+                        x = 0.0;
+                    }
+
+                    glyphs.push(PositionedGlyph {
+                        id,
+                        character,
+                        position: [origin_x + x, origin_y + y + metrics.ascent],
+                    });
+                    width = width.max(x + advance);
+                }
+
+                x += advance;
+                previous = Some(id);
+            }
+        }
+    }
+
+    TextLayout {
+        glyphs,
+        lines,
+        size: [width, y + line_height],
+    }
+}
+
+// width of `word` when it follows `previous` on the same line
+fn measure(font: &Font, size: f32, word: &str, mut previous: Option<GlyphId>) -> f32 {
+    let mut width = 0.0;
+
+    for character in word.chars() {
+        let id = font.glyph_id(character);
+        if let Some(previous) = previous {
+            width += font.kern(previous, id, size);
+        }
+        width += font.advance(id, size);
+        previous = Some(id);
+    }
+
+    width
+}
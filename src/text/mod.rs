@@ -0,0 +1,9 @@
+pub mod atlas;
+pub mod batch;
+pub mod font;
+pub mod layout;
+
+pub use atlas::{signed_distance_field, AtlasGlyph, GlyphAtlas, GlyphMode, TextOptions};
+pub use batch::{DrawText, TextBatch};
+pub use font::{Font, FontMetrics};
+pub use layout::{layout_text, PositionedGlyph, Text, TextLayout};
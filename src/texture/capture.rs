@@ -0,0 +1,162 @@
+use anyhow::*;
+
+// copy_texture_to_buffer requires every row of the destination buffer to be a multiple of
+// wgpu::COPY_BYTES_PER_ROW_ALIGNMENT (256) bytes, so rows may have padding at the end
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded_bytes_per_row = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padding = (align - unpadded_bytes_per_row % align) % align;
+
+    unpadded_bytes_per_row + padding
+}
+
+// formats a texture can be captured from, every one of them stores 4 bytes per pixel
+fn is_bgra(format: wgpu::TextureFormat) -> Result<bool> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+        format => bail!("Unable to capture texture with format {:?}", format),
+    }
+}
+
+// Turns the padded rows copied out of a texture into an image. Surfaces are often BGRA, those
+// get their red and blue channels swapped. The bytes are kept as they are otherwise: an sRGB
+// format already stores sRGB encoded values, which is what png expects, and a non sRGB surface
+// is shown on screen exactly as it is stored
+pub fn image_from_padded_rows(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> Result<image::RgbaImage> {
+    let is_bgra = is_bgra(format)?;
+    let padded_bytes_per_row = padded_bytes_per_row(width) as usize;
+    let unpadded_bytes_per_row = (width * 4) as usize;
+
+    let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * height as usize);
+    for row in data.chunks(padded_bytes_per_row).take(height as usize) {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+    }
+
+    if is_bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    image::RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| anyhow!("Captured data does not match texture dimensions"))
+}
+
+// A copy of a texture on its way back to the cpu. The copy is submitted when it is created,
+// `try_finish` can then be checked once a frame without blocking (the only option on the web),
+// or `finish` waits for it
+pub struct TextureCapture {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    receiver: futures_channel::oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+impl TextureCapture {
+    // Copies the first mip level of `texture` into a buffer and starts mapping it. The texture
+    // needs to have been created with `TextureUsages::COPY_SRC`
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<Self> {
+        let width = texture.width();
+        let height = texture.height();
+        let format = texture.format();
+        is_bgra(format)?;
+
+        let padded_bytes_per_row = padded_bytes_per_row(width);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            format,
+            receiver,
+        })
+    }
+
+    fn read(&self) -> Result<image::RgbaImage> {
+        let image = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            image_from_padded_rows(&data, self.width, self.height, self.format)
+        };
+        self.buffer.unmap();
+
+        image
+    }
+
+    // None while the copy is still in flight. On native the device has to be polled for this
+    // to ever finish (`device.poll(wgpu::Maintain::Poll)`), on the web the browser does it
+    pub fn try_finish(&mut self) -> Option<Result<image::RgbaImage>> {
+        match self.receiver.try_recv() {
+            Result::Ok(None) => None,
+            Result::Ok(Some(Result::Ok(()))) => Some(self.read()),
+            Result::Ok(Some(Err(error))) => Some(Err(error.into())),
+            Err(error) => Some(Err(error.into())),
+        }
+    }
+
+    pub async fn finish(mut self, device: &wgpu::Device) -> Result<image::RgbaImage> {
+        // on native we have to poll the device for the map callback to run,
+        // on the web this is a no-op and the browser resolves the map for us
+        device.poll(wgpu::Maintain::Wait);
+        (&mut self.receiver).await??;
+
+        self.read()
+    }
+}
+
+// Copies the first mip level of `texture` back to the cpu. The texture needs to have been
+// created with `TextureUsages::COPY_SRC`
+pub async fn capture_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<image::RgbaImage> {
+    TextureCapture::new(device, queue, texture)?
+        .finish(device)
+        .await
+}
@@ -0,0 +1,222 @@
+use anyhow::*;
+use cgmath::{InnerSpace, Vector3};
+
+use crate::texture::mipmap;
+
+// The six faces of a cube texture, in the order wgpu lays out its layers: +x, -x, +y, -y, +z,
+// -z. Every face is `size` x `size` linear rgba colors, rows from the top, as seen from inside
+// the cube
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubemap {
+    pub size: u32,
+    pub faces: [Vec<[f32; 4]>; 6],
+}
+
+impl Cubemap {
+    // every face a single color
+    pub fn from_color(color: [f32; 4]) -> Self {
+        Self {
+            size: 1,
+            faces: std::array::from_fn(|_| vec![color]),
+        }
+    }
+
+    // Six square images of the same size, +x, -x, +y, -y, +z, -z. 8 bit images are sRGB encoded,
+    // float ones (like .hdr files) are already linear
+    pub fn from_faces(images: &[image::DynamicImage]) -> Result<Self> {
+        let [first, ..] = images else {
+            bail!("a cubemap needs 6 faces, found none");
+        };
+        if images.len() != 6 {
+            bail!("a cubemap needs 6 faces, found {}", images.len());
+        }
+
+        let size = first.width();
+        for (face, image) in images.iter().enumerate() {
+            if image.width() != size || image.height() != size {
+                bail!(
+                    "cubemap faces have to be square and the same size, face {} is {}x{} instead of {}x{}",
+                    face,
+                    image.width(),
+                    image.height(),
+                    size,
+                    size,
+                );
+            }
+        }
+
+        Ok(Self {
+            size,
+            faces: std::array::from_fn(|face| linear_pixels(&images[face])),
+        })
+    }
+
+    // Resamples a panorama covering every direction, longitude along x and latitude along y
+    // with straight up at the top, into `size` x `size` faces
+    pub fn from_equirectangular(image: &image::Rgba32FImage, size: u32) -> Self {
+        let size = size.max(1);
+        let faces = std::array::from_fn(|face| {
+            (0..size * size).map(|index| {
+                let u = (index % size) as f32 + 0.5;
+                let v = (index / size) as f32 + 0.5;
+                let direction = face_direction(face, u / size as f32, v / size as f32);
+
+                sample_equirectangular(image, direction)
+            }).collect()
+        });
+
+        Self { size, faces }
+    }
+
+    // Decodes a panorama from a Radiance .hdr file, or any format `image` reads as sRGB
+    pub fn equirectangular_from_bytes(bytes: &[u8]) -> Result<image::Rgba32FImage> {
+        if !bytes.starts_with(b"#?") {
+            let image = image::load_from_memory(bytes)?;
+            let pixels = linear_pixels(&image);
+
+            return image::Rgba32FImage::from_raw(image.width(), image.height(), pixels.concat())
+                .context("the decoded panorama doesn't match its size");
+        }
+
+        let decoder = image::codecs::hdr::HdrDecoder::new(std::io::Cursor::new(bytes))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?;
+        let rgba = pixels.iter().flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0]).collect();
+
+        image::Rgba32FImage::from_raw(metadata.width, metadata.height, rgba)
+            .context("the decoded panorama doesn't match its size")
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        mipmap::mip_level_count(self.size, self.size)
+    }
+
+    // Every level down to 1x1, the full size faces first. Each level is a 2x2 box filter of the
+    // one above it, so rough surfaces can sample a blurrier reflection further down
+    pub fn mip_levels(&self) -> Vec<[Vec<[f32; 4]>; 6]> {
+        let mut levels = vec![self.faces.clone()];
+        let mut size = self.size;
+
+        while size > 1 {
+            let next_size = size / 2;
+            let previous = levels.last().unwrap();
+            let next = std::array::from_fn(|face| downsample(&previous[face], size, next_size));
+            levels.push(next);
+            size = next_size;
+        }
+
+        levels
+    }
+}
+
+// The direction through `u`, `v` (0-1 from the top left) of a face, following the cube map
+// conventions every backend samples with
+pub fn face_direction(face: usize, u: f32, v: f32) -> Vector3<f32> {
+    let x = u * 2.0 - 1.0;
+    let y = v * 2.0 - 1.0;
+
+    let direction = match face {
+        0 => Vector3::new(1.0, -y, -x),
+        1 => Vector3::new(-1.0, -y, x),
+        2 => Vector3::new(x, 1.0, y),
+        3 => Vector3::new(x, -1.0, -y),
+        4 => Vector3::new(x, -y, 1.0),
+        _ => Vector3::new(-x, -y, -1.0),
+    };
+
+    direction.normalize()
+}
+
+// Bilinear lookup, wrapping around horizontally. u = 0.5 looks down +x and u = 0.75 down +z
+fn sample_equirectangular(image: &image::Rgba32FImage, direction: Vector3<f32>) -> [f32; 4] {
+    let (width, height) = image.dimensions();
+    let u = direction.z.atan2(direction.x) / std::f32::consts::TAU + 0.5;
+    let v = direction.y.clamp(-1.0, 1.0).acos() / std::f32::consts::PI;
+
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as u32).min(height - 1);
+        image.get_pixel(x, y).0
+    };
+    let [top_left, top_right, bottom_left, bottom_right] = [
+        texel(x0, y0),
+        texel(x0 + 1.0, y0),
+        texel(x0, y0 + 1.0),
+        texel(x0 + 1.0, y0 + 1.0),
+    ];
+
+    std::array::from_fn(|channel| {
+        let top = top_left[channel] + (top_right[channel] - top_left[channel]) * tx;
+        let bottom = bottom_left[channel] + (bottom_right[channel] - bottom_left[channel]) * tx;
+        top + (bottom - top) * ty
+    })
+}
+
+fn linear_pixels(image: &image::DynamicImage) -> Vec<[f32; 4]> {
+    match image {
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => {
+            image.to_rgba32f().pixels().map(|pixel| pixel.0).collect()
+        },
+        _ => image.to_rgba8().pixels().map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            [mipmap::srgb_to_linear(r), mipmap::srgb_to_linear(g), mipmap::srgb_to_linear(b), a as f32 / 255.0]
+        }).collect(),
+    }
+}
+
+fn downsample(face: &[[f32; 4]], size: u32, next_size: u32) -> Vec<[f32; 4]> {
+    (0..next_size * next_size).map(|index| {
+        let (x, y) = (index % next_size * 2, index / next_size * 2);
+        let texels = [(0, 0), (1, 0), (0, 1), (1, 1)]
+            .map(|(dx, dy)| face[((y + dy).min(size - 1) * size + (x + dx).min(size - 1)) as usize]);
+
+        std::array::from_fn(|channel| texels.iter().map(|texel| texel[channel]).sum::<f32>() / 4.0)
+    }).collect()
+}
+
+// Rounds to the nearest half float. Anything too large for one becomes the largest finite
+// value, so a bright sun can't turn into infinity (and NaNs further down the frame)
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7bff;
+    }
+    if exponent <= 0 {
+        // subnormal, or too small for a half float at all
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+
+        return sign | ((mantissa >> shift) + round) as u16;
+    }
+
+    let half = ((exponent as u32) << 10 | mantissa >> 13) + ((mantissa >> 12) & 1);
+
+    sign | half.min(0x7bff) as u16
+}
+
+// the pixels as Rgba16Float texels
+pub fn to_rgba16f(pixels: &[[f32; 4]]) -> Vec<u8> {
+    pixels.iter()
+        .flat_map(|pixel| pixel.map(f32_to_f16))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
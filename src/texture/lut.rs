@@ -0,0 +1,121 @@
+use anyhow::*;
+
+// A 3D color lookup table in the Adobe/Resolve .cube format. `data` has `size`^3 entries with
+// red changing fastest, then green, then blue, the same order a 3D texture is laid out in
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+    pub title: Option<String>,
+    pub size: u32,
+    // the input range the table covers, colors are scaled from it into 0-1 before the lookup
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    pub data: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    // WebGL2 only guarantees 3D textures up to 256 on a side
+    pub const MAX_SIZE: u32 = 256;
+
+    // maps every color to itself
+    pub fn identity(size: u32) -> Self {
+        let size = size.clamp(2, Self::MAX_SIZE);
+        let step = 1.0 / (size - 1) as f32;
+        let data = (0..size).flat_map(|b| {
+            (0..size).flat_map(move |g| {
+                (0..size).map(move |r| [r as f32 * step, g as f32 * step, b as f32 * step])
+            })
+        }).collect();
+
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            match keyword {
+                "TITLE" => title = Some(rest.trim_matches('"').to_string()),
+                "LUT_3D_SIZE" => {
+                    let parsed = rest.parse::<u32>()
+                        .with_context(|| format!("line {}: invalid LUT_3D_SIZE {:?}", line_number, rest))?;
+                    if !(2..=Self::MAX_SIZE).contains(&parsed) {
+                        bail!("line {}: LUT_3D_SIZE {} is outside 2-{}", line_number, parsed, Self::MAX_SIZE);
+                    }
+                    size = Some(parsed);
+                },
+                "LUT_1D_SIZE" => bail!("line {}: 1D LUTs aren't supported", line_number),
+                "DOMAIN_MIN" => domain_min = parse_triple(rest, line_number)?,
+                "DOMAIN_MAX" => domain_max = parse_triple(rest, line_number)?,
+                // the table itself, one "r g b" entry per line
+                _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                    data.push(parse_triple(line, line_number)?);
+                },
+                // other keywords (LUT_IN_VIDEO_RANGE etc) don't change the table
+                _ => log::warn!("Ignoring unknown .cube keyword {} on line {}", keyword, line_number),
+            }
+        }
+
+        let size = size.context("missing LUT_3D_SIZE")?;
+        let expected = (size * size * size) as usize;
+        if data.len() != expected {
+            bail!("expected {} entries for a size {} LUT, found {}", expected, size, data.len());
+        }
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            bail!("DOMAIN_MAX {:?} has to be above DOMAIN_MIN {:?}", domain_max, domain_min);
+        }
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+
+    pub async fn load(file_name: &str) -> Result<Self> {
+        let source = crate::resources::load_string(file_name).await?;
+
+        Self::parse(&source).with_context(|| format!("Unable to parse {}", file_name))
+    }
+
+    // the table as an Rgba8Unorm 3D texture
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.data.iter()
+            .flat_map(|[r, g, b]| {
+                let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [to_byte(*r), to_byte(*g), to_byte(*b), 255]
+            })
+            .collect()
+    }
+}
+
+fn parse_triple(text: &str, line_number: usize) -> Result<[f32; 3]> {
+    let values = text.split_whitespace()
+        .map(|value| value.parse::<f32>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("line {}: invalid number in {:?}", line_number, text))?;
+
+    match values[..] {
+        [r, g, b] => Ok([r, g, b]),
+        _ => bail!("line {}: expected 3 values, found {}", line_number, values.len()),
+    }
+}
@@ -0,0 +1,325 @@
+// Mip chain generation for loaded textures. Native backends render every level from the one
+// above it with a linear sampler (a blit), WebGL builds the levels on the cpu instead and
+// uploads them one by one
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// number of levels down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+pub fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Halves `img` with a 2x2 box filter. Color textures are averaged in linear space, normal maps
+// are decoded to vectors, averaged and renormalized so the lower levels stay unit length
+pub fn downsample(img: &image::RgbaImage, is_srgb: bool, is_normal_map: bool) -> image::RgbaImage {
+    let width = (img.width() / 2).max(1);
+    let height = (img.height() / 2).max(1);
+
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        // odd sizes drop the last row/column, the same as the gpu path
+        let texels = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+            let sx = (x * 2 + dx).min(img.width() - 1);
+            let sy = (y * 2 + dy).min(img.height() - 1);
+            img.get_pixel(sx, sy).0
+        });
+
+        let average = |channel: usize, decode: &dyn Fn(u8) -> f32| -> f32 {
+            texels.iter().map(|texel| decode(texel[channel])).sum::<f32>() / 4.0
+        };
+        let alpha = average(3, &|v| v as f32).round() as u8;
+
+        if is_normal_map {
+            let decode = |v: u8| v as f32 / 255.0 * 2.0 - 1.0;
+            let normal = cgmath::Vector3::new(average(0, &decode), average(1, &decode), average(2, &decode));
+            let length = cgmath::InnerSpace::magnitude(normal);
+            let normal = if length > 0.0 { normal / length } else { cgmath::Vector3::unit_z() };
+            let encode = |v: f32| ((v * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+            image::Rgba([encode(normal.x), encode(normal.y), encode(normal.z), alpha])
+        } else if is_srgb {
+            let [r, g, b] = [0, 1, 2].map(|channel| linear_to_srgb(average(channel, &srgb_to_linear)));
+
+            image::Rgba([r, g, b, alpha])
+        } else {
+            let [r, g, b] = [0, 1, 2].map(|channel| average(channel, &|v| v as f32).round() as u8);
+
+            image::Rgba([r, g, b, alpha])
+        }
+    })
+}
+
+// every level below the full size image, smallest last
+pub fn generate_mipmaps_cpu(img: &image::RgbaImage, is_srgb: bool, is_normal_map: bool) -> Vec<image::RgbaImage> {
+    let level_count = mip_level_count(img.width(), img.height());
+    let mut levels: Vec<image::RgbaImage> = Vec::with_capacity(level_count as usize - 1);
+
+    for _ in 1..level_count {
+        let previous = levels.last().unwrap_or(img);
+        let next = downsample(previous, is_srgb, is_normal_map);
+        levels.push(next);
+    }
+
+    levels
+}
+
+// upload the cpu generated levels, starting at mip level 1
+pub fn write_mipmaps_cpu(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    img: &image::RgbaImage,
+    is_srgb: bool,
+    is_normal_map: bool,
+) {
+    for (index, level) in generate_mipmaps_cpu(img, is_srgb, is_normal_map).iter().enumerate() {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: index as u32 + 1,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            level,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * level.width()),
+                rows_per_image: Some(level.height()),
+            },
+            wgpu::Extent3d {
+                width: level.width(),
+                height: level.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+// Renders every mip level of a texture from the level above it. The shader, layouts and
+// sampler are created once per device, the pipelines once per format and kind of texture, so
+// loading a model doesn't build them again for each of its textures
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+    // keyed by target format and whether it is a normal map
+    pipelines: RefCell<HashMap<(wgpu::TextureFormat, bool), wgpu::RenderPipeline>>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../shaders/mipmap.wgsl").into()
+            ),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("mipmap_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            pipelines: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // how many pipelines have been built so far
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.borrow().len()
+    }
+
+    // sampling and rendering an sRGB view converts to linear and back, so the filtering happens
+    // in linear space. Normal maps get renormalized instead
+    fn create_pipeline(&self, device: &wgpu::Device, format: wgpu::TextureFormat, is_normal_map: bool) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: if is_normal_map { "fs_normal" } else { "fs_main" },
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // Each level is rendered into its own scratch texture and copied into place, sampling one
+    // mip level of a texture while rendering into another isn't supported by every backend (GL)
+    pub fn generate(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        img: &image::RgbaImage,
+        is_normal_map: bool,
+    ) {
+        let mut pipelines = self.pipelines.borrow_mut();
+        let pipeline = pipelines.entry((texture.format(), is_normal_map))
+            .or_insert_with(|| self.create_pipeline(device, texture.format(), is_normal_map));
+
+        let create_level_texture = |width: u32, height: u32| device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Mip Level Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture.format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let mut source = create_level_texture(img.width(), img.height());
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &source,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            img,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * img.width()),
+                rows_per_image: Some(img.height()),
+            },
+            source.size(),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap Encoder"),
+        });
+
+        for target_mip in 1..texture.mip_level_count() {
+            let target = create_level_texture(
+                (texture.width() >> target_mip).max(1),
+                (texture.height() >> target_mip).max(1),
+            );
+            let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+            let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: None,
+            });
+
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Mipmap Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+
+            encoder.copy_texture_to_texture(
+                target.as_image_copy(),
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture,
+                    mip_level: target_mip,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                target.size(),
+            );
+
+            // the next level is rendered from this one
+            source = target;
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
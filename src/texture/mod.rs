@@ -1,3 +1,12 @@
 pub mod texture;
+pub mod capture;
+pub mod cubemap;
+pub mod lut;
+pub mod mipmap;
+pub mod options;
 
 pub use texture::Texture;
+pub use options::{ColorSpace, MipmapGeneration, TextureFiltering, TextureOptions};
+pub use capture::{capture_texture, TextureCapture};
+pub use lut::CubeLut;
+pub use cubemap::Cubemap;
@@ -0,0 +1,190 @@
+// How a texture is stored, sampled and filtered. Passed to `Texture::from_bytes`/`from_image`,
+// the glTF loader builds one from each texture's sampler
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    // color data, decoded to linear when sampled
+    Srgb,
+    // data that is used as is (normal, metallic-roughness, occlusion maps)
+    Linear,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MipmapGeneration {
+    // a single level, minification only uses `min_filter`
+    None,
+    // on the gpu, except on WebGL where the cpu path is used
+    Auto,
+    Gpu,
+    Cpu,
+}
+
+// shorthand for the usual filter combinations
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextureFiltering {
+    Nearest,
+    // linear within a mip level, nearest between levels
+    Bilinear,
+    // linear within and between mip levels
+    #[default]
+    Trilinear,
+    // trilinear plus anisotropic filtering with the given number of samples (1 to 16)
+    Anisotropic(u16),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextureOptions {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // max anisotropic samples, 1 turns it off. Only used when every filter is linear
+    pub anisotropy: u16,
+    pub color_space: ColorSpace,
+    pub mipmaps: MipmapGeneration,
+    // normal maps are renormalized when their mip levels are generated
+    pub is_normal_map: bool,
+    // added to TEXTURE_BINDING | COPY_DST, which every loaded texture needs
+    pub usage: wgpu::TextureUsages,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            color_space: ColorSpace::Srgb,
+            mipmaps: MipmapGeneration::Auto,
+            is_normal_map: false,
+            usage: wgpu::TextureUsages::empty(),
+        }
+    }
+}
+
+impl TextureOptions {
+    // for textures holding data rather than color
+    pub fn linear() -> Self {
+        Self {
+            color_space: ColorSpace::Linear,
+            ..Default::default()
+        }
+    }
+
+    pub fn normal_map() -> Self {
+        Self {
+            color_space: ColorSpace::Linear,
+            is_normal_map: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self.address_mode_w = address_mode;
+        self
+    }
+
+    pub fn with_filtering(mut self, filtering: TextureFiltering) -> Self {
+        let (filter, mipmap_filter, anisotropy) = match filtering {
+            TextureFiltering::Nearest => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest, 1),
+            TextureFiltering::Bilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest, 1),
+            TextureFiltering::Trilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, 1),
+            TextureFiltering::Anisotropic(samples) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, samples),
+        };
+
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self.mipmap_filter = mipmap_filter;
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: MipmapGeneration) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self.color_space {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    pub fn sampler_descriptor<'a>(&self, label: Option<&'a str>) -> wgpu::SamplerDescriptor<'a> {
+        let all_linear = self.mag_filter == wgpu::FilterMode::Linear
+            && self.min_filter == wgpu::FilterMode::Linear
+            && self.mipmap_filter == wgpu::FilterMode::Linear;
+        // wgpu rejects anisotropic samplers that aren't linear everywhere
+        let anisotropy_clamp = if all_linear { self.anisotropy.clamp(1, 16) } else { 1 };
+
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+
+    // Maps a glTF sampler onto the options, anything the file leaves out keeps the current value.
+    // The min filters without a mipmap part mean the texture isn't mipmapped
+    pub fn with_gltf_sampler(mut self, sampler: &gltf::texture::Sampler) -> Self {
+        use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+        let address_mode = |mode: WrappingMode| match mode {
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+        };
+
+        self.address_mode_u = address_mode(sampler.wrap_s());
+        self.address_mode_v = address_mode(sampler.wrap_t());
+
+        if let Some(mag_filter) = sampler.mag_filter() {
+            self.mag_filter = match mag_filter {
+                MagFilter::Nearest => wgpu::FilterMode::Nearest,
+                MagFilter::Linear => wgpu::FilterMode::Linear,
+            };
+        }
+
+        if let Some(min_filter) = sampler.min_filter() {
+            let (min_filter, mipmap_filter) = match min_filter {
+                MinFilter::Nearest => (wgpu::FilterMode::Nearest, None),
+                MinFilter::Linear => (wgpu::FilterMode::Linear, None),
+                MinFilter::NearestMipmapNearest => (wgpu::FilterMode::Nearest, Some(wgpu::FilterMode::Nearest)),
+                MinFilter::LinearMipmapNearest => (wgpu::FilterMode::Linear, Some(wgpu::FilterMode::Nearest)),
+                MinFilter::NearestMipmapLinear => (wgpu::FilterMode::Nearest, Some(wgpu::FilterMode::Linear)),
+                MinFilter::LinearMipmapLinear => (wgpu::FilterMode::Linear, Some(wgpu::FilterMode::Linear)),
+            };
+
+            self.min_filter = min_filter;
+            match mipmap_filter {
+                Some(mipmap_filter) => self.mipmap_filter = mipmap_filter,
+                None => {
+                    self.mipmap_filter = wgpu::FilterMode::Nearest;
+                    self.mipmaps = MipmapGeneration::None;
+                },
+            }
+        }
+
+        self
+    }
+}
@@ -1,6 +1,13 @@
-use image::GenericImageView;
 use anyhow::*;
 
+use crate::texture::cubemap::{self, Cubemap};
+use crate::texture::lut::CubeLut;
+use crate::texture::mipmap::{self, MipmapGenerator};
+use crate::texture::options::{ColorSpace, MipmapGeneration, TextureOptions};
+
+// WebGL can't always render into the texture formats we load, build the mip chain on the cpu there
+const GENERATE_MIPMAPS_ON_CPU: bool = cfg!(target_arch = "wasm32");
+
 #[derive(Debug)]
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -12,24 +19,74 @@ impl Texture {
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
         bytes: &[u8],
         label: &str,
-        is_normal_map: bool,
+        options: &TextureOptions,
     ) -> Result<Self> {
         let img = image::load_from_memory(bytes)?;
 
-        Self::from_image(device, queue, &img, Some(label), is_normal_map)
+        Self::from_image(device, queue, mipmaps, &img, Some(label), options)
+    }
+
+    // a 1x1 texture of a single color, used as a stand in for maps a material doesn't have
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        label: &str,
+        options: &TextureOptions,
+    ) -> Self {
+        let rgba = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+
+        // a single pixel has no levels below it
+        Self::from_rgba(device, queue, &rgba, Some(label), options, 1)
     }
 
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
         img: &image::DynamicImage,
         label: Option<&str>,
-        is_normal_map: bool,
+        options: &TextureOptions,
     ) -> Result<Self> {
         let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        let mip_level_count = match options.mipmaps {
+            MipmapGeneration::None => 1,
+            _ => mipmap::mip_level_count(rgba.width(), rgba.height()),
+        };
+
+        let texture = Self::from_rgba(device, queue, &rgba, label, options, mip_level_count);
+
+        if mip_level_count > 1 {
+            let is_srgb = options.color_space == ColorSpace::Srgb;
+            let on_cpu = match options.mipmaps {
+                MipmapGeneration::Cpu => true,
+                MipmapGeneration::Auto => GENERATE_MIPMAPS_ON_CPU,
+                _ => false,
+            };
+
+            if on_cpu {
+                mipmap::write_mipmaps_cpu(queue, &texture.texture, &rgba, is_srgb, options.is_normal_map);
+            } else {
+                mipmaps.generate(device, queue, &texture.texture, &rgba, options.is_normal_map);
+            }
+        }
+
+        Ok(texture)
+    }
+
+    // uploads the full size level, the ones below it are left to the caller
+    fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        label: Option<&str>,
+        options: &TextureOptions,
+        mip_level_count: u32,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -37,21 +94,15 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
-        let format = if is_normal_map {
-            wgpu::TextureFormat::Rgba8Unorm
-        } else {
-            wgpu::TextureFormat::Rgba8UnormSrgb
-        };
-
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                format: options.format(),
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | options.usage,
                 view_formats: &[],
             }
         );
@@ -63,7 +114,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -73,19 +124,9 @@ impl Texture {
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(
-            &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                ..Default::default()
-            }
-        );
+        let sampler = device.create_sampler(&options.sampler_descriptor(label));
 
-        Ok(Self { texture, view, sampler })
+        Self { texture, view, sampler }
     }
 
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
@@ -128,4 +169,208 @@ impl Texture {
 
         Self { texture, view, sampler }
     }
+
+    // square depth texture array with one layer per shadow map. `view` covers every layer for
+    // sampling, render into a single layer with `create_layer_view`
+    pub fn create_shadow_texture(device: &wgpu::Device, size: u32, layers: u32, label: &str) -> Self {
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler }
+    }
+
+    // a 3D texture sampled with the (display encoded) color as its coordinates, for color grading
+    pub fn from_cube_lut(device: &wgpu::Device, queue: &wgpu::Queue, lut: &CubeLut, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &lut.to_rgba8(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * lut.size),
+                rows_per_image: Some(lut.size),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // linear and in the hdr range, the same as the scene it is drawn behind
+    pub const CUBEMAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    // A cube texture with its whole mip chain, `view` samples it with a direction
+    pub fn from_cubemap(device: &wgpu::Device, queue: &wgpu::Queue, cubemap: &Cubemap, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: cubemap.size,
+                height: cubemap.size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: cubemap.mip_level_count(),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::CUBEMAP_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (mip_level, faces) in cubemap.mip_levels().iter().enumerate() {
+            let size = (cubemap.size >> mip_level).max(1);
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &cubemap::to_rgba16f(&faces.concat()),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(8 * size),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // six square images, +x, -x, +y, -y, +z, -z
+    pub fn from_cube_faces(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage],
+        label: &str,
+    ) -> Result<Self> {
+        let cubemap = Cubemap::from_faces(faces)?;
+        check_face_size(device, cubemap.size)?;
+
+        Ok(Self::from_cubemap(device, queue, &cubemap, label))
+    }
+
+    // an equirectangular panorama (.hdr, or an sRGB png/jpeg) resampled into `face_size` faces
+    pub fn from_equirectangular(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        face_size: u32,
+        label: &str,
+    ) -> Result<Self> {
+        // checked first, resampling into faces this large would take a while
+        check_face_size(device, face_size)?;
+        let panorama = Cubemap::equirectangular_from_bytes(bytes)?;
+        let cubemap = Cubemap::from_equirectangular(&panorama, face_size);
+
+        Ok(Self::from_cubemap(device, queue, &cubemap, label))
+    }
+
+    pub fn create_layer_view(&self, layer: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Texture Layer View"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+}
+
+// the faces are layers of a 2d texture, bound by its size limit
+fn check_face_size(device: &wgpu::Device, face_size: u32) -> Result<()> {
+    let max = device.limits().max_texture_dimension_2d;
+    if face_size == 0 || face_size > max {
+        bail!("cubemap faces have to be 1 to {} pixels wide, not {}", max, face_size);
+    }
+
+    Ok(())
 }
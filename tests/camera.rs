@@ -0,0 +1,122 @@
+use cgmath::*;
+use instant::Duration;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
+
+use wgpu_renderer::camera::{Camera, OrbitController, Projection};
+use wgpu_renderer::model::Aabb;
+
+const EPSILON: f32 = 1e-4;
+
+fn camera() -> Camera {
+    Camera::new((0.0, 5.0, 10.0), Deg(-90.0), Deg(-20.0))
+}
+
+fn projection() -> Projection {
+    Projection::new(800, 600, Deg(45.0), 0.1, 100.0)
+}
+
+#[test]
+fn aabb_union_and_transform() {
+    let bounds = Aabb::new([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+    assert!(Aabb::empty().is_empty());
+    assert_eq!(Aabb::empty().union(&bounds), bounds);
+    assert_eq!(bounds.union(&Aabb::empty()), bounds);
+
+    let moved = bounds.transform(&Matrix4::from_translation(Vector3::new(2.0, 0.0, 0.0)));
+    assert_eq!(moved, Aabb::new([1.0, -1.0, -1.0], [3.0, 1.0, 1.0]));
+
+    // a rotated box grows to contain its corners
+    let rotated = bounds.transform(&Matrix4::from_angle_y(Deg(45.0)));
+    assert!((rotated.max.x - 2.0f32.sqrt()).abs() < EPSILON);
+    assert!((rotated.max.y - 1.0).abs() < EPSILON);
+
+    let union = bounds.union(&moved);
+    assert_eq!(union, Aabb::new([-1.0, -1.0, -1.0], [3.0, 1.0, 1.0]));
+    assert_eq!(union.center(), Point3::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn attaching_keeps_the_camera_in_place() {
+    let mut camera = camera();
+    let position = camera.position;
+    let mut orbit = OrbitController::new(1.0, 0.01);
+
+    orbit.attach(&camera);
+    orbit.update_camera(&mut camera, Duration::ZERO);
+
+    assert!((camera.position - position).magnitude() < EPSILON);
+    assert!(((orbit.target - camera.position).magnitude() - orbit.distance).abs() < EPSILON);
+}
+
+#[test]
+fn rotating_keeps_the_distance_to_the_target() {
+    let mut camera = camera();
+    let mut orbit = OrbitController::new(1.0, 0.01);
+    orbit.attach(&camera);
+    let target = orbit.target;
+
+    orbit.process_mouse_button(MouseButton::Left, ElementState::Pressed);
+    orbit.process_mouse(120.0, -40.0);
+    orbit.update_camera(&mut camera, Duration::ZERO);
+
+    assert_eq!(orbit.target, target);
+    assert!(((target - camera.position).magnitude() - orbit.distance).abs() < EPSILON);
+    // still looking straight at the target
+    assert!(camera.forward().dot((target - camera.position).normalize()) > 1.0 - EPSILON);
+}
+
+#[test]
+fn panning_moves_the_target_across_the_screen() {
+    let mut camera = camera();
+    let mut orbit = OrbitController::new(1.0, 0.01);
+    orbit.attach(&camera);
+    let target = orbit.target;
+    let forward = camera.forward();
+
+    orbit.process_mouse_button(MouseButton::Right, ElementState::Pressed);
+    orbit.process_mouse(50.0, 0.0);
+    orbit.update_camera(&mut camera, Duration::ZERO);
+
+    let offset = orbit.target - target;
+    assert!(offset.magnitude() > 0.0);
+    assert!(offset.dot(forward).abs() < EPSILON);
+    // dragging right moves the scene right, so the target goes left
+    assert!(offset.dot(forward.cross(Vector3::unit_y())) < 0.0);
+    assert_eq!(camera.forward(), forward);
+}
+
+#[test]
+fn scrolling_dollies_within_the_limits() {
+    let mut camera = camera();
+    let mut orbit = OrbitController::new(1.0, 0.01);
+    orbit.attach(&camera);
+    let distance = orbit.distance;
+
+    orbit.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+    orbit.update_camera(&mut camera, Duration::ZERO);
+    assert!(orbit.distance < distance);
+
+    orbit.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1000.0));
+    orbit.update_camera(&mut camera, Duration::ZERO);
+    assert_eq!(orbit.distance, orbit.min_distance);
+}
+
+#[test]
+fn framing_fits_the_bounds_in_view() {
+    let mut camera = camera();
+    let projection = projection();
+    let mut orbit = OrbitController::new(1.0, 0.01);
+    let bounds = Aabb::new([2.0, -1.0, -3.0], [6.0, 3.0, 1.0]);
+
+    orbit.frame(&mut camera, &projection, &bounds);
+
+    assert_eq!(orbit.target, bounds.center());
+    let view_projection = projection.calc_matrix() * camera.calc_matrix();
+    for corner in bounds.corners() {
+        let clip = view_projection * corner.to_homogeneous();
+        let ndc = clip.truncate() / clip.w;
+
+        assert!(clip.w > 0.0, "{:?} is behind the camera", corner);
+        assert!(ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0, "{:?} is outside the view: {:?}", corner, ndc);
+    }
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use wgpu_renderer::texture::capture::{image_from_padded_rows, padded_bytes_per_row};
+use wgpu_renderer::texture::TextureCapture;
+
+// rows of `width` pixels where every pixel is [x, y, 2, 255], padded like a texture copy
+fn padded_rows(width: u32, height: u32) -> Vec<u8> {
+    let padded_bytes_per_row = padded_bytes_per_row(width) as usize;
+    let mut data = vec![0xAA; padded_bytes_per_row * height as usize];
+
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let offset = y * padded_bytes_per_row + x * 4;
+            data[offset..offset + 4].copy_from_slice(&[x as u8, y as u8, 2, 255]);
+        }
+    }
+
+    data
+}
+
+#[test]
+fn rows_are_padded_to_256_bytes() {
+    assert_eq!(padded_bytes_per_row(64), 256);
+    assert_eq!(padded_bytes_per_row(65), 512);
+    assert_eq!(padded_bytes_per_row(3), 256);
+}
+
+#[test]
+fn padding_is_removed() {
+    let image = image_from_padded_rows(&padded_rows(3, 2), 3, 2, wgpu::TextureFormat::Rgba8UnormSrgb).unwrap();
+
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(image.get_pixel(2, 1).0, [2, 1, 2, 255]);
+}
+
+#[test]
+fn bgra_is_swizzled_to_rgba() {
+    let image = image_from_padded_rows(&padded_rows(3, 2), 3, 2, wgpu::TextureFormat::Bgra8Unorm).unwrap();
+
+    assert_eq!(image.get_pixel(2, 1).0, [2, 1, 2, 255]);
+    assert_eq!(image.get_pixel(1, 0).0, [2, 0, 1, 255]);
+}
+
+#[test]
+fn unsupported_formats_are_rejected() {
+    assert!(image_from_padded_rows(&padded_rows(3, 2), 3, 2, wgpu::TextureFormat::Rgba16Float).is_err());
+}
+
+#[test]
+fn captures_bgra_texture_with_unaligned_width() {
+    let Some((device, queue)) = common::device() else {
+        return;
+    };
+
+    // 70 * 4 bytes isn't a multiple of 256, so the copy has padding on every row
+    let (width, height) = (70, 5);
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Capture Test Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Bgra8UnormSrgb,
+        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    // stored as [b, g, r, a]
+    let bgra = (0..width * height)
+        .flat_map(|i| [(i % width) as u8, (i / width) as u8, 200, 255])
+        .collect::<Vec<_>>();
+    queue.write_texture(
+        texture.as_image_copy(),
+        &bgra,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: Some(height),
+        },
+        texture.size(),
+    );
+
+    let image = pollster::block_on(TextureCapture::new(&device, &queue, &texture).unwrap().finish(&device)).unwrap();
+
+    assert_eq!(image.dimensions(), (width, height));
+    assert_eq!(image.get_pixel(69, 4).0, [200, 4, 69, 255]);
+    assert_eq!(image.get_pixel(0, 0).0, [200, 0, 0, 255]);
+}
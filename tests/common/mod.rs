@@ -0,0 +1,88 @@
+// Helpers shared by the integration tests. Every test binary compiles its own copy and only
+// uses some of them
+#![allow(dead_code)]
+
+use std::io::Write;
+
+use wgpu_renderer::app::{HeadlessApp, HeadlessOptions};
+
+// Run with `REQUIRE_ADAPTER=1` (as CI should) to fail the gpu tests instead of skipping them
+// when there is no software adapter
+fn require_adapter() -> bool {
+    std::env::var("REQUIRE_ADAPTER").map(|v| v == "1").unwrap_or(false)
+}
+
+// Reports a test that returns early without checking anything. Written straight to stderr
+// since the test harness only shows captured output of failing tests
+pub fn skip(reason: &str) {
+    let thread = std::thread::current();
+    let test = thread.name().unwrap_or("test");
+    let _ = writeln!(std::io::stderr(), "SKIPPED {}: {}", test, reason);
+}
+
+fn no_adapter(error: impl std::fmt::Debug) {
+    if require_adapter() {
+        panic!("No software adapter available: {:?}", error);
+    }
+
+    skip(&format!("no software adapter available: {:?}", error));
+}
+
+// A `width` x `height` app on the software adapter, None when this machine doesn't have one
+pub fn headless_app(width: u32, height: u32) -> Option<HeadlessApp> {
+    headless_app_with(HeadlessOptions {
+        width,
+        height,
+        ..Default::default()
+    })
+}
+
+pub fn headless_app_with(options: HeadlessOptions) -> Option<HeadlessApp> {
+    let app = pollster::block_on(HeadlessApp::new(HeadlessOptions {
+        force_fallback_adapter: true,
+        ..options
+    }));
+
+    match app {
+        Ok(app) => Some(app),
+        Err(e) => {
+            no_adapter(e);
+            None
+        },
+    }
+}
+
+// a bare device on the software adapter, for tests that don't need a whole app
+pub fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        dx12_shader_compiler: Default::default(),
+    });
+
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: None,
+        force_fallback_adapter: true,
+    }));
+    let Some(adapter) = adapter else {
+        no_adapter("no adapter found");
+        return None;
+    };
+
+    let device = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            features: wgpu::Features::empty(),
+            limits: adapter.limits(),
+            label: None,
+        },
+        None,
+    ));
+
+    match device {
+        Ok(device) => Some(device),
+        Err(e) => {
+            no_adapter(e);
+            None
+        },
+    }
+}
@@ -0,0 +1,84 @@
+use cgmath::*;
+
+use wgpu_renderer::camera::{Camera, Frustum, Projection};
+use wgpu_renderer::model::{Aabb, BoundingSphere};
+use wgpu_renderer::render::culling::visible_instance_ranges;
+
+// at the origin looking down -z
+fn frustum() -> Frustum {
+    let camera = Camera::new((0.0, 0.0, 0.0), Deg(-90.0), Deg(0.0));
+    let projection = Projection::new(800, 600, Deg(45.0), 0.1, 100.0);
+
+    Frustum::from_matrix(projection.calc_matrix() * camera.calc_matrix())
+}
+
+fn unit_box() -> (Aabb, BoundingSphere) {
+    let bounds = Aabb::new([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5]);
+    (bounds, BoundingSphere::new(bounds.center(), bounds.radius()))
+}
+
+#[test]
+fn spheres_inside_and_outside_the_view() {
+    let frustum = frustum();
+
+    assert!(frustum.intersects_sphere(&BoundingSphere::new([0.0, 0.0, -10.0], 1.0)));
+    // behind the camera
+    assert!(!frustum.intersects_sphere(&BoundingSphere::new([0.0, 0.0, 10.0], 1.0)));
+    // past the far plane, and straddling it
+    assert!(!frustum.intersects_sphere(&BoundingSphere::new([0.0, 0.0, -120.0], 1.0)));
+    assert!(frustum.intersects_sphere(&BoundingSphere::new([0.0, 0.0, -100.5], 1.0)));
+    // off to the side, and just touching the left plane
+    assert!(!frustum.intersects_sphere(&BoundingSphere::new([-20.0, 0.0, -10.0], 1.0)));
+    assert!(frustum.intersects_sphere(&BoundingSphere::new([-6.0, 0.0, -10.0], 1.5)));
+}
+
+#[test]
+fn planes_face_inward_and_are_normalized() {
+    let frustum = frustum();
+    let inside = Point3::new(0.0, 0.0, -10.0);
+
+    for plane in frustum.planes {
+        assert!((plane.normal.magnitude() - 1.0).abs() < 1e-4);
+        assert!(plane.signed_distance(inside) > 0.0);
+    }
+
+    // the near and far planes are at their distances from the camera
+    let near = frustum.planes[4];
+    let far = frustum.planes[5];
+    assert!((near.signed_distance(Point3::new(0.0, 0.0, -0.1))).abs() < 1e-3);
+    assert!((far.signed_distance(Point3::new(0.0, 0.0, -100.0))).abs() < 1e-2);
+}
+
+#[test]
+fn boxes_are_tested_after_the_transform() {
+    let frustum = frustum();
+    let (bounds, sphere) = unit_box();
+
+    let ahead = Matrix4::from_translation(Vector3::new(0.0, 0.0, -5.0));
+    let behind = Matrix4::from_translation(Vector3::new(0.0, 0.0, 5.0));
+    assert!(frustum.intersects_bounds(&bounds, &sphere, &ahead));
+    assert!(!frustum.intersects_bounds(&bounds, &sphere, &behind));
+
+    // scaling a box up that is just outside the view brings it back in
+    let beside = Matrix4::from_translation(Vector3::new(6.0, 0.0, -5.0));
+    assert!(!frustum.intersects_bounds(&bounds, &sphere, &beside));
+    assert!(frustum.intersects_bounds(&bounds, &sphere, &(beside * Matrix4::from_scale(8.0))));
+
+    assert!(!frustum.intersects_aabb(&Aabb::empty()));
+}
+
+#[test]
+fn visible_instances_are_grouped_into_ranges() {
+    let frustum = frustum();
+    let (bounds, sphere) = unit_box();
+
+    let z = [-5.0, -6.0, 5.0, -7.0, 6.0, 7.0, -8.0, -9.0];
+    let transforms = z.iter()
+        .map(|&z| Matrix4::from_translation(Vector3::new(0.0, 0.0, z)))
+        .collect::<Vec<_>>();
+
+    let ranges = visible_instance_ranges(&frustum, &bounds, &sphere, &transforms);
+    assert_eq!(ranges, vec![0..2, 3..4, 6..8]);
+
+    assert!(visible_instance_ranges(&frustum, &bounds, &sphere, &[]).is_empty());
+}
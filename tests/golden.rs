@@ -0,0 +1,375 @@
+// Golden image tests. Each test renders a known scene offscreen on a software adapter and
+// compares it against a reference png in `tests/golden`.
+//
+// Run with `UPDATE_GOLDEN=1 cargo test --test golden` to (re)write the references after an
+// intentional change to the output, a missing reference fails the test otherwise. When a
+// comparison fails a diff image is written next to the rendered frame in cargo's test tmp dir
+// and the path is printed in the failure message.
+mod common;
+
+use std::path::PathBuf;
+
+use cgmath::{One, Rotation3};
+
+use wgpu_renderer::app::HeadlessApp;
+use wgpu_renderer::instance::Instance;
+use wgpu_renderer::light::Light;
+use wgpu_renderer::model::Transform;
+use wgpu_renderer::scene::Scene;
+use wgpu_renderer::sprite::{Sprite, SpriteRect};
+use wgpu_renderer::text::{GlyphMode, Text, TextBatch, TextOptions};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+// max difference of a single channel before a pixel is counted as different
+const CHANNEL_TOLERANCE: u8 = 8;
+// fraction of the image that is allowed to differ, software rasterizers
+// don't always agree on edge pixels
+const MAX_DIFFERENT_PIXELS: f32 = 0.005;
+
+struct ImageComparison {
+    different_pixels: u32,
+    diff: image::RgbaImage,
+}
+
+fn compare_images(reference: &image::RgbaImage, actual: &image::RgbaImage) -> ImageComparison {
+    let mut different_pixels = 0;
+    let diff = image::RgbaImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let expected = reference.get_pixel(x, y);
+        let pixel = actual.get_pixel(x, y);
+        let is_different = expected.0.iter()
+            .zip(pixel.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE);
+
+        if is_different {
+            different_pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            // faded copy of the reference so the differences stand out
+            let [r, g, b, _] = expected.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+            image::Rgba([luma, luma, luma, 255])
+        }
+    });
+
+    ImageComparison { different_pixels, diff }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn output_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("golden")
+        .join(format!("{}-{}.png", name, suffix))
+}
+
+fn should_update() -> bool {
+    std::env::var("UPDATE_GOLDEN").map(|v| v == "1").unwrap_or(false)
+}
+
+fn assert_golden(name: &str, actual: &image::RgbaImage) {
+    let reference_path = golden_path(name);
+
+    if should_update() {
+        std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        actual.save(&reference_path).unwrap();
+        eprintln!("Wrote golden image {:?}", reference_path);
+        return;
+    }
+
+    assert!(
+        reference_path.exists(),
+        "{} has no golden image at {:?}, run with UPDATE_GOLDEN=1 to write it",
+        name,
+        reference_path,
+    );
+
+    let reference = image::open(&reference_path)
+        .expect("Unable to open golden image")
+        .to_rgba8();
+
+    assert_eq!(
+        reference.dimensions(),
+        actual.dimensions(),
+        "{} has different dimensions than its golden image",
+        name,
+    );
+
+    let ImageComparison { different_pixels, diff } = compare_images(&reference, actual);
+    let allowed = (reference.width() * reference.height()) as f32 * MAX_DIFFERENT_PIXELS;
+
+    if different_pixels as f32 > allowed {
+        let actual_path = output_path(name, "actual");
+        let diff_path = output_path(name, "diff");
+        std::fs::create_dir_all(actual_path.parent().unwrap()).unwrap();
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+
+        panic!(
+            "{} differs from {:?} in {} pixels (allowed {}). Rendered: {:?} Diff: {:?}",
+            name, reference_path, different_pixels, allowed as u32, actual_path, diff_path,
+        );
+    }
+}
+
+#[test]
+fn compare_images_counts_pixels_outside_tolerance() {
+    let reference = image::RgbaImage::from_pixel(4, 4, image::Rgba([100, 100, 100, 255]));
+    let mut actual = reference.clone();
+    actual.put_pixel(0, 0, image::Rgba([100 + CHANNEL_TOLERANCE, 100, 100, 255]));
+    actual.put_pixel(1, 0, image::Rgba([100, 100 + CHANNEL_TOLERANCE + 1, 100, 255]));
+
+    let comparison = compare_images(&reference, &actual);
+
+    assert_eq!(comparison.different_pixels, 1);
+    assert_eq!(comparison.diff.get_pixel(1, 0), &image::Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn golden_greg_gltf() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    app.renderer.show_overlay = false;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("greg_gltf", &frame);
+}
+
+// the .glb export is the same scene with its textures packed into the binary chunk
+#[test]
+fn golden_greg_glb() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/greg/greg_basic_export_applied_uv.glb")).unwrap();
+    app.renderer.show_overlay = false;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("greg_gltf", &frame);
+}
+
+#[test]
+fn golden_cube_obj() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/cube/cube.obj")).unwrap();
+    app.renderer.show_overlay = false;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("cube_obj", &frame);
+}
+
+// a point, spot and directional light in different colors, each with its own gizmo
+#[test]
+fn golden_multiple_lights() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/cube/cube.obj")).unwrap();
+    app.renderer.show_overlay = false;
+
+    let lights = &mut app.renderer.lights;
+    lights.lights.clear();
+    lights.add(
+        Light::point([-4.0, 1.0, 0.0], [1.0, 0.2, 0.2])
+            .with_intensity(4.0)
+            .with_range(8.0)
+            .with_attenuation(1.0, 0.0, 0.1)
+    ).unwrap();
+    lights.add(
+        Light::spot([0.0, 4.0, -1.5], [0.0, -1.0, 0.0], [0.2, 1.0, 0.2], cgmath::Deg(15.0), cgmath::Deg(30.0))
+            .with_intensity(2.0)
+    ).unwrap();
+    lights.add(Light::directional([1.0, -1.0, -1.0], [0.2, 0.2, 1.0]).with_intensity(0.5)).unwrap();
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("multiple_lights", &frame);
+}
+
+fn shadow_scene() -> Option<HeadlessApp> {
+    let mut app = common::headless_app(WIDTH, HEIGHT)?;
+    pollster::block_on(app.renderer.load_model_hierarchy(&app.device, &app.queue, "meshes/hierarchy/hierarchy.gltf")).unwrap();
+    app.renderer.instances.clear();
+    app.renderer.show_overlay = false;
+
+    // flatten the left box into a floor and float the top box above it
+    let hierarchy = app.renderer.model_hierarchy.as_mut().unwrap();
+    let floor = hierarchy.find_node("Left").unwrap();
+    let top = hierarchy.find_node("Top").unwrap();
+    for index in 0..hierarchy.nodes.len() {
+        hierarchy.set_transform(index, Transform::default());
+    }
+    hierarchy.set_transform(floor, Transform {
+        scale: cgmath::Vector3::new(6.0, 0.1, 6.0),
+        ..Default::default()
+    });
+    hierarchy.set_transform(top, Transform {
+        translation: cgmath::Vector3::new(0.0, 1.5, 0.0),
+        ..Default::default()
+    });
+    hierarchy.update(&app.queue);
+
+    let lights = &mut app.renderer.lights;
+    lights.lights.clear();
+    lights.add(Light::directional([0.3, -1.0, 0.2], [1.0, 1.0, 1.0]).with_shadows(true)).unwrap();
+
+    Some(app)
+}
+
+#[test]
+fn golden_shadows() {
+    let Some(mut app) = shadow_scene() else { return };
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("shadows", &frame);
+}
+
+#[test]
+fn golden_shadow_debug_view() {
+    let Some(mut app) = shadow_scene() else { return };
+    app.renderer.show_shadow_debug = true;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("shadow_debug_view", &frame);
+}
+
+#[test]
+fn golden_hierarchy_gltf_flattened() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/hierarchy/hierarchy.gltf")).unwrap();
+    // a single untransformed instance so the result matches drawing the nodes directly
+    app.renderer.instances.set_all(vec![Instance {
+        position: cgmath::Vector3::new(0.0, 0.0, 0.0),
+        rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+        ..Default::default()
+    }]);
+    app.renderer.show_overlay = false;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("hierarchy_gltf", &frame);
+}
+
+// rendering the node tree with per-node transforms should match the baked version
+#[test]
+fn golden_hierarchy_gltf_nodes() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model_hierarchy(&app.device, &app.queue, "meshes/hierarchy/hierarchy.gltf")).unwrap();
+    // no instances means the default model isn't drawn, only the hierarchy
+    app.renderer.instances.clear();
+    app.renderer.show_overlay = false;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("hierarchy_gltf", &frame);
+}
+
+// squashed, stretched and tinted copies of the cube
+#[test]
+fn golden_scaled_tinted_instances() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/cube/cube.obj")).unwrap();
+    app.renderer.show_overlay = false;
+    app.renderer.instances.set_all(vec![
+        Instance::new(cgmath::Vector3::new(-3.0, 0.0, 0.0), cgmath::Quaternion::one())
+            .with_scale(cgmath::Vector3::new(0.5, 2.0, 0.5))
+            .with_tint([1.0, 0.3, 0.3, 1.0]),
+        Instance::new(cgmath::Vector3::new(0.0, -0.5, 0.0), cgmath::Quaternion::from_angle_y(cgmath::Deg(30.0)))
+            .with_scale(cgmath::Vector3::new(2.0, 0.5, 1.0))
+            .with_tint([0.3, 1.0, 0.3, 1.0]),
+        Instance::new(cgmath::Vector3::new(3.0, 0.0, 0.0), cgmath::Quaternion::one())
+            .with_uniform_scale(0.75)
+            .with_tint([0.3, 0.3, 1.0, 1.0]),
+    ]);
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("scaled_tinted_instances", &frame);
+}
+
+#[test]
+fn golden_quad_overlay() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    app.renderer.show_scene = false;
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("quad_overlay", &frame);
+}
+
+// the whole mario sheet, a quarter of it rotated around its center and a tinted, layered copy
+#[test]
+fn golden_sprites() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    app.renderer.show_scene = false;
+    app.renderer.sprites.extend([
+        Sprite::new(0, [200.0, 20.0], [105.0, 105.0]).with_layer(1),
+        Sprite::new(0, [40.0, 120.0], [96.0, 96.0])
+            .with_source(SpriteRect::from_grid(0, 0, 210.0, 210.0))
+            .with_rotation(cgmath::Deg(30.0)),
+        Sprite::new(0, [220.0, 60.0], [80.0, 80.0])
+            .with_color([1.0, 0.4, 0.4, 0.8]),
+    ]);
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("sprites", &frame);
+}
+
+fn text_scene(app: &mut HeadlessApp) {
+    app.renderer.show_scene = false;
+    let text = &mut app.renderer.text;
+    text.push(Text::new("AVAWAY kerned 123", [10.0, 130.0], 18.0));
+    text.push(
+        Text::new("A longer line that wraps between its words", [10.0, 160.0], 14.0)
+            .with_max_width(180.0)
+            .with_color([1.0, 0.9, 0.3, 1.0])
+    );
+    text.push(Text::new("Big", [220.0, 140.0], 64.0).with_color([1.0, 0.4, 0.4, 1.0]));
+}
+
+#[test]
+fn golden_text_bitmap() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    text_scene(&mut app);
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("text_bitmap", &frame);
+}
+
+#[test]
+fn golden_text_sdf() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    let renderer = &mut app.renderer;
+    renderer.text = TextBatch::new(
+        &app.device,
+        app.config.format,
+        renderer.ortho_bind_group_layout(),
+        renderer.text.font.clone(),
+        TextOptions::default().with_mode(GlyphMode::sdf()),
+        renderer.sample_count(),
+    );
+    text_scene(&mut app);
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("text_sdf", &frame);
+}
+
+// the scene the app starts with, model, light and overlay all come from the file
+#[test]
+fn golden_default_scene() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    let scene = pollster::block_on(Scene::load("scenes/default.json")).unwrap();
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &scene)).unwrap();
+
+    let frame = pollster::block_on(app.render()).unwrap();
+
+    assert_golden("default_scene", &frame);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use cgmath::*;
+
+use wgpu_renderer::model::{Node, Transform};
+use wgpu_renderer::model::hierarchy::compute_world_transforms;
+
+fn node(name: &str, transform: Transform, children: Vec<usize>, parent: Option<usize>) -> Node {
+    Node {
+        name: name.to_string(),
+        transform,
+        meshes: vec![],
+        children,
+        parent,
+    }
+}
+
+// where the node's local origin ends up in world space
+fn world_origin(world: Matrix4<f32>) -> Point3<f32> {
+    Point3::from_vec(world.w.truncate())
+}
+
+fn assert_point_eq(actual: Point3<f32>, expected: Point3<f32>) {
+    assert!(
+        (actual - expected).magnitude() < 1e-5,
+        "expected {:?} got {:?}", expected, actual,
+    );
+}
+
+#[test]
+fn world_transforms_compose_parent_then_child() {
+    let nodes = vec![
+        node("Root", Transform {
+            translation: Vector3::new(0.0, 1.0, 0.0),
+            rotation: Quaternion::from_angle_y(Deg(90.0)),
+            ..Default::default()
+        }, vec![1], None),
+        node("Child", Transform {
+            translation: Vector3::new(2.0, 0.0, 0.0),
+            scale: Vector3::new(0.5, 0.5, 0.5),
+            ..Default::default()
+        }, vec![2], Some(0)),
+        node("Grandchild", Transform {
+            translation: Vector3::new(0.0, 2.0, 0.0),
+            ..Default::default()
+        }, vec![], Some(1)),
+    ];
+
+    let world = compute_world_transforms(&nodes, &[0]);
+
+    // rotating +x by 90 degrees around y points it down -z
+    assert_point_eq(world_origin(world[1]), Point3::new(0.0, 1.0, -2.0));
+    // the child's scale applies to the grandchild's translation
+    assert_point_eq(world_origin(world[2]), Point3::new(0.0, 2.0, -2.0));
+}
+
+#[test]
+fn unreachable_nodes_keep_their_local_transform() {
+    let local = Transform {
+        translation: Vector3::new(3.0, 0.0, 0.0),
+        ..Default::default()
+    };
+    let nodes = vec![
+        node("Root", Transform::default(), vec![], None),
+        node("Orphan", local, vec![], None),
+    ];
+
+    let world = compute_world_transforms(&nodes, &[0]);
+
+    assert_eq!(world[1], local.matrix());
+}
+
+#[test]
+fn only_nodes_of_the_scene_are_loaded() {
+    let Some(mut app) = common::headless_app(64, 48) else { return };
+    let file_name = "meshes/hierarchy/outside_scene.gltf";
+
+    pollster::block_on(app.renderer.load_model_hierarchy(&app.device, &app.queue, file_name)).unwrap();
+    let hierarchy = app.renderer.model_hierarchy.as_ref().unwrap();
+    let names = hierarchy.nodes.iter().map(|node| node.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["Root", "Left"]);
+    assert_eq!(hierarchy.roots, [0]);
+    assert_eq!(hierarchy.nodes[1].parent, Some(0));
+
+    // flattened, only the box under Left is baked in
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, file_name)).unwrap();
+    assert_eq!(app.renderer.obj_model.meshes.len(), 1);
+}
+
+#[test]
+fn buffer_views_past_the_end_of_their_buffer_are_errors() {
+    let Some(mut app) = common::headless_app(64, 48) else { return };
+    let file_name = "meshes/hierarchy/truncated.gltf";
+    let meshes = app.renderer.obj_model.meshes.len();
+
+    assert!(pollster::block_on(app.renderer.load_model(&app.device, &app.queue, file_name)).is_err());
+    assert!(pollster::block_on(app.renderer.load_model_hierarchy(&app.device, &app.queue, file_name)).is_err());
+    assert_eq!(app.renderer.obj_model.meshes.len(), meshes);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use cgmath::*;
+
+use wgpu_renderer::instance::{Instance, InstanceManager, InstanceRaw};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+fn instance(x: f32, z: f32) -> Instance {
+    Instance::new(Vector3::new(x, 0.0, z), Quaternion::one())
+}
+
+#[test]
+fn only_changed_instances_are_uploaded() {
+    let Some(app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    let mut instances = InstanceManager::new(&app.device, vec![instance(0.0, 0.0); 4]);
+    assert_eq!(instances.dirty_range(), Some(0..4));
+    instances.upload(&app.device, &app.queue);
+    assert_eq!(instances.dirty_range(), None);
+
+    instances.set(2, instance(1.0, 0.0));
+    instances.get_mut(1).unwrap().position.y = 2.0;
+    assert_eq!(instances.dirty_range(), Some(1..3));
+    instances.upload(&app.device, &app.queue);
+
+    // swapping the last instance into the hole only touches that slot
+    assert_eq!(instances.swap_remove(0), instance(0.0, 0.0));
+    assert_eq!(instances.dirty_range(), Some(0..1));
+    instances.upload(&app.device, &app.queue);
+
+    // keeping the order moves everything after it
+    instances.remove(0);
+    assert_eq!(instances.dirty_range(), Some(0..2));
+    instances.upload(&app.device, &app.queue);
+
+    // removing the last one leaves nothing to write
+    instances.remove(1);
+    instances.upload(&app.device, &app.queue);
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances.range(), 0..1);
+}
+
+#[test]
+fn the_buffer_grows_geometrically() {
+    let Some(app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    let mut instances = InstanceManager::new(&app.device, vec![]);
+    let capacity = instances.capacity();
+    assert_eq!(capacity, InstanceManager::INITIAL_CAPACITY);
+
+    instances.extend((0..capacity).map(|x| instance(x as f32, 0.0)));
+    instances.upload(&app.device, &app.queue);
+    assert_eq!(instances.capacity(), capacity);
+
+    instances.add(instance(0.0, 1.0));
+    assert_eq!(instances.dirty_range(), Some(capacity..capacity + 1));
+    instances.upload(&app.device, &app.queue);
+    assert_eq!(instances.capacity(), capacity * 2);
+    assert_eq!(instances.buffer.size(), (capacity * 2 * std::mem::size_of::<InstanceRaw>()) as u64);
+
+    // a big jump goes straight to what is needed
+    instances.extend((0..capacity * 5).map(|x| instance(x as f32, 2.0)));
+    instances.upload(&app.device, &app.queue);
+    assert_eq!(instances.capacity(), capacity * 6 + 1);
+}
+
+// the renderer draws instances added after it was created without anything being rebuilt
+#[test]
+fn added_instances_are_drawn() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/cube/cube.obj")).unwrap();
+    app.renderer.enable_id_buffer(&app.device, &app.config);
+    app.renderer.show_overlay = false;
+    pollster::block_on(app.render()).unwrap();
+
+    let added = app.renderer.instances.add(instance(3.0, 1.0));
+    for x in 0..InstanceManager::INITIAL_CAPACITY {
+        app.renderer.instances.add(instance(-20.0 - x as f32 * 3.0, -20.0));
+    }
+    pollster::block_on(app.render()).unwrap();
+
+    let renderer = &app.renderer;
+    let view_projection = Matrix4::from(renderer.camera_uniform.view_projection);
+    let clip = view_projection * Point3::new(3.0, 0.0, 1.0).to_homogeneous();
+    let (x, y) = ((clip.x / clip.w + 1.0) * 0.5 * WIDTH as f32, (1.0 - clip.y / clip.w) * 0.5 * HEIGHT as f32);
+
+    let readback = renderer.read_id(&app.device, &app.queue, x as u32, y as u32).unwrap();
+    let hit = pollster::block_on(readback.finish(&app.device)).unwrap().unwrap();
+    assert_eq!(hit.instance, added);
+    assert_eq!(renderer.instances.capacity(), InstanceManager::INITIAL_CAPACITY * 2);
+}
+
+#[test]
+fn normals_stay_perpendicular_under_non_uniform_scale() {
+    let instance = Instance::new(Vector3::new(1.0, 2.0, 3.0), Quaternion::from_angle_y(Deg(30.0)))
+        .with_scale(Vector3::new(4.0, 0.5, 1.0));
+    let raw = instance.to_raw();
+    let model = Matrix4::from(raw.model);
+    let normal_matrix = Matrix3::from(raw.normal);
+
+    // a slanted surface, its normal and a direction lying in it
+    let normal = Vector3::new(1.0, 1.0, 0.0).normalize();
+    let along_surface = Vector3::new(1.0, -1.0, 0.0);
+
+    let world_normal = normal_matrix * normal;
+    let world_along_surface = model.transform_vector(along_surface);
+    assert!(world_normal.dot(world_along_surface).abs() < 1e-4);
+
+    // the rotation alone would have been wrong here
+    let rotated_normal = Matrix3::from(instance.rotation) * normal;
+    assert!(rotated_normal.dot(world_along_surface).abs() > 0.1);
+
+    assert_eq!(model, instance.model_matrix());
+    assert_eq!(model.w.truncate(), instance.position);
+}
+
+#[test]
+fn tint_and_custom_data_are_passed_through() {
+    let instance = Instance::default()
+        .with_tint([1.0, 0.0, 0.0, 0.5])
+        .with_custom([1.0, 2.0, 3.0, 4.0]);
+    let raw = instance.to_raw();
+
+    assert_eq!(raw.tint, [1.0, 0.0, 0.0, 0.5]);
+    assert_eq!(raw.custom, [1.0, 2.0, 3.0, 4.0]);
+    // matrices from elsewhere (hierarchy nodes) aren't tinted
+    assert_eq!(InstanceRaw::from_matrix(Matrix4::identity()).tint, [1.0; 4]);
+
+    // the layout covers the whole struct
+    let layout = InstanceRaw::layout();
+    let last = layout.attributes.last().unwrap();
+    assert_eq!(layout.array_stride, std::mem::size_of::<InstanceRaw>() as u64);
+    assert_eq!(last.offset + last.format.size(), layout.array_stride);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use wgpu_renderer::texture::mipmap::{downsample, generate_mipmaps_cpu, mip_level_count};
+
+#[test]
+fn mip_level_count_goes_down_to_one_texel() {
+    assert_eq!(mip_level_count(1, 1), 1);
+    assert_eq!(mip_level_count(2, 2), 2);
+    assert_eq!(mip_level_count(1024, 1024), 11);
+    // the longest side decides
+    assert_eq!(mip_level_count(1024, 16), 11);
+    assert_eq!(mip_level_count(300, 200), 9);
+}
+
+#[test]
+fn generate_mipmaps_cpu_halves_each_level() {
+    let img = image::RgbaImage::from_pixel(8, 2, image::Rgba([10, 20, 30, 255]));
+
+    let levels = generate_mipmaps_cpu(&img, true, false);
+    let sizes = levels.iter().map(|level| level.dimensions()).collect::<Vec<_>>();
+
+    assert_eq!(sizes, vec![(4, 1), (2, 1), (1, 1)]);
+    // a flat color stays the same color
+    assert_eq!(levels[2].get_pixel(0, 0), &image::Rgba([10, 20, 30, 255]));
+}
+
+#[test]
+fn downsample_averages_srgb_in_linear_space() {
+    let mut img = image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 0, 255]));
+    img.put_pixel(0, 0, image::Rgba([255, 255, 255, 255]));
+    img.put_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+
+    let srgb = downsample(&img, true, false);
+    let linear = downsample(&img, false, false);
+
+    // 50% linear light is 188 in sRGB, averaging the encoded values would give a darker 128
+    assert_eq!(srgb.get_pixel(0, 0).0[0], 188);
+    assert_eq!(linear.get_pixel(0, 0).0[0], 128);
+}
+
+#[test]
+fn downsample_renormalizes_normal_maps() {
+    // two normals tilted 45 degrees in opposite directions along x
+    let mut img = image::RgbaImage::new(2, 2);
+    for y in 0..2 {
+        img.put_pixel(0, y, image::Rgba([37, 128, 218, 255]));
+        img.put_pixel(1, y, image::Rgba([218, 128, 218, 255]));
+    }
+
+    let level = downsample(&img, false, true);
+    let [r, g, b, _] = level.get_pixel(0, 0).0;
+
+    // the average points straight out and is unit length again instead of ~0.7
+    assert!(r.abs_diff(128) <= 1);
+    assert!(g.abs_diff(128) <= 1);
+    assert_eq!(b, 255);
+}
+
+#[test]
+fn mipmap_pipelines_are_built_once_per_kind_of_texture() {
+    let Some(mut app) = common::headless_app(64, 48) else { return };
+    let file_name = "meshes/greg/greg_basic_export_applied_uv.gltf";
+
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, file_name)).unwrap();
+    let pipelines = app.renderer.mipmaps.pipeline_count();
+    // sRGB colors and normal maps at least
+    assert!(pipelines >= 2, "{}", pipelines);
+
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, file_name)).unwrap();
+    assert_eq!(app.renderer.mipmaps.pipeline_count(), pipelines);
+}
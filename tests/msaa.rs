@@ -0,0 +1,188 @@
+mod common;
+
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use wgpu_renderer::app::{HeadlessApp, HeadlessOptions, Renderer};
+use wgpu_renderer::render::graph::AttachmentSlot;
+use wgpu_renderer::render::multisample::sample_counts_supported_by;
+use wgpu_renderer::render::{
+    choose_sample_count,
+    AttachmentDescriptor,
+    AttachmentId,
+    AttachmentLoad,
+    NodeBuilder,
+    NodeContext,
+    RenderNode,
+    HDR_FORMAT,
+};
+use wgpu_renderer::texture::Texture;
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+fn headless_app(sample_count: u32) -> Option<HeadlessApp> {
+    common::headless_app_with(HeadlessOptions {
+        width: WIDTH,
+        height: HEIGHT,
+        sample_count,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn guaranteed_sample_counts_are_one_and_four() {
+    let formats = Renderer::multisampled_formats(HeadlessApp::FORMAT);
+    let supported = sample_counts_supported_by(&formats, |format| {
+        format.guaranteed_format_features(wgpu::Features::empty())
+    });
+
+    assert_eq!(supported, [1, 4]);
+}
+
+#[test]
+fn the_closest_supported_count_below_the_request_is_chosen() {
+    assert_eq!(choose_sample_count(&[1, 4], 8), 4);
+    assert_eq!(choose_sample_count(&[1, 4], 4), 4);
+    assert_eq!(choose_sample_count(&[1, 4], 2), 1);
+    assert_eq!(choose_sample_count(&[1, 2, 4, 8], 8), 8);
+    assert_eq!(choose_sample_count(&[], 4), 1);
+}
+
+#[test]
+fn multisampled_attachments_resolve_into_hdr_and_the_target() {
+    let Some(mut app) = headless_app(4) else {
+        return;
+    };
+    assert_eq!(app.renderer.sample_count(), 4);
+
+    let graph = &app.renderer.graph;
+    let schedule = graph.schedule().unwrap();
+    let hdr = graph.attachment("hdr").unwrap();
+    let resolves = schedule.nodes.iter()
+        .flat_map(|node| node.colors.iter().filter_map(|color| color.resolve_target))
+        .collect::<Vec<_>>();
+    assert_eq!(resolves, [schedule.slot(hdr).unwrap(), AttachmentSlot::Target]);
+
+    let multisampled = schedule.textures.iter()
+        .filter(|texture| texture.sample_count == 4)
+        .map(|texture| texture.format)
+        .collect::<HashSet<_>>();
+    assert_eq!(multisampled, Renderer::multisampled_formats(HeadlessApp::FORMAT).into_iter().collect());
+    assert!(schedule.textures.iter().any(|texture| texture.format == HDR_FORMAT && texture.sample_count == 1));
+
+    // rebuilt at the new size
+    app.resize(32, 16);
+    let resources = app.renderer.graph.resources().unwrap();
+    for texture in resources.textures.iter().filter(|texture| texture.sample_count() == 4) {
+        assert_eq!((texture.width(), texture.height()), (32, 16));
+    }
+}
+
+#[test]
+fn multisampling_smooths_edges() {
+    // one app at a time, the software GL adapter doesn't cope with two devices
+    let render = |sample_count| {
+        let mut app = headless_app(sample_count)?;
+        Some(pollster::block_on(app.render()).unwrap())
+    };
+    let (Some(aliased), Some(smoothed)) = (render(1), render(4)) else {
+        return;
+    };
+
+    // the same picture, with blended colors along the edges
+    let unique_colors = |image: &image::RgbaImage| image.pixels().collect::<HashSet<_>>().len();
+    let differing = aliased.pixels().zip(smoothed.pixels()).filter(|(a, b)| a != b).count();
+    assert!(differing > 0);
+    assert!(differing < aliased.pixels().len() / 4, "{}", differing);
+    assert!(unique_colors(&smoothed) > unique_colors(&aliased));
+}
+
+#[test]
+fn the_target_is_resolved_when_the_overlay_draws_nothing() {
+    let Some(mut app) = headless_app(4) else {
+        return;
+    };
+
+    app.renderer.show_scene = false;
+    app.renderer.show_overlay = false;
+    app.renderer.show_shadow_debug = false;
+    app.renderer.clear_color = wgpu::Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+
+    let image = pollster::block_on(app.render()).unwrap();
+    assert!(image.get_pixel(WIDTH / 2, HEIGHT / 2)[0] > 128);
+}
+
+#[test]
+fn the_sample_count_can_be_changed_while_running() {
+    let Some(mut app) = headless_app(1) else {
+        return;
+    };
+    app.renderer.show_shadow_debug = true;
+    let aliased = pollster::block_on(app.render()).unwrap();
+
+    // never a supported count, nothing changes
+    assert!(app.set_sample_count(3).is_err());
+    assert_eq!(app.renderer.sample_count(), 1);
+
+    app.set_sample_count(4).unwrap();
+    assert_eq!(app.renderer.sample_count(), 4);
+    let schedule = app.renderer.graph.schedule().unwrap();
+    assert!(schedule.textures.iter().any(|texture| texture.format == Texture::DEPTH_FORMAT && texture.sample_count == 4));
+    let smoothed = pollster::block_on(app.render()).unwrap();
+    assert_ne!(smoothed, aliased);
+
+    app.set_sample_count(1).unwrap();
+    assert_eq!(pollster::block_on(app.render()).unwrap(), aliased);
+}
+
+// reads the scene and clears a scratch attachment of its own, counting how often it ran
+struct CountingNode {
+    hdr: AttachmentId,
+    scratch: AttachmentId,
+    runs: Rc<Cell<u32>>,
+}
+
+impl RenderNode<Renderer> for CountingNode {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder.read(self.hdr).color(self.scratch, AttachmentLoad::Clear);
+    }
+
+    fn run(&self, _renderer: &Renderer, context: &mut NodeContext) {
+        assert!(context.view(self.hdr).is_some());
+        context.begin_render_pass("Counting Pass");
+        self.runs.set(self.runs.get() + 1);
+    }
+}
+
+#[test]
+fn added_passes_survive_a_sample_count_change() {
+    let Some(mut app) = headless_app(1) else {
+        return;
+    };
+
+    let graph = &mut app.renderer.graph;
+    let hdr = graph.attachment("hdr").unwrap();
+    let scratch = graph.add_attachment(AttachmentDescriptor::new("scratch", HDR_FORMAT));
+    let runs = Rc::new(Cell::new(0));
+    graph.add_node(Box::new(CountingNode { hdr, scratch, runs: runs.clone() }));
+    graph.compile(&app.device).unwrap();
+
+    pollster::block_on(app.render()).unwrap();
+    assert_eq!(runs.get(), 1);
+
+    for sample_count in [4, 1] {
+        app.set_sample_count(sample_count).unwrap();
+        let graph = &app.renderer.graph;
+        assert_eq!(graph.attachment("scratch"), Some(scratch));
+        assert_eq!(graph.node(graph.len() - 1).unwrap().name(), "counting");
+
+        pollster::block_on(app.render()).unwrap();
+    }
+    assert_eq!(runs.get(), 3);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use cgmath::*;
+
+use wgpu_renderer::camera::OrthoProjection;
+use wgpu_renderer::primitives::quad::{quad_vertices, QuadOptions};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+const EPSILON: f32 = 1e-5;
+
+fn to_clip(projection: &OrthoProjection, position: [f32; 2]) -> [f32; 2] {
+    let clip = projection.calc_matrix() * Vector4::new(position[0], position[1], 0.0, 1.0);
+
+    [clip.x / clip.w, clip.y / clip.w]
+}
+
+fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+    assert!(
+        (actual[0] - expected[0]).abs() < EPSILON && (actual[1] - expected[1]).abs() < EPSILON,
+        "{:?} != {:?}", actual, expected,
+    );
+}
+
+#[test]
+fn quad_vertices_are_in_pixels() {
+    let options = QuadOptions {
+        position: [10.0, 20.0],
+        color: (255, 0, 0, 1.0),
+        dimensions: (100.0, 50.0),
+    };
+
+    let positions = quad_vertices(&options).map(|vertex| vertex.position);
+
+    // relative to the top left corner, the model uniform takes care of `position`
+    assert_eq!(positions, [
+        [0.0, 0.0, 0.0],
+        [100.0, 0.0, 0.0],
+        [0.0, 50.0, 0.0],
+        [100.0, 50.0, 0.0],
+    ]);
+    assert_eq!(quad_vertices(&options)[0].color, [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn the_top_left_corner_is_the_origin() {
+    let projection = OrthoProjection::new(800, 600, 0.0, 1.0);
+
+    assert_close(to_clip(&projection, [0.0, 0.0]), [-1.0, 1.0]);
+    assert_close(to_clip(&projection, [800.0, 600.0]), [1.0, -1.0]);
+    assert_close(to_clip(&projection, [400.0, 150.0]), [0.0, 0.5]);
+
+    // on the near plane, in front of everything the scene drew
+    let depth = projection.calc_matrix() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert!(depth.z.abs() < EPSILON);
+}
+
+#[test]
+fn hidpi_screens_use_logical_pixels() {
+    let projection = OrthoProjection::new(1600, 1200, 0.0, 1.0).with_scale_factor(2.0);
+
+    assert_eq!(projection.logical_size(), [800.0, 600.0]);
+    assert_close(to_clip(&projection, [800.0, 600.0]), [1.0, -1.0]);
+    assert_close(to_clip(&projection, [400.0, 300.0]), [0.0, 0.0]);
+}
+
+#[test]
+fn quad_corners_land_on_their_pixels() {
+    let projection = OrthoProjection::new(WIDTH, HEIGHT, 0.0, 1.0);
+    let options = QuadOptions {
+        position: [40.0, 60.0],
+        dimensions: (80.0, 120.0),
+        ..Default::default()
+    };
+    let model = Matrix4::from_translation(Vector3::new(40.0, 60.0, 0.0));
+
+    let corners = quad_vertices(&options).map(|vertex| {
+        let clip = projection.calc_matrix() * model * Vector3::from(vertex.position).extend(1.0);
+        // back to pixels the way the rasterizer does it
+        [(clip.x + 1.0) * 0.5 * WIDTH as f32, (1.0 - clip.y) * 0.5 * HEIGHT as f32]
+    });
+
+    assert_close(corners[0], [40.0, 60.0]);
+    assert_close(corners[3], [120.0, 180.0]);
+}
+
+// the overlay quad stays where it was put when the target is resized or the dpi changes
+#[test]
+fn quads_keep_their_place_across_resizes() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    app.renderer.show_scene = false;
+    let options = &app.renderer.quad_model.options;
+    let [x, y] = options.position;
+    let (width, height) = options.dimensions;
+    let (right, bottom) = ((x + width) as u32, (y + height) as u32);
+
+    let frame = pollster::block_on(app.render()).unwrap();
+    let background = *frame.get_pixel(0, 0);
+    assert_ne!(*frame.get_pixel(x as u32 + 1, y as u32 + 1), background);
+    assert_ne!(*frame.get_pixel(right - 1, bottom - 1), background);
+    assert_eq!(*frame.get_pixel(right + 1, bottom + 1), background);
+
+    app.resize(WIDTH * 2, HEIGHT * 2);
+    let frame = pollster::block_on(app.render()).unwrap();
+    assert_ne!(*frame.get_pixel(x as u32 + 1, y as u32 + 1), background);
+    assert_ne!(*frame.get_pixel(right - 1, bottom - 1), background);
+    assert_eq!(*frame.get_pixel(right + 1, bottom + 1), background);
+
+    // twice the pixels per logical pixel doubles everything on screen
+    app.renderer.set_scale_factor(2.0);
+    let frame = pollster::block_on(app.render()).unwrap();
+    assert_eq!(*frame.get_pixel(x as u32 * 2 - 1, y as u32 * 2 - 1), background);
+    assert_ne!(*frame.get_pixel(right * 2 - 1, bottom * 2 - 1), background);
+    assert_eq!(*frame.get_pixel(right * 2 + 1, bottom * 2 + 1), background);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use cgmath::*;
+
+use wgpu_renderer::camera::{Camera, Projection, Ray};
+use wgpu_renderer::model::Aabb;
+
+const EPSILON: f32 = 1e-4;
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+// the pixel a world position ends up on
+fn project(camera: &Camera, projection: &Projection, point: Point3<f32>, size: (u32, u32)) -> (f32, f32) {
+    let clip = projection.calc_matrix() * camera.calc_matrix() * point.to_homogeneous();
+    let ndc = clip.truncate() / clip.w;
+
+    ((ndc.x + 1.0) * 0.5 * size.0 as f32, (1.0 - ndc.y) * 0.5 * size.1 as f32)
+}
+
+#[test]
+fn ray_hits_triangle_from_either_side() {
+    let (a, b, c) = (Point3::new(-1.0, -1.0, 0.0), Point3::new(1.0, -1.0, 0.0), Point3::new(0.0, 1.0, 0.0));
+
+    let front = Ray::new([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]);
+    let back = Ray::new([0.0, 0.0, -2.0], [0.0, 0.0, 1.0]);
+    assert!((front.intersect_triangle(a, b, c).unwrap() - 5.0).abs() < EPSILON);
+    assert!((back.intersect_triangle(a, b, c).unwrap() - 2.0).abs() < EPSILON);
+
+    // pointing away, passing beside it and running parallel to it
+    assert_eq!(Ray::new([0.0, 0.0, 5.0], [0.0, 0.0, 1.0]).intersect_triangle(a, b, c), None);
+    assert_eq!(Ray::new([2.0, 0.0, 5.0], [0.0, 0.0, -1.0]).intersect_triangle(a, b, c), None);
+    assert_eq!(Ray::new([0.0, 0.0, 5.0], [1.0, 0.0, 0.0]).intersect_triangle(a, b, c), None);
+}
+
+#[test]
+fn ray_enters_boxes() {
+    let bounds = Aabb::new([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+
+    let hit = Ray::new([-5.0, 0.5, 0.0], [1.0, 0.0, 0.0]).intersect_aabb(&bounds);
+    assert!((hit.unwrap() - 4.0).abs() < EPSILON);
+
+    // starting inside counts as a hit straight away
+    assert_eq!(Ray::new([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]).intersect_aabb(&bounds), Some(0.0));
+
+    assert_eq!(Ray::new([-5.0, 2.0, 0.0], [1.0, 0.0, 0.0]).intersect_aabb(&bounds), None);
+    assert_eq!(Ray::new([5.0, 0.0, 0.0], [1.0, 0.0, 0.0]).intersect_aabb(&bounds), None);
+    assert_eq!(Ray::new([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0]).intersect_aabb(&Aabb::empty()), None);
+}
+
+#[test]
+fn screen_rays_go_through_their_pixel() {
+    let camera = Camera::new((1.0, 2.0, 8.0), Deg(-100.0), Deg(-15.0));
+    let projection = Projection::new(800, 600, Deg(45.0), 0.1, 100.0);
+    let size = (800, 600);
+
+    let center = Ray::from_screen(&camera, &projection, (400.0, 300.0), size);
+    assert!(center.direction.dot(camera.forward()) > 1.0 - EPSILON);
+    // starting on the near plane
+    assert!(((center.origin - camera.position).magnitude() - 0.1).abs() < 1e-3);
+
+    for pixel in [(0.0, 0.0), (800.0, 600.0), (123.0, 456.0)] {
+        let ray = Ray::from_screen(&camera, &projection, pixel, size);
+        let (x, y) = project(&camera, &projection, ray.at(20.0), size);
+
+        assert!((x - pixel.0).abs() < 0.01 && (y - pixel.1).abs() < 0.01, "{:?} came back as {:?}", pixel, (x, y));
+    }
+}
+
+#[test]
+fn picking_finds_the_instance_under_the_cursor() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else { return };
+    pollster::block_on(app.renderer.load_model(&app.device, &app.queue, "meshes/cube/cube.obj")).unwrap();
+    app.renderer.enable_id_buffer(&app.device, &app.config);
+    pollster::block_on(app.render()).unwrap();
+
+    let renderer = &app.renderer;
+    let instance = renderer.instances.get(0).unwrap();
+    let instance_center = Point3::from_vec(instance.position);
+    let center = project(&renderer.camera, &renderer.projection, instance_center, (WIDTH, HEIGHT));
+
+    let hit = renderer.pick(center).expect("the cube is under its own center");
+    assert_eq!(hit.instance, 0);
+    // on the side of the cube facing the camera
+    assert!(hit.distance < (renderer.camera.position - instance_center).magnitude());
+    let bounds = renderer.obj_model.bounds().transform(&instance.model_matrix());
+    for axis in 0..3 {
+        assert!(hit.position[axis] >= bounds.min[axis] - 1e-3 && hit.position[axis] <= bounds.max[axis] + 1e-3);
+    }
+
+    assert_eq!(renderer.pick((1.0, 1.0)), None);
+
+    // the id buffer agrees
+    let readback = renderer.read_id(&app.device, &app.queue, center.0 as u32, center.1 as u32).unwrap();
+    let id_hit = pollster::block_on(readback.finish(&app.device)).unwrap().unwrap();
+    assert_eq!((id_hit.instance, id_hit.mesh), (hit.instance, hit.mesh));
+
+    let readback = renderer.read_id(&app.device, &app.queue, 1, 1).unwrap();
+    assert_eq!(pollster::block_on(readback.finish(&app.device)).unwrap(), None);
+
+    assert!(renderer.read_id(&app.device, &app.queue, WIDTH, 0).is_none());
+}
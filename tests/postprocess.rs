@@ -0,0 +1,181 @@
+mod common;
+
+use wgpu_renderer::app::HeadlessApp;
+use wgpu_renderer::render::postprocess::{PostProcessUniform, BLOOM_LEVELS};
+use wgpu_renderer::render::{
+    Bloom,
+    ChromaticAberration,
+    ColorGrading,
+    Fxaa,
+    PostProcessOptions,
+    TonemapOptions,
+    Tonemapper,
+    Vignette,
+};
+use wgpu_renderer::texture::CubeLut;
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+// only the clear color, at `value` in every channel
+fn render_clear_color(app: &mut HeadlessApp, value: f64, options: PostProcessOptions) -> image::RgbaImage {
+    app.renderer.show_scene = false;
+    app.renderer.show_overlay = false;
+    app.renderer.clear_color = wgpu::Color { r: value, g: value, b: value, a: 1.0 };
+    app.renderer.tonemapping.options = TonemapOptions::default().with_tonemapper(Tonemapper::Reinhard);
+    app.renderer.post_processing.options = options;
+
+    pollster::block_on(app.render()).unwrap()
+}
+
+fn render_scene(app: &mut HeadlessApp, options: PostProcessOptions) -> image::RgbaImage {
+    app.renderer.post_processing.options = options;
+
+    pollster::block_on(app.render()).unwrap()
+}
+
+#[test]
+fn cube_luts_are_parsed() {
+    let lut = CubeLut::parse(
+        "# a comment\n\
+        TITLE \"Invert\"\n\
+        LUT_3D_SIZE 2\n\
+        DOMAIN_MIN 0 0 0\n\
+        DOMAIN_MAX 1 1 1\n\
+        \n\
+        1 1 1\n0 1 1\n1 0 1\n0 0 1\n\
+        1 1 0\n0 1 0\n1 0 0\n0 0 0\n"
+    ).unwrap();
+
+    assert_eq!(lut.title.as_deref(), Some("Invert"));
+    assert_eq!(lut.size, 2);
+    assert_eq!(lut.data.len(), 8);
+    // red changes fastest
+    assert_eq!(lut.data[1], [0.0, 1.0, 1.0]);
+    assert_eq!(&lut.to_rgba8()[4..8], &[0, 255, 255, 255]);
+
+    let identity = CubeLut::identity(3);
+    assert_eq!(identity.data.len(), 27);
+    assert_eq!(identity.data[1], [0.5, 0.0, 0.0]);
+    assert_eq!(identity.data[26], [1.0, 1.0, 1.0]);
+
+    let warm = pollster::block_on(CubeLut::load("luts/warm.cube")).unwrap();
+    assert_eq!(warm.data.len(), (warm.size * warm.size * warm.size) as usize);
+}
+
+#[test]
+fn broken_cube_luts_are_errors() {
+    assert!(CubeLut::parse("0 0 0\n").is_err());
+    assert!(CubeLut::parse("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n").is_err());
+    assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    assert!(CubeLut::parse("LUT_3D_SIZE 1\n0 0 0\n").is_err());
+    assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0\n").is_err());
+
+    let mut flipped_domain = String::from("LUT_3D_SIZE 2\nDOMAIN_MIN 1 1 1\nDOMAIN_MAX 0 0 0\n");
+    flipped_domain.push_str(&"0 0 0\n".repeat(8));
+    assert!(CubeLut::parse(&flipped_domain).is_err());
+}
+
+#[test]
+fn disabled_effects_are_zeroed_in_the_uniform() {
+    let mut uniform = PostProcessUniform::new();
+    uniform.update(&PostProcessOptions::default());
+    assert_eq!(uniform.bloom, 0);
+    assert_eq!(uniform.fxaa, 0);
+    assert_eq!(uniform.vignette, 0);
+    assert_eq!(uniform.chromatic_aberration, 0.0);
+    assert_eq!(uniform.lut_contribution, 0.0);
+
+    let options = PostProcessOptions::default()
+        .with_bloom(Bloom { intensity: 1.0, ..Default::default() })
+        .with_fxaa(Fxaa::default())
+        .with_chromatic_aberration(ChromaticAberration { intensity: 0.02 })
+        .with_color_grading(ColorGrading { contribution: 2.0 });
+    uniform.update(&options);
+    assert_eq!(uniform.bloom, 1);
+    assert_eq!(uniform.bloom_intensity, 1.0 / BLOOM_LEVELS as f32);
+    assert_eq!(uniform.fxaa, 1);
+    assert_eq!(uniform.chromatic_aberration, 0.02);
+    assert_eq!(uniform.lut_contribution, 1.0);
+}
+
+#[test]
+fn bloom_brightens_what_is_above_the_threshold() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+
+    let bloom = PostProcessOptions::default().with_bloom(Bloom { threshold: 1.0, knee: 0.0, intensity: 1.0 });
+    let center = |image: &image::RgbaImage| image.get_pixel(WIDTH / 2, HEIGHT / 2)[0];
+
+    let bright = center(&render_clear_color(&mut app, 4.0, PostProcessOptions::default()));
+    let bloomed = center(&render_clear_color(&mut app, 4.0, bloom));
+    assert!(bloomed > bright, "{} <= {}", bloomed, bright);
+
+    // below the threshold nothing changes
+    let dim = center(&render_clear_color(&mut app, 0.5, PostProcessOptions::default()));
+    let not_bloomed = center(&render_clear_color(&mut app, 0.5, bloom));
+    assert_eq!(dim, not_bloomed);
+}
+
+#[test]
+fn vignette_darkens_the_corners_only() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+
+    let plain = render_clear_color(&mut app, 1.0, PostProcessOptions::default());
+    let vignetted = render_clear_color(&mut app, 1.0, PostProcessOptions::default().with_vignette(Vignette::default()));
+
+    let center = (WIDTH / 2, HEIGHT / 2);
+    assert_eq!(plain.get_pixel(center.0, center.1), vignetted.get_pixel(center.0, center.1));
+    assert!(vignetted.get_pixel(0, 0)[0] < plain.get_pixel(0, 0)[0]);
+}
+
+#[test]
+fn color_grading_runs_the_frame_through_the_lut() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+
+    // black and white swapped
+    let invert = CubeLut {
+        data: CubeLut::identity(2).data.into_iter().map(|[r, g, b]| [1.0 - r, 1.0 - g, 1.0 - b]).collect(),
+        ..CubeLut::identity(2)
+    };
+    app.renderer.post_processing.set_lut(&app.device, &app.queue, &invert);
+
+    let black = render_clear_color(&mut app, 0.0, PostProcessOptions::default());
+    assert_eq!(black.get_pixel(0, 0)[0], 0);
+    let graded = PostProcessOptions::default().with_color_grading(ColorGrading::default());
+    let inverted = render_clear_color(&mut app, 0.0, graded);
+    assert_eq!(inverted.get_pixel(0, 0)[0], 255);
+
+    let half = PostProcessOptions::default().with_color_grading(ColorGrading { contribution: 0.5 });
+    let blended = render_clear_color(&mut app, 0.0, half).get_pixel(0, 0)[0];
+    assert!(blended > 0 && blended < 255, "{}", blended);
+}
+
+#[test]
+fn chromatic_aberration_and_fxaa_change_edges() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+
+    let plain = render_scene(&mut app, PostProcessOptions::default());
+    let differing = |image: &image::RgbaImage| plain.pixels().zip(image.pixels()).filter(|(a, b)| a != b).count();
+
+    let split = render_scene(
+        &mut app,
+        PostProcessOptions::default().with_chromatic_aberration(ChromaticAberration { intensity: 0.05 }),
+    );
+    assert!(differing(&split) > 0);
+    // next to nothing moves in the middle of the frame, the pixel center is half a texel off it
+    let (before, after) = (plain.get_pixel(WIDTH / 2, HEIGHT / 2), split.get_pixel(WIDTH / 2, HEIGHT / 2));
+    assert!(before.0.iter().zip(after.0).all(|(a, b)| a.abs_diff(b) <= 1), "{:?} {:?}", before, after);
+
+    let antialiased = render_scene(&mut app, PostProcessOptions::default().with_fxaa(Fxaa::default()));
+    let changed = differing(&antialiased);
+    assert!(changed > 0);
+    assert!(changed < plain.pixels().len() / 4, "{}", changed);
+}
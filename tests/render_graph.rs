@@ -0,0 +1,302 @@
+mod common;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use wgpu_renderer::app::Renderer;
+use wgpu_renderer::render::graph::{AttachmentSlot, Schedule};
+use wgpu_renderer::render::{
+    AttachmentDescriptor,
+    AttachmentId,
+    AttachmentLoad,
+    AttachmentSize,
+    NodeBuilder,
+    NodeContext,
+    RenderGraph,
+    RenderNode,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// a node that only declares attachments, for checking the schedule without a device
+#[derive(Default)]
+struct Node {
+    name: &'static str,
+    reads: Vec<AttachmentId>,
+    colors: Vec<(AttachmentId, AttachmentLoad)>,
+}
+
+impl Node {
+    fn new(name: &'static str) -> Self {
+        Self { name, ..Default::default() }
+    }
+
+    fn read(mut self, attachment: AttachmentId) -> Self {
+        self.reads.push(attachment);
+        self
+    }
+
+    fn color(mut self, attachment: AttachmentId, load: AttachmentLoad) -> Self {
+        self.colors.push((attachment, load));
+        self
+    }
+}
+
+impl RenderNode<()> for Node {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        for attachment in &self.reads {
+            builder.read(*attachment);
+        }
+        for (attachment, load) in &self.colors {
+            builder.color(*attachment, *load);
+        }
+    }
+
+    fn run(&self, _world: &(), _context: &mut NodeContext) {}
+}
+
+fn graph() -> RenderGraph<()> {
+    RenderGraph::new(800, 600, wgpu::TextureFormat::Bgra8UnormSrgb)
+}
+
+fn names(graph: &RenderGraph<()>, schedule: &Schedule) -> Vec<String> {
+    schedule.order().into_iter()
+        .map(|node| graph.node(node).unwrap().name().to_string())
+        .collect()
+}
+
+#[test]
+fn nodes_reading_an_attachment_run_after_the_nodes_writing_it() {
+    let mut graph = graph();
+    let hdr = graph.add_attachment(AttachmentDescriptor::new("hdr", FORMAT));
+
+    // added before the node it depends on
+    graph.add_node(Box::new(Node::new("tonemap").read(hdr).color(AttachmentId::TARGET, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("opaque").color(hdr, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("transparent").color(hdr, AttachmentLoad::Load)));
+
+    let schedule = graph.build_schedule().unwrap();
+    assert_eq!(names(&graph, &schedule), ["opaque", "transparent", "tonemap"]);
+}
+
+#[test]
+fn nodes_drawing_on_top_keep_the_order_they_were_added_in() {
+    let mut graph = graph();
+    graph.add_node(Box::new(Node::new("overlay").color(AttachmentId::TARGET, AttachmentLoad::Load)));
+    graph.add_node(Box::new(Node::new("debug").color(AttachmentId::TARGET, AttachmentLoad::Load)));
+    graph.add_node(Box::new(Node::new("opaque").color(AttachmentId::TARGET, AttachmentLoad::Clear)));
+    // unrelated to the others, stays where it was added
+    graph.add_node(Box::new(Node::new("unrelated")));
+
+    let schedule = graph.build_schedule().unwrap();
+    assert_eq!(names(&graph, &schedule), ["opaque", "overlay", "debug", "unrelated"]);
+}
+
+#[test]
+fn cycles_and_missing_writes_are_errors() {
+    let mut graph = graph();
+    let a = graph.add_attachment(AttachmentDescriptor::new("a", FORMAT));
+    let b = graph.add_attachment(AttachmentDescriptor::new("b", FORMAT));
+    graph.add_node(Box::new(Node::new("first").read(b).color(a, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("second").read(a).color(b, AttachmentLoad::Clear)));
+
+    let error = graph.build_schedule().unwrap_err().to_string();
+    assert!(error.contains("cycle"), "{}", error);
+
+    let mut graph = graph_with_unwritten_attachment();
+    let error = graph.build_schedule().unwrap_err().to_string();
+    assert!(error.contains("no node clears or writes it"), "{}", error);
+
+    // the target can't be sampled
+    graph = self::graph();
+    graph.add_node(Box::new(Node::new("reader").read(AttachmentId::TARGET)));
+    assert!(graph.build_schedule().is_err());
+}
+
+#[test]
+fn added_nodes_can_be_moved_to_a_rebuilt_graph() {
+    let build = || {
+        let mut graph = graph();
+        let hdr = graph.add_attachment(AttachmentDescriptor::new("hdr", FORMAT));
+        graph.add_node(Box::new(Node::new("opaque").color(hdr, AttachmentLoad::Clear)));
+        (graph, hdr)
+    };
+    let (mut old, hdr) = build();
+    let scratch = old.add_attachment(AttachmentDescriptor::new("scratch", FORMAT));
+    old.add_node(Box::new(Node::new("added").read(hdr).color(scratch, AttachmentLoad::Clear)));
+
+    let (mut new, _) = build();
+    new.take_added(&mut old).unwrap();
+    assert_eq!(new.attachment("scratch"), Some(scratch));
+    let schedule = new.build_schedule().unwrap();
+    assert_eq!(names(&new, &schedule), ["opaque", "added"]);
+    assert_eq!(old.len(), 1);
+    assert_eq!(old.attachment("scratch"), None);
+
+    // graphs built differently are left alone
+    let mut other = graph();
+    other.add_attachment(AttachmentDescriptor::new("ldr", FORMAT));
+    assert!(other.take_added(&mut new).is_err());
+    assert_eq!(new.len(), 2);
+}
+
+fn graph_with_unwritten_attachment() -> RenderGraph<()> {
+    let mut graph = graph();
+    let a = graph.add_attachment(AttachmentDescriptor::new("a", FORMAT));
+    graph.add_node(Box::new(Node::new("loads").color(a, AttachmentLoad::Load)));
+    graph
+}
+
+#[test]
+fn transient_textures_are_shared_when_their_uses_dont_overlap() {
+    let mut graph = graph();
+    let a = graph.add_attachment(AttachmentDescriptor::new("a", FORMAT));
+    let b = graph.add_attachment(AttachmentDescriptor::new("b", FORMAT));
+    let c = graph.add_attachment(AttachmentDescriptor::new("c", FORMAT));
+    let half = graph.add_attachment(AttachmentDescriptor::new("half", FORMAT).with_size(AttachmentSize::Scaled(0.5)));
+
+    graph.add_node(Box::new(Node::new("a").color(a, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("b").read(a).color(b, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("c").read(b).color(c, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("half").read(c).color(half, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("present").read(half).color(AttachmentId::TARGET, AttachmentLoad::Clear)));
+
+    let schedule = graph.build_schedule().unwrap();
+    // `a` is done with by the time `c` is drawn, `half` is a different size
+    assert_eq!(schedule.slot(a), schedule.slot(c));
+    assert_ne!(schedule.slot(a), schedule.slot(b));
+    assert_eq!(schedule.textures.len(), 3);
+
+    let Some(AttachmentSlot::Texture(half_texture)) = schedule.slot(half) else {
+        panic!("half wasn't allocated");
+    };
+    let allocation = &schedule.textures[half_texture];
+    assert_eq!((allocation.width, allocation.height), (400, 300));
+}
+
+#[test]
+fn multisampled_attachments_are_resolved_by_their_last_writer() {
+    let mut graph = graph();
+    let msaa = graph.add_attachment(AttachmentDescriptor::new("msaa", FORMAT).with_sample_count(4));
+    graph.add_node(Box::new(Node::new("opaque").color(msaa, AttachmentLoad::Clear)));
+    graph.add_node(Box::new(Node::new("transparent").color(msaa, AttachmentLoad::Load)));
+    graph.add_node(Box::new(Node::new("post").read(msaa).color(AttachmentId::TARGET, AttachmentLoad::Clear)));
+
+    let schedule = graph.build_schedule().unwrap();
+    let resolved = schedule.read_slot(msaa);
+    assert!(resolved.is_some());
+    assert_ne!(resolved, schedule.slot(msaa));
+
+    assert_eq!(schedule.nodes[0].colors[0].resolve_target, None);
+    assert_eq!(schedule.nodes[1].colors[0].resolve_target, resolved);
+
+    let samples = schedule.textures.iter().map(|texture| texture.sample_count).collect::<Vec<_>>();
+    assert_eq!(samples, [4, 1]);
+}
+
+#[test]
+fn multisampled_attachments_can_resolve_into_the_target() {
+    let mut graph = graph();
+    let msaa = graph.add_attachment(
+        AttachmentDescriptor::new("msaa", wgpu::TextureFormat::Bgra8UnormSrgb)
+            .with_sample_count(4)
+            .with_resolve_target(AttachmentId::TARGET),
+    );
+    graph.add_node(Box::new(Node::new("overlay").color(AttachmentId::TARGET, AttachmentLoad::Load)));
+    graph.add_node(Box::new(Node::new("opaque").color(msaa, AttachmentLoad::Clear)));
+
+    let schedule = graph.build_schedule().unwrap();
+    assert_eq!(names(&graph, &schedule), ["opaque", "overlay"]);
+    assert_eq!(schedule.nodes[0].colors[0].resolve_target, Some(AttachmentSlot::Target));
+
+    // formats have to match
+    let mut graph = self::graph();
+    graph.add_attachment(
+        AttachmentDescriptor::new("msaa", FORMAT)
+            .with_sample_count(4)
+            .with_resolve_target(AttachmentId::TARGET),
+    );
+    assert!(graph.build_schedule().is_err());
+}
+
+// clears an attachment of its own, counting how often it runs
+struct CountingNode {
+    scratch: AttachmentId,
+    runs: Rc<Cell<u32>>,
+}
+
+impl RenderNode<Renderer> for CountingNode {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    fn setup(&self, builder: &mut NodeBuilder) {
+        builder.color(self.scratch, AttachmentLoad::Clear);
+    }
+
+    fn run(&self, _renderer: &Renderer, context: &mut NodeContext) {
+        context.begin_render_pass("Counting Pass");
+        self.runs.set(self.runs.get() + 1);
+    }
+}
+
+#[test]
+fn renderer_passes_run_through_the_graph() {
+    let Some(mut app) = common::headless_app(64, 48) else {
+        return;
+    };
+
+    let graph = &app.renderer.graph;
+    let schedule = graph.schedule().unwrap();
+    let order = schedule.order().into_iter()
+        .map(|node| graph.node(node).unwrap().name().to_string())
+        .collect::<Vec<_>>();
+    // auto exposure is only there with compute shaders
+    let order = order.into_iter().filter(|name| name != "auto exposure").collect::<Vec<_>>();
+    assert_eq!(order, [
+        "shadows",
+        "id buffer",
+        "opaque",
+        "skybox",
+        "bloom prefilter",
+        "bloom downsample 1",
+        "bloom downsample 2",
+        "bloom downsample 3",
+        "bloom downsample 4",
+        "bloom upsample 3",
+        "bloom upsample 2",
+        "bloom upsample 1",
+        "bloom upsample 0",
+        "tonemap",
+        "post process",
+        "fxaa",
+        "blit",
+        "overlay",
+        "shadow debug",
+    ]);
+
+    let runs = Rc::new(Cell::new(0));
+    let scratch = app.renderer.graph.add_attachment(AttachmentDescriptor::new("scratch", FORMAT));
+    app.renderer.graph.add_node(Box::new(CountingNode { scratch, runs: runs.clone() }));
+    app.renderer.graph.compile(&app.device).unwrap();
+
+    pollster::block_on(app.render()).unwrap();
+    assert_eq!(runs.get(), 1);
+
+    // attachments follow the size of the target
+    app.resize(32, 16);
+    let resources = app.renderer.graph.resources().unwrap();
+    let Some(AttachmentSlot::Texture(texture)) = app.renderer.graph.schedule().unwrap().slot(scratch) else {
+        panic!("scratch wasn't allocated");
+    };
+    assert_eq!(resources.textures[texture].width(), 32);
+    assert_eq!(resources.textures[texture].height(), 16);
+    assert!(resources.view(scratch).is_some());
+
+    pollster::block_on(app.render()).unwrap();
+    assert_eq!(runs.get(), 2);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use cgmath::*;
+
+use wgpu_renderer::light::MAX_LIGHTS;
+use wgpu_renderer::scene::{InstanceDescription, LightKindDescription, Scene};
+use wgpu_renderer::text::Text;
+
+const EPSILON: f32 = 1e-4;
+
+const SCENE: &str = r#"{
+    "model": {
+        "path": "meshes/cube/cube.obj",
+        "instances": [
+            { "position": [1.0, 0.0, 0.0], "rotation": [0.0, 90.0, 0.0], "tint": [1.0, 0.0, 0.0, 1.0] }
+        ],
+        "grid": { "amount": 2, "spacing": 3.0 }
+    },
+    "hierarchy": "meshes/hierarchy/hierarchy.gltf",
+    "lights": [
+        { "kind": "spot", "position": [0.0, 4.0, 0.0], "outer_cone_angle": 40.0, "cast_shadows": true },
+        { "kind": "directional", "direction": [1.0, -1.0, 0.0], "intensity": 0.5 }
+    ],
+    "camera": { "position": [0.0, 2.0, 8.0], "yaw": -90.0, "pitch": -10.0, "fovy": 60.0 },
+    "clear_color": [0.0, 0.0, 0.0, 1.0],
+    "overlay": {
+        "text": [{ "content": "hello", "position": [4.0, 4.0], "size": 12.0 }]
+    }
+}"#;
+
+#[test]
+fn missing_fields_use_defaults() {
+    let scene = Scene::from_json(SCENE).unwrap();
+
+    assert_eq!(scene.model.unwrap().to_instances().len(), 1 + 4);
+    assert_eq!(scene.hierarchy.unwrap(), "meshes/hierarchy/hierarchy.gltf");
+    assert_eq!(scene.lights[0].kind, LightKindDescription::Spot);
+    assert_eq!(scene.lights[0].inner_cone_angle, 20.0);
+    assert_eq!(scene.camera.znear, 0.1);
+    assert_eq!(scene.overlay.quad, None);
+    assert_eq!(scene.overlay.text[0].color, [1.0; 4]);
+}
+
+#[test]
+fn scenes_survive_a_round_trip() {
+    let scene = Scene::from_json(SCENE).unwrap();
+
+    let json = scene.to_json().unwrap();
+
+    assert_eq!(Scene::from_json(&json).unwrap(), scene);
+}
+
+#[test]
+fn the_default_scene_file_is_valid() {
+    let scene = pollster::block_on(Scene::load("scenes/default.json")).unwrap();
+
+    scene.validate().unwrap();
+    // it starts out the same as the renderer does
+    let initial = Scene::initial();
+    assert_eq!(scene.model, initial.model);
+    assert_eq!(scene.camera, initial.camera);
+    assert_eq!(scene.clear_color, initial.clear_color);
+}
+
+#[test]
+fn instance_rotations_are_in_degrees() {
+    let description = InstanceDescription {
+        rotation: [0.0, 90.0, 0.0],
+        ..Default::default()
+    };
+
+    let instance = description.to_instance();
+    let rotated = instance.rotation.rotate_vector(Vector3::unit_x());
+    assert!((rotated - -Vector3::unit_z()).magnitude() < EPSILON);
+
+    let back = InstanceDescription::from_instance(&instance);
+    assert!((back.rotation[1] - 90.0).abs() < EPSILON);
+}
+
+#[test]
+fn loading_a_scene_replaces_what_is_drawn() {
+    let Some(mut app) = common::headless_app(320, 240) else { return };
+    let scene = Scene::from_json(SCENE).unwrap();
+
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &scene)).unwrap();
+
+    let renderer = &app.renderer;
+    assert_eq!(renderer.instances.len(), 5);
+    assert!(renderer.model_hierarchy.is_some());
+    assert_eq!(renderer.lights.len(), 2);
+    assert!(!renderer.show_quad);
+    assert_eq!(renderer.text.len(), 1);
+    assert!((renderer.camera.pitch - Rad::from(Deg(-10.0))).0.abs() < EPSILON);
+
+    // what is saved back out matches what was loaded, with the grid spelled out
+    let current = renderer.current_scene();
+    let model = current.model.as_ref().unwrap();
+    assert_eq!(model.instances.len(), 5);
+    assert_eq!(model.grid, None);
+    assert_eq!(current.hierarchy, scene.hierarchy);
+    assert_eq!(current.overlay, scene.overlay);
+    assert_eq!(current.lights.len(), 2);
+    assert!((current.camera.fovy - 60.0).abs() < EPSILON);
+    assert!(model.instances[0].tint == [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn loading_a_scene_keeps_text_it_does_not_own() {
+    let Some(mut app) = common::headless_app(320, 240) else { return };
+    let label = app.renderer.text.push(Text::new("fps", [8.0, 8.0], 16.0));
+    let scene = Scene::from_json(SCENE).unwrap();
+
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &scene)).unwrap();
+    assert_eq!(app.renderer.text.get(label).unwrap().content, "fps");
+    assert_eq!(app.renderer.current_scene().overlay.text, scene.overlay.text);
+
+    // the scene's text goes away with it, the label stays
+    let without_text = Scene { overlay: Default::default(), ..scene.clone() };
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &without_text)).unwrap();
+    assert_eq!(app.renderer.text.get(label).unwrap().content, "fps");
+    assert!(app.renderer.current_scene().overlay.text.is_empty());
+
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &scene)).unwrap();
+    assert_eq!(app.renderer.current_scene().overlay.text, scene.overlay.text);
+}
+
+#[test]
+fn scenes_with_too_many_lights_leave_the_renderer_as_it_was() {
+    let Some(mut app) = common::headless_app(320, 240) else { return };
+    let mut scene = Scene::from_json(SCENE).unwrap();
+    scene.lights = vec![scene.lights[0]; MAX_LIGHTS + 1];
+    assert!(scene.validate().is_err());
+
+    let before = app.renderer.current_scene();
+    assert!(pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &scene)).is_err());
+    assert_eq!(app.renderer.current_scene(), before);
+}
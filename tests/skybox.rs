@@ -0,0 +1,276 @@
+mod common;
+
+use cgmath::*;
+
+use wgpu_renderer::app::HeadlessApp;
+use wgpu_renderer::render::{EnvironmentOptions, TonemapOptions, Tonemapper};
+use wgpu_renderer::resources;
+use wgpu_renderer::scene::{Scene, SkyboxDescription};
+use wgpu_renderer::texture::cubemap::{f32_to_f16, face_direction};
+use wgpu_renderer::texture::{Cubemap, Texture};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+const EPSILON: f32 = 1e-4;
+
+// +x red, -x green, +y blue, -y yellow, +z magenta, -z cyan
+const FACE_COLORS: [[f32; 4]; 6] = [
+    [1.0, 0.0, 0.0, 1.0],
+    [0.0, 1.0, 0.0, 1.0],
+    [0.0, 0.0, 1.0, 1.0],
+    [1.0, 1.0, 0.0, 1.0],
+    [1.0, 0.0, 1.0, 1.0],
+    [0.0, 1.0, 1.0, 1.0],
+];
+
+// large enough that filtering across the edges doesn't reach the middle of a face
+fn colored_faces() -> Cubemap {
+    Cubemap {
+        size: 16,
+        faces: FACE_COLORS.map(|color| vec![color; 16 * 16]),
+    }
+}
+
+// only the sky, the clear color shows wherever it isn't drawn
+fn render_sky(app: &mut HeadlessApp) -> image::RgbaImage {
+    app.renderer.show_scene = false;
+    app.renderer.show_overlay = false;
+    app.renderer.clear_color = wgpu::Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    // ACES mixes the channels, Reinhard keeps the face colors apart
+    app.renderer.tonemapping.options = TonemapOptions::default().with_tonemapper(Tonemapper::Reinhard);
+
+    pollster::block_on(app.render()).unwrap()
+}
+
+#[test]
+fn half_floats_round_and_clamp() {
+    assert_eq!(f32_to_f16(0.0), 0x0000);
+    assert_eq!(f32_to_f16(1.0), 0x3c00);
+    assert_eq!(f32_to_f16(-2.0), 0xc000);
+    assert_eq!(f32_to_f16(0.5), 0x3800);
+    assert_eq!(f32_to_f16(65504.0), 0x7bff);
+    // too large for a half float, but still finite
+    assert_eq!(f32_to_f16(1.0e6), 0x7bff);
+    assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+    // the smallest subnormal and below it
+    assert_eq!(f32_to_f16(2.0f32.powi(-24)), 0x0001);
+    assert_eq!(f32_to_f16(1.0e-10), 0x0000);
+}
+
+#[test]
+fn half_floats_keep_signs_nans_and_subnormals() {
+    assert_eq!(f32_to_f16(-0.0), 0x8000);
+    assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+    assert_eq!(f32_to_f16(-1.0e6), 0xfbff);
+    // still a NaN, not an infinity
+    assert_eq!(f32_to_f16(f32::NAN) & 0x7c00, 0x7c00);
+    assert_ne!(f32_to_f16(f32::NAN) & 0x03ff, 0);
+    // subnormals, the largest one and rounding up into the smallest normal
+    assert_eq!(f32_to_f16(2.0f32.powi(-15)), 0x0200);
+    assert_eq!(f32_to_f16(-2.0f32.powi(-15)), 0x8200);
+    assert_eq!(f32_to_f16(1023.0 / 1024.0 * 2.0f32.powi(-14)), 0x03ff);
+    assert_eq!(f32_to_f16(0.99999 * 2.0f32.powi(-14)), 0x0400);
+}
+
+#[test]
+fn face_directions_follow_the_cube_map_conventions() {
+    let centers = (0..6).map(|face| face_direction(face, 0.5, 0.5)).collect::<Vec<_>>();
+    assert_eq!(centers, [
+        Vector3::unit_x(),
+        -Vector3::unit_x(),
+        Vector3::unit_y(),
+        -Vector3::unit_y(),
+        Vector3::unit_z(),
+        -Vector3::unit_z(),
+    ]);
+
+    // the top of the side faces is up, the top of +y is towards -z
+    assert!(face_direction(0, 0.5, 0.0).y > 0.0);
+    assert!(face_direction(4, 0.5, 0.0).y > 0.0);
+    assert!(face_direction(2, 0.5, 0.0).z < 0.0);
+    // looking at +z from inside, +x is on the right
+    assert!(face_direction(4, 1.0, 0.5).x > 0.0);
+}
+
+#[test]
+fn panoramas_are_resampled_onto_the_matching_faces() {
+    // the sky above the horizon is red, below it blue, with a green band just above the
+    // horizon facing +x
+    let panorama = image::Rgba32FImage::from_fn(64, 32, |x, y| {
+        if (10..16).contains(&y) && (28..36).contains(&x) {
+            image::Rgba([0.0, 4.0, 0.0, 1.0])
+        } else if y < 16 {
+            image::Rgba([2.0, 0.0, 0.0, 1.0])
+        } else {
+            image::Rgba([0.0, 0.0, 1.0, 1.0])
+        }
+    });
+    let cubemap = Cubemap::from_equirectangular(&panorama, 8);
+    let pixel = |face: usize, x: u32, y: u32| cubemap.faces[face][(y * cubemap.size + x) as usize];
+
+    assert_eq!(cubemap.size, 8);
+    assert_eq!(pixel(2, 4, 4), [2.0, 0.0, 0.0, 1.0]);
+    assert_eq!(pixel(3, 4, 4), [0.0, 0.0, 1.0, 1.0]);
+    // above the horizon, looking along +x and +z. Values above 1 survive
+    assert_eq!(pixel(0, 4, 2), [0.0, 4.0, 0.0, 1.0]);
+    assert_eq!(pixel(4, 4, 2), [2.0, 0.0, 0.0, 1.0]);
+
+    let levels = cubemap.mip_levels();
+    assert_eq!(levels.len() as u32, cubemap.mip_level_count());
+    assert_eq!(levels.last().unwrap()[2], vec![[2.0, 0.0, 0.0, 1.0]]);
+    assert_eq!(levels.last().unwrap()[3], vec![[0.0, 0.0, 1.0, 1.0]]);
+}
+
+#[test]
+fn faces_have_to_be_six_matching_squares() {
+    let face = |width, height| image::DynamicImage::ImageRgba8(image::RgbaImage::new(width, height));
+
+    assert!(Cubemap::from_faces(&vec![face(4, 4); 5]).is_err());
+    assert!(Cubemap::from_faces(&vec![face(4, 2); 6]).is_err());
+
+    let mut faces = vec![face(4, 4); 6];
+    faces[3] = face(8, 8);
+    assert!(Cubemap::from_faces(&faces).is_err());
+
+    // 8 bit faces are sRGB, white stays white and mid grey gets darker
+    let mut faces = vec![face(2, 2); 6];
+    faces[0] = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 128, 0, 255])));
+    let cubemap = Cubemap::from_faces(&faces).unwrap();
+    let [r, g, b, a] = cubemap.faces[0][0];
+    assert_eq!((r, b, a), (1.0, 0.0, 1.0));
+    assert!((g - 0.2158).abs() < EPSILON, "{}", g);
+}
+
+#[test]
+fn the_sky_panorama_decodes_as_hdr() {
+    let bytes = pollster::block_on(resources::load_binary("skyboxes/sky.hdr")).unwrap();
+    let panorama = Cubemap::equirectangular_from_bytes(&bytes).unwrap();
+
+    assert_eq!(panorama.dimensions(), (512, 256));
+    // the sun is far brighter than anything an 8 bit image holds
+    let brightest = panorama.pixels().map(|pixel| pixel[0]).fold(0.0, f32::max);
+    assert!(brightest > 10.0, "{}", brightest);
+}
+
+#[test]
+fn skyboxes_need_exactly_one_source() {
+    let scene = |skybox| Scene { skybox: Some(skybox), ..Default::default() };
+    let faces = ["px", "nx", "py", "ny", "pz", "nz"].map(|face| format!("skyboxes/{}.png", face));
+
+    assert!(scene(SkyboxDescription::default()).validate().is_err());
+    assert!(scene(SkyboxDescription {
+        equirectangular: Some("skyboxes/sky.hdr".to_string()),
+        faces: Some(faces.clone()),
+        ..Default::default()
+    }).validate().is_err());
+
+    let from_faces = scene(SkyboxDescription { faces: Some(faces), ..Default::default() });
+    assert!(from_faces.validate().is_ok());
+    let json = from_faces.to_json().unwrap();
+    assert_eq!(Scene::from_json(&json).unwrap(), from_faces);
+    assert!(!json.contains("equirectangular"));
+
+    let empty = SkyboxDescription { face_size: 0, ..from_faces.skybox.clone().unwrap() };
+    assert!(scene(empty).validate().is_err());
+}
+
+#[test]
+fn faces_larger_than_the_device_allows_are_errors() {
+    let Some((device, queue)) = common::device() else {
+        return;
+    };
+    let bytes = pollster::block_on(resources::load_binary("skyboxes/sky.hdr")).unwrap();
+    let too_large = device.limits().max_texture_dimension_2d + 1;
+
+    assert!(Texture::from_equirectangular(&device, &queue, &bytes, too_large, "Too Large").is_err());
+    assert!(Texture::from_equirectangular(&device, &queue, &bytes, 0, "Empty").is_err());
+    assert!(Texture::from_equirectangular(&device, &queue, &bytes, 4, "Small").is_ok());
+}
+
+#[test]
+fn the_skybox_replaces_the_clear_color() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+    let center = |image: &image::RgbaImage| image.get_pixel(WIDTH / 2, HEIGHT / 2).0;
+
+    // nothing to draw until a cubemap is set
+    assert!(!app.renderer.environment.draws_skybox());
+    assert_eq!(center(&render_sky(&mut app)), [0, 0, 0, 255]);
+
+    let cubemap = Texture::from_cubemap(&app.device, &app.queue, &colored_faces(), "Colored Faces");
+    app.renderer.environment.set_cubemap(&app.device, cubemap);
+    // the camera starts out looking down -z, a little from above
+    let [r, g, b, _] = center(&render_sky(&mut app));
+    assert!(r < 16 && g > 128 && b > 128, "{:?}", [r, g, b]);
+
+    app.renderer.environment.options = EnvironmentOptions::default().with_skybox(false);
+    assert_eq!(center(&render_sky(&mut app)), [0, 0, 0, 255]);
+}
+
+#[test]
+fn the_sky_turns_with_the_camera_but_never_moves() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+
+    let cubemap = Texture::from_cubemap(&app.device, &app.queue, &colored_faces(), "Colored Faces");
+    app.renderer.environment.set_cubemap(&app.device, cubemap);
+    app.renderer.camera.pitch = Deg(0.0).into();
+
+    let before = render_sky(&mut app);
+    app.renderer.camera.position += Vector3::new(50.0, -20.0, 30.0);
+    let moved = render_sky(&mut app);
+    assert_eq!(before, moved);
+
+    // facing +x
+    app.renderer.camera.yaw = Deg(0.0).into();
+    let [r, g, b, _] = render_sky(&mut app).get_pixel(WIDTH / 2, HEIGHT / 2).0;
+    assert!(r > 128 && g < 16 && b < 16, "{:?}", [r, g, b]);
+}
+
+#[test]
+fn models_reflect_the_environment() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+    let brightness = |image: &image::RgbaImage| image.pixels().map(|pixel| pixel[0] as u64 + pixel[1] as u64 + pixel[2] as u64).sum::<u64>();
+    app.renderer.show_overlay = false;
+
+    let plain = pollster::block_on(app.render()).unwrap();
+
+    let white = Cubemap::from_color([1.0, 1.0, 1.0, 1.0]);
+    let cubemap = Texture::from_cubemap(&app.device, &app.queue, &white, "White");
+    app.renderer.environment.set_cubemap(&app.device, cubemap);
+    // without the sky only the model changes
+    app.renderer.environment.options = EnvironmentOptions::default().with_skybox(false);
+    let reflecting = pollster::block_on(app.render()).unwrap();
+    assert!(brightness(&reflecting) > brightness(&plain));
+    assert_eq!(plain.get_pixel(0, 0), reflecting.get_pixel(0, 0));
+
+    app.renderer.environment.options = app.renderer.environment.options.with_reflection_intensity(0.0);
+    let off = pollster::block_on(app.render()).unwrap();
+    assert_eq!(off, plain);
+}
+
+#[test]
+fn scenes_load_and_clear_their_skybox() {
+    let Some(mut app) = common::headless_app(WIDTH, HEIGHT) else {
+        return;
+    };
+
+    let scene = pollster::block_on(Scene::load("scenes/default.json")).unwrap();
+    let skybox = scene.skybox.clone().unwrap();
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &scene)).unwrap();
+    assert!(app.renderer.environment.draws_skybox());
+    let size = app.renderer.environment.cubemap.texture.size();
+    assert_eq!((size.width, size.height, size.depth_or_array_layers), (skybox.face_size, skybox.face_size, 6));
+
+    app.renderer.environment.options.intensity = 2.0;
+    assert_eq!(app.renderer.current_scene().skybox.unwrap().intensity, 2.0);
+
+    let without = Scene { skybox: None, ..scene };
+    pollster::block_on(app.renderer.load_scene(&app.device, &app.queue, &without)).unwrap();
+    assert!(!app.renderer.environment.has_cubemap());
+    assert_eq!(app.renderer.current_scene().skybox, None);
+}
@@ -0,0 +1,67 @@
+use wgpu_renderer::sprite::{batch_sprites, uv_rect, Sprite, SpriteDraw, SpriteRect};
+
+const SHEET: (u32, u32) = (420, 420);
+
+#[test]
+fn sub_rects_are_addressed_in_pixels() {
+    let rect = SpriteRect::from_grid(1, 0, 210.0, 210.0);
+    assert_eq!(rect, SpriteRect::new(210.0, 0.0, 210.0, 210.0));
+
+    let (uv_min, uv_max) = uv_rect(&rect, SHEET.0, SHEET.1);
+    assert_eq!(uv_min, [0.5, 0.0]);
+    assert_eq!(uv_max, [1.0, 0.5]);
+}
+
+#[test]
+fn sprites_are_grouped_by_atlas() {
+    let sprites = (0..6)
+        .map(|i| Sprite::new(i % 2, [i as f32, 0.0], [16.0, 16.0]))
+        .collect::<Vec<_>>();
+
+    let (instances, draws) = batch_sprites(&sprites, &[SHEET, (64, 64)]);
+
+    assert_eq!(instances.len(), 6);
+    assert_eq!(draws, vec![
+        SpriteDraw { atlas: 0, instances: 0..3 },
+        SpriteDraw { atlas: 1, instances: 3..6 },
+    ]);
+    // the order sprites were added in is kept within a batch
+    let xs = instances.iter().map(|instance| instance.position[0]).collect::<Vec<_>>();
+    assert_eq!(xs, vec![0.0, 2.0, 4.0, 1.0, 3.0, 5.0]);
+}
+
+#[test]
+fn layers_are_drawn_in_order() {
+    let sprites = vec![
+        Sprite::new(0, [0.0, 0.0], [16.0, 16.0]).with_layer(2),
+        Sprite::new(1, [1.0, 0.0], [16.0, 16.0]).with_layer(1),
+        Sprite::new(0, [2.0, 0.0], [16.0, 16.0]).with_layer(1),
+    ];
+
+    let (instances, draws) = batch_sprites(&sprites, &[SHEET, SHEET]);
+
+    // layer 1 first even though it uses both atlases, then layer 2
+    assert_eq!(draws, vec![
+        SpriteDraw { atlas: 0, instances: 0..1 },
+        SpriteDraw { atlas: 1, instances: 1..2 },
+        SpriteDraw { atlas: 0, instances: 2..3 },
+    ]);
+    assert_eq!(instances[2].position, [0.0, 0.0]);
+}
+
+#[test]
+fn sprites_without_an_atlas_are_skipped() {
+    let sprites = vec![
+        Sprite::new(3, [0.0, 0.0], [16.0, 16.0]),
+        Sprite::new(0, [0.0, 0.0], [16.0, 16.0]).with_color([1.0, 0.0, 0.0, 1.0]),
+    ];
+
+    let (instances, draws) = batch_sprites(&sprites, &[SHEET]);
+
+    assert_eq!(instances.len(), 1);
+    assert_eq!(draws.len(), 1);
+    // no source means the whole atlas
+    assert_eq!(instances[0].uv_min, [0.0, 0.0]);
+    assert_eq!(instances[0].uv_max, [1.0, 1.0]);
+    assert_eq!(instances[0].color, [1.0, 0.0, 0.0, 1.0]);
+}
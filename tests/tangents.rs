@@ -0,0 +1,184 @@
+use cgmath::*;
+
+use wgpu_renderer::model::{generate_tangents, ModelVertex};
+use wgpu_renderer::resources::obj_vertices;
+
+const EPSILON: f32 = 1e-4;
+
+fn load_obj(path: &str) -> Vec<(Vec<ModelVertex>, Vec<u32>)> {
+    let path = format!("{}/assets/meshes/{}", env!("CARGO_MANIFEST_DIR"), path);
+    let (models, _) = tobj::load_obj(&path, &tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    }).unwrap();
+
+    models.iter()
+        .map(|model| {
+            let mut vertices = obj_vertices(&model.mesh);
+            let mut indices = model.mesh.indices.clone();
+            generate_tangents(&mut vertices, &mut indices);
+
+            (vertices, indices)
+        })
+        .collect()
+}
+
+fn vertex(position: [f32; 3], tex_coords: [f32; 2]) -> ModelVertex {
+    ModelVertex {
+        position,
+        tex_coords,
+        normal: [0.0, 0.0, 1.0],
+        tangent: [0.0; 4],
+    }
+}
+
+fn tangent(vertex: &ModelVertex) -> Vector3<f32> {
+    Vector3::new(vertex.tangent[0], vertex.tangent[1], vertex.tangent[2])
+}
+
+fn bitangent(vertex: &ModelVertex) -> Vector3<f32> {
+    Vector3::from(vertex.normal).cross(tangent(vertex)) * vertex.tangent[3]
+}
+
+// (d position / d u, d position / d v) of a triangle, None for degenerate uvs
+fn triangle_derivatives(vertices: &[ModelVertex], triangle: &[u32]) -> Option<(Vector3<f32>, Vector3<f32>)> {
+    let [v0, v1, v2] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+    let delta_pos1 = Vector3::from(v1.position) - Vector3::from(v0.position);
+    let delta_pos2 = Vector3::from(v2.position) - Vector3::from(v0.position);
+    let delta_uv1 = Vector2::from(v1.tex_coords) - Vector2::from(v0.tex_coords);
+    let delta_uv2 = Vector2::from(v2.tex_coords) - Vector2::from(v0.tex_coords);
+
+    let area = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+    if area.abs() < 1e-8 {
+        return None;
+    }
+
+    let dp_du = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) / area;
+    let dp_dv = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) / area;
+
+    Some((dp_du, dp_dv))
+}
+
+fn assert_valid_tangent_space(vertices: &[ModelVertex]) {
+    for vertex in vertices {
+        let normal = Vector3::from(vertex.normal).normalize();
+        let tangent = tangent(vertex);
+
+        assert!((tangent.magnitude() - 1.0).abs() < EPSILON, "tangent isn't unit length: {:?}", vertex);
+        assert!(tangent.dot(normal).abs() < EPSILON, "tangent isn't perpendicular to the normal: {:?}", vertex);
+        assert!(vertex.tangent[3] == 1.0 || vertex.tangent[3] == -1.0, "bad handedness: {:?}", vertex);
+    }
+}
+
+#[test]
+fn cube_tangents_follow_the_uvs_of_every_face() {
+    for (vertices, indices) in load_obj("cube/cube.obj") {
+        assert_valid_tangent_space(&vertices);
+
+        for triangle in indices.chunks(3) {
+            let (dp_du, dp_dv) = triangle_derivatives(&vertices, triangle).unwrap();
+            let face_normal = dp_du.cross(dp_dv).normalize();
+
+            for &index in triangle {
+                let vertex = &vertices[index as usize];
+                assert!(tangent(vertex).dot(dp_du) > 0.0, "{:?} {:?}", vertex, dp_du);
+                assert!(bitangent(vertex).dot(dp_dv) > 0.0, "{:?} {:?}", vertex, dp_dv);
+                assert_eq!(vertex.tangent[3], 1.0);
+
+                // the faces are flat away from the bevelled edges, so the tangent space there is
+                // exactly the one of the triangles
+                if Vector3::from(vertex.normal).normalize().dot(face_normal) > 0.9999 {
+                    assert!((tangent(vertex) - dp_du.normalize()).magnitude() < 1e-3, "{:?} {:?}", vertex, dp_du);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn monkey_tangents_are_orthonormal_with_consistent_handedness() {
+    for (vertices, indices) in load_obj("monkey/lp-monkey.obj") {
+        assert_valid_tangent_space(&vertices);
+
+        for triangle in indices.chunks(3) {
+            let Some((dp_du, dp_dv)) = triangle_derivatives(&vertices, triangle) else {
+                continue;
+            };
+            let [p0, p1, p2] = [0, 1, 2].map(|i| Vector3::from(vertices[triangle[i] as usize].position));
+            let face_normal = (p1 - p0).cross(p2 - p0);
+            // mirrored uvs have a negative handedness, every corner agrees with its triangle
+            let handedness = dp_du.cross(dp_dv).dot(face_normal).signum();
+
+            for &index in triangle {
+                let vertex = &vertices[index as usize];
+                // a few folds have smoothed normals facing away from their triangle
+                if Vector3::from(vertex.normal).dot(face_normal) <= 0.0 {
+                    continue;
+                }
+                assert_eq!(vertex.tangent[3], handedness, "{:?}", vertex);
+                // smoothed, but still pointing along the triangle's u
+                assert!(tangent(vertex).dot(dp_du) > 0.0, "{:?} {:?}", vertex, dp_du);
+            }
+        }
+    }
+}
+
+#[test]
+fn shared_vertices_average_their_triangles() {
+    // a quad folded along its diagonal, both halves have u along x
+    let mut vertices = vec![
+        vertex([0.0, 0.0, 0.0], [0.0, 0.0]),
+        vertex([1.0, 0.0, 0.0], [1.0, 0.0]),
+        vertex([1.0, 1.0, 0.0], [1.0, 1.0]),
+        vertex([0.0, 1.0, 0.0], [0.0, 1.0]),
+    ];
+    let mut indices = vec![0, 1, 2, 0, 2, 3];
+
+    generate_tangents(&mut vertices, &mut indices);
+
+    assert_eq!(vertices.len(), 4);
+    for vertex in &vertices {
+        assert!((tangent(vertex) - Vector3::unit_x()).magnitude() < EPSILON, "{:?}", vertex);
+        assert!((bitangent(vertex) - Vector3::unit_y()).magnitude() < EPSILON, "{:?}", vertex);
+    }
+}
+
+#[test]
+fn mirrored_uvs_split_the_shared_vertices() {
+    // the right triangle's u runs the other way, like a mirrored half of a face
+    let mut vertices = vec![
+        vertex([-1.0, 0.0, 0.0], [0.0, 0.0]),
+        vertex([0.0, 0.0, 0.0], [1.0, 0.0]),
+        vertex([0.0, 1.0, 0.0], [1.0, 1.0]),
+        vertex([1.0, 0.0, 0.0], [0.0, 0.0]),
+    ];
+    let mut indices = vec![0, 1, 2, 1, 3, 2];
+
+    generate_tangents(&mut vertices, &mut indices);
+
+    // the shared edge got a copy for the mirrored side
+    assert_eq!(vertices.len(), 6);
+    for &index in &indices[..3] {
+        assert_eq!(vertices[index as usize].tangent, [1.0, 0.0, 0.0, 1.0]);
+    }
+    for &index in &indices[3..] {
+        assert_eq!(vertices[index as usize].tangent, [-1.0, 0.0, 0.0, -1.0]);
+        assert!((bitangent(&vertices[index as usize]) - Vector3::unit_y()).magnitude() < EPSILON);
+    }
+}
+
+#[test]
+fn degenerate_uvs_still_get_a_tangent() {
+    let mut vertices = vec![
+        vertex([0.0, 0.0, 0.0], [0.5, 0.5]),
+        vertex([1.0, 0.0, 0.0], [0.5, 0.5]),
+        vertex([0.0, 1.0, 0.0], [0.5, 0.5]),
+    ];
+    let mut indices = vec![0, 1, 2];
+
+    generate_tangents(&mut vertices, &mut indices);
+
+    assert_valid_tangent_space(&vertices);
+    assert!(vertices.iter().flat_map(|vertex| vertex.tangent).all(f32::is_finite));
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use wgpu_renderer::text::{layout_text, signed_distance_field, Font, GlyphAtlas, GlyphMode, Text, TextOptions};
+
+fn font() -> Font {
+    pollster::block_on(Font::load("fonts/DejaVuSans.ttf")).unwrap()
+}
+
+#[test]
+fn pairs_are_kerned() {
+    let font = font();
+    let (a, v) = (font.glyph_id('A'), font.glyph_id('V'));
+    assert!(font.kern(a, v, 32.0) < 0.0);
+
+    let layout = layout_text(&font, &Text::new("AV", [0.0, 0.0], 32.0));
+    let second = layout.glyphs[1].position[0];
+    assert!((second - (font.advance(a, 32.0) + font.kern(a, v, 32.0))).abs() < 1e-4);
+}
+
+#[test]
+fn glyphs_sit_on_the_baseline() {
+    let font = font();
+    let metrics = font.metrics(20.0);
+
+    let layout = layout_text(&font, &Text::new("a b\nc", [10.0, 5.0], 20.0));
+
+    // the space doesn't get a glyph
+    let characters = layout.glyphs.iter().map(|glyph| glyph.character).collect::<String>();
+    assert_eq!(characters, "abc");
+    assert_eq!(layout.lines, 2);
+    assert_eq!(layout.glyphs[0].position, [10.0, 5.0 + metrics.ascent]);
+    assert_eq!(layout.glyphs[2].position, [10.0, 5.0 + metrics.ascent + metrics.line_height()]);
+    assert!((layout.size[1] - metrics.line_height() * 2.0).abs() < 1e-4);
+}
+
+#[test]
+fn long_lines_wrap_between_words() {
+    let font = font();
+    let text = Text::new("the quick brown fox", [0.0, 0.0], 16.0);
+    let single_line = layout_text(&font, &text);
+    assert_eq!(single_line.lines, 1);
+
+    let wrapped = layout_text(&font, &text.clone().with_max_width(single_line.size[0] * 0.6));
+    assert_eq!(wrapped.lines, 2);
+    assert!(wrapped.size[0] <= single_line.size[0] * 0.6);
+
+    // every line starts at the left edge with the first letter of a word
+    let line_starts = wrapped.glyphs.iter()
+        .filter(|glyph| glyph.position[0] == 0.0)
+        .map(|glyph| glyph.character)
+        .collect::<String>();
+    assert_eq!(line_starts, "tb");
+}
+
+#[test]
+fn words_wider_than_a_line_are_broken() {
+    let font = font();
+    let text = Text::new("abcdefghij", [0.0, 0.0], 16.0);
+    let width = layout_text(&font, &text).size[0];
+
+    let wrapped = layout_text(&font, &text.with_max_width(width / 3.0));
+
+    assert!(wrapped.lines >= 3);
+    assert_eq!(wrapped.glyphs.len(), 10);
+    assert!(wrapped.size[0] <= width / 3.0);
+}
+
+#[test]
+fn distance_fields_are_half_on_the_outline() {
+    // the left half of a 12x1 strip is inside
+    let coverage = [255, 255, 255, 255, 255, 255, 0, 0, 0, 0, 0, 0];
+
+    let field = signed_distance_field(&coverage, 12, 1, 4.0);
+
+    // falls off across the edge
+    assert!(field.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert!(field[5] > 128 && field[6] < 128);
+    assert_eq!(field[5] as i32 + field[6] as i32, 255);
+    // and is clamped past the spread
+    assert_eq!(field[0], 255);
+    assert_eq!(field[11], 0);
+}
+
+#[test]
+fn glyphs_are_rasterized_once() {
+    let Some(app) = common::headless_app(320, 240) else { return };
+    let font = font();
+    let layout = GlyphAtlas::create_bind_group_layout(&app.device);
+    let a = font.glyph_id('a');
+
+    let mut bitmaps = GlyphAtlas::new(&app.device, &layout, &TextOptions::default());
+    let small = bitmaps.glyph(&font, a, 12.0).unwrap();
+    assert_eq!(bitmaps.glyph(&font, a, 12.0), Some(small));
+    let large = bitmaps.glyph(&font, a, 24.0).unwrap();
+    assert!(large.rect.width > small.rect.width);
+    // nothing to draw for a space
+    assert_eq!(bitmaps.glyph(&font, font.glyph_id(' '), 12.0), None);
+    assert_eq!(bitmaps.len(), 2);
+
+    // a distance field is shared between sizes
+    let mut fields = GlyphAtlas::new(&app.device, &layout, &TextOptions::default().with_mode(GlyphMode::sdf()));
+    let glyph = fields.glyph(&font, a, 12.0).unwrap();
+    assert_eq!(fields.glyph(&font, a, 96.0), Some(glyph));
+    assert_eq!(glyph.size, 48.0);
+    assert_eq!(fields.len(), 1);
+    fields.upload(&app.queue);
+}
+
+#[test]
+fn a_full_atlas_skips_glyphs() {
+    let Some(app) = common::headless_app(320, 240) else { return };
+    let font = font();
+    let layout = GlyphAtlas::create_bind_group_layout(&app.device);
+    let mut atlas = GlyphAtlas::new(&app.device, &layout, &TextOptions::default().with_atlas_size(64));
+
+    assert!(atlas.glyph(&font, font.glyph_id('W'), 48.0).is_some());
+    assert!(atlas.glyph(&font, font.glyph_id('M'), 48.0).is_none());
+
+    atlas.clear();
+    assert!(atlas.glyph(&font, font.glyph_id('M'), 48.0).is_some());
+}
@@ -0,0 +1,64 @@
+use wgpu_renderer::texture::{ColorSpace, MipmapGeneration, TextureFiltering, TextureOptions};
+
+// a glTF document with only samplers in it
+fn samplers(json: &str) -> gltf::Gltf {
+    let document = format!(r#"{{"asset": {{"version": "2.0"}}, "samplers": {}}}"#, json);
+
+    gltf::Gltf::from_slice(document.as_bytes()).unwrap()
+}
+
+#[test]
+fn gltf_sampler_sets_wrap_modes_and_filters() {
+    let gltf = samplers(r#"[{"wrapS": 33648, "wrapT": 33071, "magFilter": 9728, "minFilter": 9985}]"#);
+    let sampler = gltf.samplers().next().unwrap();
+
+    let options = TextureOptions::default().with_gltf_sampler(&sampler);
+
+    assert_eq!(options.address_mode_u, wgpu::AddressMode::MirrorRepeat);
+    assert_eq!(options.address_mode_v, wgpu::AddressMode::ClampToEdge);
+    assert_eq!(options.mag_filter, wgpu::FilterMode::Nearest);
+    // LINEAR_MIPMAP_NEAREST
+    assert_eq!(options.min_filter, wgpu::FilterMode::Linear);
+    assert_eq!(options.mipmap_filter, wgpu::FilterMode::Nearest);
+    assert_eq!(options.mipmaps, MipmapGeneration::Auto);
+}
+
+#[test]
+fn gltf_sampler_without_mipmap_filter_disables_mipmaps() {
+    let gltf = samplers(r#"[{"minFilter": 9729}]"#);
+    let sampler = gltf.samplers().next().unwrap();
+
+    let options = TextureOptions::linear().with_gltf_sampler(&sampler);
+
+    assert_eq!(options.mipmaps, MipmapGeneration::None);
+    // the sampler doesn't change how the texture is stored
+    assert_eq!(options.color_space, ColorSpace::Linear);
+    assert_eq!(options.format(), wgpu::TextureFormat::Rgba8Unorm);
+}
+
+#[test]
+fn gltf_sampler_defaults_to_repeat_and_keeps_filters() {
+    let gltf = samplers("[{}]");
+    let sampler = gltf.samplers().next().unwrap();
+
+    let options = TextureOptions::default()
+        .with_filtering(TextureFiltering::Bilinear)
+        .with_gltf_sampler(&sampler);
+
+    assert_eq!(options.address_mode_u, wgpu::AddressMode::Repeat);
+    assert_eq!(options.address_mode_v, wgpu::AddressMode::Repeat);
+    assert_eq!(options.min_filter, wgpu::FilterMode::Linear);
+    assert_eq!(options.mipmap_filter, wgpu::FilterMode::Nearest);
+}
+
+#[test]
+fn anisotropy_only_applies_when_every_filter_is_linear() {
+    let anisotropic = TextureOptions::default().with_filtering(TextureFiltering::Anisotropic(32));
+    assert_eq!(anisotropic.sampler_descriptor(None).anisotropy_clamp, 16);
+
+    let nearest = TextureOptions {
+        mag_filter: wgpu::FilterMode::Nearest,
+        ..anisotropic
+    };
+    assert_eq!(nearest.sampler_descriptor(None).anisotropy_clamp, 1);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use wgpu_renderer::app::HeadlessApp;
+use wgpu_renderer::render::tonemap::{HistogramParams, TonemapUniform};
+use wgpu_renderer::render::{AutoExposure, TonemapOptions, Tonemapper};
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// only the clear color, at `value` in every channel
+fn render_clear_color(app: &mut HeadlessApp, value: f64, options: TonemapOptions) -> u8 {
+    app.renderer.show_scene = false;
+    app.renderer.show_overlay = false;
+    app.renderer.clear_color = wgpu::Color { r: value, g: value, b: value, a: 1.0 };
+    app.renderer.tonemapping.options = options;
+
+    let image = pollster::block_on(app.render()).unwrap();
+    image.get_pixel(32, 24)[0]
+}
+
+#[test]
+fn tonemappers_cycle_and_exposure_is_in_stops() {
+    assert_eq!(Tonemapper::default(), Tonemapper::Aces);
+    assert_eq!(Tonemapper::Aces.next(), Tonemapper::Reinhard);
+    assert_eq!(Tonemapper::Reinhard.next(), Tonemapper::AgX);
+    assert_eq!(Tonemapper::AgX.next(), Tonemapper::Aces);
+
+    let mut uniform = TonemapUniform::new();
+    uniform.update(&TonemapOptions::default().with_exposure(-2.0).with_tonemapper(Tonemapper::AgX), false);
+    assert_eq!(uniform.exposure, 0.25);
+    assert_eq!(uniform.tonemapper, 2);
+    assert_eq!(uniform.auto_exposure, 0);
+}
+
+#[test]
+fn histogram_adaptation_follows_the_speed() {
+    let instant = AutoExposure { adaptation_speed: f32::INFINITY, ..Default::default() };
+    assert_eq!(HistogramParams::new(&instant, 0.0, (4, 4)).adaptation, 1.0);
+
+    let params = HistogramParams::new(&AutoExposure::default(), 0.0, (4, 4));
+    assert_eq!(params.adaptation, 0.0);
+    assert_eq!(params.pixel_count, 16);
+    assert_eq!(params.log_luminance_range, 12.0);
+
+    let slow = HistogramParams::new(&AutoExposure::default(), 1.0, (4, 4)).adaptation;
+    assert!(slow > 0.5 && slow < 1.0, "{}", slow);
+}
+
+#[test]
+fn values_above_one_are_not_clipped() {
+    let Some(mut app) = common::headless_app(64, 48) else {
+        return;
+    };
+
+    let reinhard = TonemapOptions::default().with_tonemapper(Tonemapper::Reinhard);
+    // 4 / (1 + 4), and two stops down 1 / (1 + 1)
+    let bright = render_clear_color(&mut app, 4.0, reinhard);
+    assert!(bright.abs_diff(linear_to_srgb(0.8)) <= 2, "{}", bright);
+    let darker = render_clear_color(&mut app, 4.0, reinhard.with_exposure(-2.0));
+    assert!(darker.abs_diff(linear_to_srgb(0.5)) <= 2, "{}", darker);
+
+    // every curve keeps 4.0 and 16.0 apart and below white
+    for tonemapper in [Tonemapper::Aces, Tonemapper::Reinhard, Tonemapper::AgX] {
+        let options = TonemapOptions::default().with_tonemapper(tonemapper);
+        let four = render_clear_color(&mut app, 4.0, options);
+        let sixteen = render_clear_color(&mut app, 16.0, options);
+        assert!(four < sixteen, "{:?}: {} {}", tonemapper, four, sixteen);
+        assert!(four < 255, "{:?}: {}", tonemapper, four);
+    }
+}
+
+#[test]
+fn auto_exposure_brings_the_scene_to_mid_grey() {
+    let Some(mut app) = common::headless_app(64, 48) else {
+        return;
+    };
+
+    if app.renderer.tonemapping.histogram.is_none() {
+        common::skip("no compute shader support");
+        return;
+    }
+
+    let auto_exposure = AutoExposure { adaptation_speed: f32::INFINITY, ..Default::default() };
+    let options = TonemapOptions::default()
+        .with_tonemapper(Tonemapper::Reinhard)
+        .with_auto_exposure(auto_exposure);
+
+    // a scene at 4.0 and one at 0.25 both end up close to 0.18 / (1 + 0.18)
+    let expected = linear_to_srgb(0.18 / 1.18);
+    for value in [4.0, 0.25] {
+        let exposed = render_clear_color(&mut app, value, options);
+        assert!(exposed.abs_diff(expected) <= 6, "{}: {} != {}", value, exposed, expected);
+    }
+}